@@ -1,5 +1,11 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=src/proto/cdk_ldk_management.proto");
-    tonic_build::compile_protos("src/proto/cdk_ldk_management.proto")?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("cdk_ldk_management_descriptor.bin"))
+        .compile_protos(&["src/proto/cdk_ldk_management.proto"], &["src/proto"])?;
     Ok(())
 }
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ldk_node::bitcoin::hashes::{Hash, sha256};
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// How far a proof of work stamp's timestamp may be from our clock
+pub const POW_STAMP_WINDOW_SECS: u64 = 600;
+
+/// What `POST /channel-quote` requires before creating a quote
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AntiSpam {
+    /// Hashcash style stamp with at least `difficulty_bits` leading zero bits
+    Pow { difficulty_bits: u32 },
+    /// Non-refundable ecash token worth at least `amount_sats`
+    Bond { amount_sats: u64 },
+}
+
+/// A valid proof of work stamp, identified by its digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowStamp {
+    digest: [u8; 32],
+    /// Unix time after which the stamp is outside the window anyway
+    expires_at: u64,
+}
+
+/// Check a `<timestamp>:<nonce>` proof of work stamp for a quote request
+///
+/// The stamp commits to the requesting node and channel size, the digest is
+/// `sha256("<node_pubkey>:<channel_size_sats>:<timestamp>:<nonce>")`. Returns
+/// `None` if the stamp is malformed, outside the window or too easy.
+pub fn verify_pow(
+    stamp: &str,
+    node_pubkey: &PublicKey,
+    channel_size_sats: u64,
+    difficulty_bits: u32,
+    now: u64,
+) -> Option<PowStamp> {
+    let (timestamp, _nonce) = stamp.split_once(':')?;
+
+    let timestamp = timestamp.parse::<u64>().ok()?;
+
+    if now.abs_diff(timestamp) > POW_STAMP_WINDOW_SECS {
+        return None;
+    }
+
+    let preimage = format!("{}:{}:{}", node_pubkey, channel_size_sats, stamp);
+    let digest = sha256::Hash::hash(preimage.as_bytes());
+
+    if leading_zero_bits(digest.as_byte_array()) < difficulty_bits {
+        return None;
+    }
+
+    Some(PowStamp {
+        digest: digest.to_byte_array(),
+        expires_at: timestamp.saturating_add(POW_STAMP_WINDOW_SECS),
+    })
+}
+
+/// Stamps already used for a quote, kept until they leave the window
+///
+/// Held in memory only, a restart forgets them but stamps expire within
+/// [`POW_STAMP_WINDOW_SECS`] of their timestamp anyway.
+#[derive(Debug, Clone, Default)]
+pub struct SpentStamps {
+    spent: Arc<Mutex<HashMap<[u8; 32], u64>>>,
+}
+
+impl SpentStamps {
+    /// Whether `stamp` was already used
+    pub fn contains(&self, stamp: &PowStamp) -> bool {
+        self.spent
+            .lock()
+            .expect("Spent stamps poisoned")
+            .contains_key(&stamp.digest)
+    }
+
+    /// Record `stamp` as used, `false` if it already was
+    pub fn spend(&self, stamp: PowStamp, now: u64) -> bool {
+        let mut spent = self.spent.lock().expect("Spent stamps poisoned");
+
+        spent.retain(|_, expires_at| *expires_at >= now);
+
+        spent.insert(stamp.digest, stamp.expires_at).is_none()
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+
+    for byte in bytes {
+        bits += byte.leading_zeros();
+
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn node_pubkey() -> PublicKey {
+        PublicKey::from_str("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+            .unwrap()
+    }
+
+    /// Search for a stamp at `timestamp` meeting `difficulty_bits`
+    fn mine(timestamp: u64, difficulty_bits: u32) -> String {
+        (0u64..)
+            .map(|nonce| format!("{}:{}", timestamp, nonce))
+            .find(|stamp| {
+                verify_pow(stamp, &node_pubkey(), 100_000, difficulty_bits, timestamp).is_some()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_stamp() {
+        let stamp = mine(NOW, 8);
+
+        assert!(verify_pow(&stamp, &node_pubkey(), 100_000, 8, NOW).is_some());
+        // Committed to the channel size
+        assert!(verify_pow(&stamp, &node_pubkey(), 200_000, 8, NOW).is_none());
+    }
+
+    #[test]
+    fn rejects_too_few_bits() {
+        let stamp = mine(NOW, 8);
+
+        assert!(verify_pow(&stamp, &node_pubkey(), 100_000, 256, NOW).is_none());
+    }
+
+    #[test]
+    fn rejects_stamp_outside_window() {
+        let stamp = mine(NOW, 8);
+
+        let late = NOW + POW_STAMP_WINDOW_SECS + 1;
+        let early = NOW - POW_STAMP_WINDOW_SECS - 1;
+
+        assert!(verify_pow(&stamp, &node_pubkey(), 100_000, 8, late).is_none());
+        assert!(verify_pow(&stamp, &node_pubkey(), 100_000, 8, early).is_none());
+        assert!(verify_pow("not a stamp", &node_pubkey(), 100_000, 0, NOW).is_none());
+    }
+
+    #[test]
+    fn rejects_replayed_stamp() {
+        let spent = SpentStamps::default();
+        let stamp = verify_pow(&mine(NOW, 8), &node_pubkey(), 100_000, 8, NOW).unwrap();
+
+        assert!(!spent.contains(&stamp));
+        assert!(spent.spend(stamp, NOW));
+        assert!(spent.contains(&stamp));
+        assert!(!spent.spend(stamp, NOW + POW_STAMP_WINDOW_SECS));
+
+        // Forgotten once it can no longer verify
+        assert!(spent.spend(stamp, NOW + POW_STAMP_WINDOW_SECS + 1));
+    }
+}
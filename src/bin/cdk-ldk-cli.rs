@@ -1,7 +1,12 @@
+use std::io::Write;
 use std::path::PathBuf;
 
 use anyhow::Result;
+use cdk_ldk_node::proto::auth::ClientToken;
 use cdk_ldk_node::proto::client::CdkLdkClient;
+use cdk_ldk_node::proto::{
+    ChannelOptions, FeeRevenue, PeerAccessLevel, QuoteRecord, QuoteStatus, RefundRecord,
+};
 use clap::{Parser, Subcommand};
 use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
 
@@ -14,6 +19,10 @@ struct Cli {
     #[arg(short, long, default_value = "~/.cdk-ldk-cli")]
     work_dir: String,
 
+    /// Bearer token for servers with auth_tokens configured
+    #[arg(long)]
+    token: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -31,11 +40,20 @@ enum Commands {
         #[arg(long)]
         address: String,
         #[arg(short, long)]
-        port: u32,
+        port: u16,
         #[arg(long)]
-        amount_msats: u64,
+        amount_sats: u64,
         #[arg(long)]
         push_msats: Option<u64>,
+        /// Open an unannounced channel
+        #[arg(long)]
+        private: bool,
+        #[arg(long)]
+        forwarding_fee_base_msat: Option<u32>,
+        #[arg(long)]
+        forwarding_fee_ppm: Option<u32>,
+        #[arg(long)]
+        cltv_expiry_delta: Option<u16>,
     },
     /// Close a channel
     CloseChannel {
@@ -43,16 +61,313 @@ enum Commands {
         channel_id: String,
         #[arg(short, long)]
         node_pubkey: String,
+        /// Force close, for peers that are unresponsive
+        #[arg(long)]
+        force: bool,
+        /// Reason recorded by the node when force closing
+        #[arg(long, requires = "force")]
+        reason: Option<String>,
+    },
+    /// Update forwarding fees and CLTV delta of a channel
+    UpdateChannelConfig {
+        #[arg(short, long)]
+        channel_id: String,
+        #[arg(short, long)]
+        node_pubkey: String,
+        #[arg(long)]
+        forwarding_fee_base_msat: Option<u32>,
+        #[arg(long)]
+        forwarding_fee_ppm: Option<u32>,
+        #[arg(long)]
+        cltv_expiry_delta: Option<u16>,
     },
     /// List balances
     ListBalance,
+    /// List ecash balances by mint and unit
+    ListCashuBalances,
+    /// Recover ecash from the wallet seed at every mint
+    RestoreWallets,
+    /// Take ecash out of the LSP's wallet as a cashu token
+    ExportProofs {
+        #[arg(short, long)]
+        mint_url: String,
+        /// Defaults to sat
+        #[arg(short, long)]
+        unit: Option<String>,
+        #[arg(short, long)]
+        amount: u64,
+        /// Lock the token to this public key
+        #[arg(long)]
+        p2pk_pubkey: Option<String>,
+        #[arg(long)]
+        memo: Option<String>,
+    },
+    /// Melt ecash at a mint into the node's lightning balance
+    MeltToLightning {
+        #[arg(short, long)]
+        mint_url: String,
+        #[arg(short, long)]
+        amount_sats: u64,
+    },
+    /// List channels
+    ListChannels,
+    /// List lightning and onchain payments, newest first
+    ListPayments {
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
     /// Send bitcoin on-chain
     SendOnchain {
         #[arg(short, long)]
-        amount_sat: u64,
+        amount_sats: u64,
+        #[arg(short, long)]
+        address: String,
+    },
+    /// List channel funding transactions and onchain payments
+    ListOnchainTransactions,
+    /// Send the whole spendable onchain balance to an address
+    SweepOnchain {
+        #[arg(short, long)]
+        address: String,
+        /// Keep reserves for anchor channel fee bumps
+        #[arg(long)]
+        retain_reserves: bool,
+    },
+    /// Check a node or invoice is routable by sending probes
+    ProbePayment {
+        #[arg(short, long, conflicts_with = "invoice")]
+        node_id: Option<String>,
+        #[arg(short, long, required_unless_present = "node_id")]
+        invoice: Option<String>,
+        #[arg(long)]
+        amount_msats: Option<u64>,
+    },
+    /// Decode a BOLT11 invoice, BOLT12 offer or NUT-18 payment request
+    DecodePaymentRequest {
+        #[arg(short, long)]
+        request: String,
+    },
+    /// Pay a BOLT11 invoice
+    PayBolt11 {
         #[arg(short, long)]
+        invoice: String,
+        /// Amount for zero-amount invoices
+        #[arg(long)]
+        amount_msats: Option<u64>,
+        #[arg(long)]
+        timeout_secs: Option<u32>,
+    },
+    /// Create a BOLT11 invoice
+    CreateInvoice {
+        /// Zero-amount invoice when unset
+        #[arg(long)]
+        amount_msats: Option<u64>,
+        #[arg(short, long, default_value = "")]
+        description: String,
+        #[arg(long)]
+        expiry_secs: Option<u32>,
+    },
+    /// Create a BOLT12 offer
+    CreateOffer {
+        /// Variable amount offer when unset
+        #[arg(long)]
+        amount_msats: Option<u64>,
+        #[arg(short, long, default_value = "")]
+        description: String,
+        #[arg(long)]
+        expiry_secs: Option<u32>,
+        #[arg(long)]
+        quantity: Option<u64>,
+    },
+    /// Pay a BOLT12 offer
+    PayOffer {
+        #[arg(short, long)]
+        offer: String,
+        /// Amount for variable amount offers
+        #[arg(long)]
+        amount_msats: Option<u64>,
+        #[arg(long)]
+        quantity: Option<u64>,
+        #[arg(long)]
+        payer_note: Option<String>,
+        #[arg(long)]
+        timeout_secs: Option<u32>,
+    },
+    /// List BOLT12 offers created with create-offer
+    ListOffers,
+    /// Connect to a peer
+    ConnectPeer {
+        #[arg(short, long)]
+        node_id: String,
+        #[arg(long)]
         address: String,
+        #[arg(short, long)]
+        port: u16,
+        /// Reconnect to the peer on restart
+        #[arg(long)]
+        persist: bool,
+    },
+    /// Disconnect from a peer
+    DisconnectPeer {
+        #[arg(short, long)]
+        node_id: String,
+    },
+    /// List peers
+    ListPeers,
+    /// List channel quotes, newest first
+    ListQuotes {
+        #[arg(short, long, value_parser = ["unpaid", "paid", "channel_pending", "channel_open", "channel_expired"])]
+        state: Option<String>,
+        #[arg(short, long)]
+        node_pubkey: Option<String>,
+        /// Unix time, inclusive
+        #[arg(long)]
+        created_after: Option<u64>,
+        /// Unix time, exclusive
+        #[arg(long)]
+        created_before: Option<u64>,
+        #[arg(long)]
+        limit: Option<u32>,
+        /// Token printed after the previous page
+        #[arg(long)]
+        page_token: Option<String>,
+    },
+    /// Show a channel quote and its state history
+    GetQuote {
+        #[arg(short, long)]
+        quote_id: String,
+    },
+    /// Show who changed quote states and when, for investigating disputes
+    QuoteAudit {
+        /// Only show changes to this quote
+        #[arg(short, long)]
+        quote_id: Option<String>,
+        /// Sequence number of the last entry seen
+        #[arg(long, default_value_t = 0)]
+        cursor: u64,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Snapshot the LSP database while it keeps running
+    BackupDatabase {
+        /// Absolute path on the LSP host to write the backup to
+        #[arg(short, long)]
+        path: String,
+    },
+    /// Export every quote as JSON lines or CSV
+    ExportQuotes {
+        #[arg(short, long, value_parser = ["json", "csv"], default_value = "json")]
+        format: String,
+        /// File to write the export to, stdout when unset
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Recover a stuck channel open or expire a quote
+    ///
+    /// Only channel_pending quotes can be set to paid or channel_open, and
+    /// only unfinished quotes can be set to channel_expired.
+    AdminSetQuoteState {
+        #[arg(short, long)]
+        quote_id: String,
+        #[arg(short, long, value_parser = ["paid", "channel_open", "channel_expired"])]
+        state: String,
+        /// Recorded in the quote history
+        #[arg(short, long)]
+        reason: String,
+    },
+    /// Retry opening the channel for a paid quote
+    RetryChannelOpen {
+        #[arg(short, long)]
+        quote_id: String,
+    },
+    /// Return the payment for a quote whose channel won't be opened, expiring it
+    RefundQuote {
+        #[arg(short, long)]
+        quote_id: String,
+        #[arg(short, long)]
+        amount_sats: u64,
+        /// Pay the refund to this invoice instead of as an ecash token
+        #[arg(short, long)]
+        invoice: Option<String>,
+    },
+    /// List refunds paid out for quotes
+    ListRefunds {
+        #[arg(short, long)]
+        quote_id: Option<String>,
+    },
+    /// Change LSP pricing and limits until restart, and the accepted mints
+    UpdateLspInfo {
+        #[arg(long)]
+        min_channel_size_sats: Option<u64>,
+        #[arg(long)]
+        max_channel_size_sats: Option<u64>,
+        #[arg(long)]
+        min_fee: Option<u64>,
+        #[arg(long)]
+        fee_ppk: Option<u64>,
+        /// Replaces the accepted mints, repeat for each mint
+        #[arg(long = "accepted-mint")]
+        accepted_mints: Vec<String>,
+    },
+    /// Start accepting a mint, persisted across restarts
+    AddMint {
+        #[arg(short, long)]
+        mint_url: String,
+    },
+    /// Stop accepting a mint, its ecash stays in the wallet
+    RemoveMint {
+        #[arg(short, long)]
+        mint_url: String,
     },
+    /// Shut down the LSP and its node
+    StopNode,
+    /// Shut down the LSP and start it again
+    RestartNode,
+    /// Sign a message with the node key
+    SignMessage {
+        #[arg(short, long)]
+        message: String,
+    },
+    /// Verify a message signed by a lightning node key
+    VerifyMessage {
+        #[arg(short, long)]
+        message: String,
+        #[arg(short, long)]
+        signature: String,
+        #[arg(short, long)]
+        pubkey: String,
+    },
+    /// List availability of channels sold to each peer
+    ListPeerSla,
+    /// Show fees earned on completed quotes
+    FeeRevenue {
+        /// Unix time to start from, defaults to the first recorded fee
+        #[arg(long)]
+        since: Option<u64>,
+        /// Unix time to stop at, defaults to now
+        #[arg(long)]
+        until: Option<u64>,
+        /// Break revenue down into windows of this many seconds, e.g. 86400 for days
+        #[arg(long)]
+        window_secs: Option<u64>,
+    },
+    /// Stream node and quote events
+    SubscribeEvents {
+        /// Replay events after this sequence number
+        #[arg(long, default_value_t = 0)]
+        cursor: u64,
+    },
+    /// Allow or deny a peer at runtime, or remove it from both runtime lists with `none`
+    SetPeerAccess {
+        #[arg(short, long)]
+        node_pubkey: String,
+        #[arg(short, long, value_parser = ["allow", "deny", "none"])]
+        access: String,
+    },
+    /// List allowed and denied peers
+    ListPeerAccess,
 }
 
 #[tokio::main]
@@ -70,12 +385,14 @@ async fn main() -> Result<()> {
         // TLS directory exists, configure TLS
         let server_root_ca_cert = std::fs::read_to_string(work_dir.join("tls/ca.pem")).unwrap();
         let server_root_ca_cert = Certificate::from_pem(server_root_ca_cert);
-        let client_cert = std::fs::read_to_string(work_dir.join("tls/client.pem"))?;
-        let client_key = std::fs::read_to_string(work_dir.join("tls/client.key"))?;
-        let client_identity = Identity::from_pem(client_cert, client_key);
-        let tls = ClientTlsConfig::new()
-            .ca_certificate(server_root_ca_cert)
-            .identity(client_identity);
+        let mut tls = ClientTlsConfig::new().ca_certificate(server_root_ca_cert);
+
+        // Client certificate is only needed when the server uses mutual TLS
+        if work_dir.join("tls/client.pem").exists() {
+            let client_cert = std::fs::read_to_string(work_dir.join("tls/client.pem"))?;
+            let client_key = std::fs::read_to_string(work_dir.join("tls/client.key"))?;
+            tls = tls.identity(Identity::from_pem(client_cert, client_key));
+        }
 
         Channel::from_shared(cli.address.to_string())?
             .tls_config(tls)?
@@ -88,12 +405,42 @@ async fn main() -> Result<()> {
             .await?
     };
 
-    let mut client = CdkLdkClient::new(channel);
+    let token = match &cli.token {
+        Some(token) => ClientToken::new(token)?,
+        None => ClientToken::default(),
+    };
+
+    let mut client = CdkLdkClient::with_token(channel, token);
 
     match cli.command {
         Commands::GetInfo => {
             let info = client.get_info().await?;
-            println!("{:?}", info);
+            println!("Node id: {}", info.node_id);
+            if let Some(alias) = info.alias {
+                println!("Alias: {}", alias);
+            }
+            println!("Network: {}", info.network);
+            println!("Block height: {} ({})", info.block_height, info.block_hash);
+            println!("Running: {}", info.is_running);
+            println!(
+                "Last onchain sync: {}",
+                info.latest_onchain_wallet_sync_timestamp
+                    .map_or("never".to_string(), |t| t.to_string())
+            );
+            println!(
+                "Last lightning sync: {}",
+                info.latest_lightning_wallet_sync_timestamp
+                    .map_or("never".to_string(), |t| t.to_string())
+            );
+            println!(
+                "Peers: {} ({} connected)",
+                info.num_peers, info.num_connected_peers
+            );
+            println!(
+                "Channels: {} ({} usable, {} pending)",
+                info.num_channels, info.num_usable_channels, info.num_pending_channels
+            );
+            println!("Uptime: {}s", info.uptime_secs);
         }
         Commands::GetNewAddress => {
             let address = client.get_new_address().await?;
@@ -103,20 +450,61 @@ async fn main() -> Result<()> {
             node_id,
             address,
             port,
-            amount_msats,
+            amount_sats,
             push_msats,
+            private,
+            forwarding_fee_base_msat,
+            forwarding_fee_ppm,
+            cltv_expiry_delta,
         } => {
+            let options = ChannelOptions {
+                announce: Some(!private),
+                forwarding_fee_base_msat,
+                forwarding_fee_ppm,
+                cltv_expiry_delta: cltv_expiry_delta.map(u32::from),
+            };
             let channel_id = client
-                .open_channel(node_id, address, port, amount_msats, push_msats)
+                .open_channel(node_id, address, port, amount_sats, push_msats, options)
                 .await?;
             println!("Opened channel with ID: {}", channel_id);
         }
         Commands::CloseChannel {
             channel_id,
             node_pubkey,
+            force,
+            reason,
         } => {
-            client.close_channel(channel_id, node_pubkey).await?;
-            println!("Channel closed successfully");
+            let response = client
+                .close_channel(channel_id, node_pubkey, force, reason)
+                .await?;
+            println!(
+                "Channel {}, funding outpoint {}",
+                response.status,
+                response.funding_txo.as_deref().unwrap_or("-")
+            );
+        }
+        Commands::UpdateChannelConfig {
+            channel_id,
+            node_pubkey,
+            forwarding_fee_base_msat,
+            forwarding_fee_ppm,
+            cltv_expiry_delta,
+        } => {
+            let config = client
+                .update_channel_config(
+                    channel_id,
+                    node_pubkey,
+                    forwarding_fee_base_msat,
+                    forwarding_fee_ppm,
+                    cltv_expiry_delta.map(u32::from),
+                )
+                .await?;
+            println!(
+                "Channel config updated: base fee {} msats, {} ppm, cltv delta {}",
+                config.forwarding_fee_base_msat,
+                config.forwarding_fee_ppm,
+                config.cltv_expiry_delta
+            );
         }
         Commands::ListBalance => {
             let balance = client.list_balance().await?;
@@ -133,14 +521,549 @@ async fn main() -> Result<()> {
                 balance.total_lightning_balance_sats
             );
         }
+        Commands::ListCashuBalances => {
+            let balances = client.list_cashu_balances().await?;
+            for balance in balances {
+                println!(
+                    "{} ({}): {} unspent, {} pending, {} reserved",
+                    balance.mint_url,
+                    balance.unit,
+                    balance.balance,
+                    balance.pending,
+                    balance.reserved
+                );
+            }
+        }
+        Commands::RestoreWallets => {
+            let balances = client.restore_wallets().await?;
+            for balance in balances {
+                match balance.error {
+                    Some(error) => {
+                        println!("{} ({}): failed, {}", balance.mint_url, balance.unit, error)
+                    }
+                    None => println!(
+                        "{} ({}): restored {}",
+                        balance.mint_url, balance.unit, balance.restored
+                    ),
+                }
+            }
+        }
+        Commands::ExportProofs {
+            mint_url,
+            unit,
+            amount,
+            p2pk_pubkey,
+            memo,
+        } => {
+            let token = client
+                .export_proofs(mint_url, unit, amount, p2pk_pubkey, memo)
+                .await?;
+            println!("{}", token);
+        }
+        Commands::MeltToLightning {
+            mint_url,
+            amount_sats,
+        } => {
+            let response = client.melt_to_lightning(mint_url, amount_sats).await?;
+            println!(
+                "Melt {}: {} sats, {} sats fee, payment hash {}",
+                response.state, response.amount_sats, response.fee_paid_sats, response.payment_hash
+            );
+        }
+        Commands::ListChannels => {
+            let channels = client.list_channels().await?;
+            for channel in channels {
+                println!(
+                    "{} ({}) with {}: {} sats, out {} msats, in {} msats, confirmations {}/{}, ready {}, usable {}, announced {}, fees {} msats + {} ppm",
+                    channel.channel_id,
+                    channel.user_channel_id,
+                    channel.counterparty_node_id,
+                    channel.channel_value_sats,
+                    channel.outbound_capacity_msats,
+                    channel.inbound_capacity_msats,
+                    channel.confirmations.unwrap_or_default(),
+                    channel.confirmations_required.unwrap_or_default(),
+                    channel.is_channel_ready,
+                    channel.is_usable,
+                    channel.is_announced,
+                    channel.forwarding_fee_base_msat,
+                    channel.forwarding_fee_ppm
+                );
+            }
+        }
+        Commands::ListPayments { offset, limit } => {
+            let response = client.list_payments(offset, limit).await?;
+            for payment in response.payments.iter() {
+                println!(
+                    "{} {} {} {}: {} msats, hash {}, updated {}",
+                    payment.id,
+                    payment.kind,
+                    payment.direction,
+                    payment.status,
+                    payment.amount_msats.unwrap_or_default(),
+                    payment.payment_hash.as_deref().unwrap_or("-"),
+                    payment.latest_update_timestamp
+                );
+            }
+            println!(
+                "Showing {} of {} payments",
+                response.payments.len(),
+                response.total
+            );
+        }
         Commands::SendOnchain {
-            amount_sat,
+            amount_sats,
             address,
         } => {
-            let txid = client.send_onchain(amount_sat, address).await?;
+            let txid = client.send_onchain(amount_sats, address).await?;
             println!("Transaction sent with txid: {}", txid);
         }
+        Commands::ListOnchainTransactions => {
+            let transactions = client.list_onchain_transactions().await?;
+            for tx in transactions {
+                println!(
+                    "{} {} {}: {} sats, confirmations {}, status {}{}",
+                    tx.txid.as_deref().unwrap_or("-"),
+                    tx.kind,
+                    tx.direction,
+                    tx.amount_sats.unwrap_or_default(),
+                    tx.confirmations
+                        .map(|c| c.to_string())
+                        .unwrap_or("-".to_string()),
+                    tx.status.as_deref().unwrap_or("-"),
+                    tx.user_channel_id
+                        .map(|id| format!(", channel {}", id))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        Commands::SweepOnchain {
+            address,
+            retain_reserves,
+        } => {
+            let response = client.sweep_onchain(address, retain_reserves).await?;
+            println!(
+                "Swept {} sats spendable balance, less fee and reserves, with txid: {}",
+                response.spendable_before_sats, response.txid
+            );
+        }
+        Commands::ProbePayment {
+            node_id,
+            invoice,
+            amount_msats,
+        } => {
+            let response = client.probe_payment(node_id, invoice, amount_msats).await?;
+            match response.error {
+                None => println!("Route found, probes sent"),
+                Some(error) => println!("Not routable: {}", error),
+            }
+        }
+        Commands::DecodePaymentRequest { request } => {
+            if let Some(decoded) = client.decode_payment_request(request).await? {
+                println!("{:#?}", decoded);
+            }
+        }
+        Commands::PayBolt11 {
+            invoice,
+            amount_msats,
+            timeout_secs,
+        } => {
+            let payment = client
+                .pay_bolt11(invoice, amount_msats, timeout_secs)
+                .await?;
+            println!("Payment {}: {}", payment.payment_id, payment.status);
+            println!("Payment hash: {}", payment.payment_hash);
+            if let Some(preimage) = payment.preimage {
+                println!("Preimage: {}", preimage);
+            }
+        }
+        Commands::CreateInvoice {
+            amount_msats,
+            description,
+            expiry_secs,
+        } => {
+            let invoice = client
+                .create_invoice(amount_msats, description, expiry_secs)
+                .await?;
+            println!("Invoice: {}", invoice.invoice);
+            println!("Payment hash: {}", invoice.payment_hash);
+        }
+        Commands::CreateOffer {
+            amount_msats,
+            description,
+            expiry_secs,
+            quantity,
+        } => {
+            let offer = client
+                .create_offer(amount_msats, description, expiry_secs, quantity)
+                .await?;
+            println!("Offer: {}", offer.offer);
+            println!("Offer id: {}", offer.offer_id);
+        }
+        Commands::PayOffer {
+            offer,
+            amount_msats,
+            quantity,
+            payer_note,
+            timeout_secs,
+        } => {
+            let payment = client
+                .pay_offer(offer, amount_msats, quantity, payer_note, timeout_secs)
+                .await?;
+            println!("Payment {}: {}", payment.payment_id, payment.status);
+            if let Some(preimage) = payment.preimage {
+                println!("Preimage: {}", preimage);
+            }
+        }
+        Commands::ListOffers => {
+            let offers = client.list_offers().await?;
+            for offer in offers {
+                println!(
+                    "{} \"{}\": {} payments, {} msats received",
+                    offer.offer_id, offer.description, offer.num_payments, offer.received_msats
+                );
+                println!("  {}", offer.offer);
+            }
+        }
+        Commands::ConnectPeer {
+            node_id,
+            address,
+            port,
+            persist,
+        } => {
+            client
+                .connect_peer(node_id.clone(), address, port, persist)
+                .await?;
+            println!("Connected to {}", node_id);
+        }
+        Commands::DisconnectPeer { node_id } => {
+            client.disconnect_peer(node_id.clone()).await?;
+            println!("Disconnected from {}", node_id);
+        }
+        Commands::ListPeers => {
+            let peers = client.list_peers().await?;
+            for peer in peers {
+                println!(
+                    "{}@{}: connected {}, persisted {}",
+                    peer.node_id, peer.address, peer.is_connected, peer.is_persisted
+                );
+            }
+        }
+        Commands::SubscribeEvents { cursor } => {
+            let mut events = client.subscribe_events(cursor).await?;
+            while let Some(event) = events.message().await? {
+                println!("{} {} {}", event.seq, event.timestamp, event.kind);
+            }
+        }
+        Commands::ListQuotes {
+            state,
+            node_pubkey,
+            created_after,
+            created_before,
+            limit,
+            page_token,
+        } => {
+            let state = state
+                .as_deref()
+                .map(quote_status)
+                .unwrap_or(QuoteStatus::Unspecified);
+            let response = client
+                .list_quotes(
+                    state,
+                    node_pubkey,
+                    created_after,
+                    created_before,
+                    limit,
+                    page_token,
+                )
+                .await?;
+            for quote in response.quotes.iter() {
+                print_quote(quote);
+            }
+            if let Some(token) = response.next_page_token {
+                println!("Next page: --page-token {}", token);
+            }
+        }
+        Commands::GetQuote { quote_id } => {
+            let response = client.get_quote(quote_id).await?;
+            if let Some(quote) = response.quote {
+                print_quote(&quote);
+            }
+            for transition in response.history {
+                println!(
+                    "  {} {:?}{}",
+                    transition.timestamp,
+                    transition.state(),
+                    transition
+                        .reason
+                        .map(|reason| format!(" ({})", reason))
+                        .unwrap_or_default()
+                );
+            }
+            if let Some(payment) = response.payment {
+                println!(
+                    "  Paid from {} ({}) at {}:",
+                    payment.mint_url, payment.unit, payment.received_at
+                );
+                for proof in payment.proofs {
+                    println!("    {} {} {}", proof.amount, proof.keyset_id, proof.y);
+                }
+            }
+        }
+        Commands::QuoteAudit {
+            quote_id,
+            cursor,
+            limit,
+        } => {
+            let entries = client.list_quote_audit(quote_id, cursor, limit).await?;
+            for entry in entries {
+                println!(
+                    "{} {} {} {:?} -> {:?} by {:?}{}",
+                    entry.seq,
+                    entry.timestamp,
+                    entry.quote_id,
+                    entry.from_state(),
+                    entry.to_state(),
+                    entry.actor(),
+                    entry
+                        .reason
+                        .as_ref()
+                        .map(|reason| format!(" ({})", reason))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        Commands::BackupDatabase { path } => {
+            let backup = client.backup_database(path).await?;
+            println!("Backed up {} bytes to {}", backup.size_bytes, backup.path);
+        }
+        Commands::ExportQuotes { format, output } => {
+            let mut out: Box<dyn Write> = match output {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            let mut chunks = client.export_quotes(Some(format)).await?;
+            while let Some(chunk) = chunks.message().await? {
+                out.write_all(&chunk.data)?;
+            }
+            out.flush()?;
+        }
+        Commands::AdminSetQuoteState {
+            quote_id,
+            state,
+            reason,
+        } => {
+            let quote = client
+                .admin_set_quote_state(quote_id, quote_status(&state), reason)
+                .await?;
+            if let Some(quote) = quote {
+                print_quote(&quote);
+            }
+        }
+        Commands::RetryChannelOpen { quote_id } => {
+            let user_channel_id = client.retry_channel_open(quote_id).await?;
+            println!("Channel opened, user channel id {}", user_channel_id);
+        }
+        Commands::RefundQuote {
+            quote_id,
+            amount_sats,
+            invoice,
+        } => {
+            if let Some(refund) = client.refund_quote(quote_id, amount_sats, invoice).await? {
+                print_refund(&refund);
+            }
+        }
+        Commands::ListRefunds { quote_id } => {
+            for refund in client.list_refunds(quote_id).await? {
+                print_refund(&refund);
+            }
+        }
+        Commands::UpdateLspInfo {
+            min_channel_size_sats,
+            max_channel_size_sats,
+            min_fee,
+            fee_ppk,
+            accepted_mints,
+        } => {
+            let accepted_mints = (!accepted_mints.is_empty()).then_some(accepted_mints);
+            let info = client
+                .update_lsp_info(
+                    min_channel_size_sats,
+                    max_channel_size_sats,
+                    min_fee,
+                    fee_ppk,
+                    accepted_mints,
+                )
+                .await?;
+            println!(
+                "Channels {}-{} sats, fee {} sats + {} ppk, mints: {}",
+                info.min_channel_size_sats,
+                info.max_channel_size_sats,
+                info.min_fee,
+                info.fee_ppk,
+                info.accepted_mints.join(", ")
+            );
+        }
+        Commands::StopNode => {
+            client.stop_node().await?;
+            println!("Node stopping");
+        }
+        Commands::AddMint { mint_url } => {
+            let mints = client.add_mint(mint_url).await?;
+            println!("Accepted mints: {}", mints.join(", "));
+        }
+        Commands::RemoveMint { mint_url } => {
+            let mints = client.remove_mint(mint_url).await?;
+            println!("Accepted mints: {}", mints.join(", "));
+        }
+        Commands::RestartNode => {
+            client.restart_node().await?;
+            println!("Node restarting");
+        }
+        Commands::SignMessage { message } => {
+            let signature = client.sign_message(message.into_bytes()).await?;
+            println!("{}", signature);
+        }
+        Commands::VerifyMessage {
+            message,
+            signature,
+            pubkey,
+        } => {
+            let valid = client
+                .verify_message(message.into_bytes(), signature, pubkey)
+                .await?;
+            println!("Signature valid: {}", valid);
+        }
+        Commands::FeeRevenue {
+            since,
+            until,
+            window_secs,
+        } => {
+            let revenue = client.get_fee_revenue(since, until, window_secs).await?;
+            for window in revenue.windows.iter() {
+                print_fee_revenue(window);
+            }
+            if let Some(total) = revenue.total.as_ref() {
+                print_fee_revenue(total);
+            }
+        }
+        Commands::ListPeerSla => {
+            let peers = client.list_peer_sla().await?;
+            for peer in peers {
+                println!(
+                    "{}: uptime {:.2}% ({}/{} samples usable, {} connected), {} forwards ({} msat), {} channels closed",
+                    peer.node_pubkey,
+                    peer.uptime_percent,
+                    peer.usable_samples,
+                    peer.samples,
+                    peer.connected_samples,
+                    peer.forwarded_payments,
+                    peer.forwarded_msats,
+                    peer.closed_channels
+                );
+            }
+        }
+        Commands::SetPeerAccess {
+            node_pubkey,
+            access,
+        } => {
+            let level = match access.as_str() {
+                "allow" => PeerAccessLevel::Allow,
+                "deny" => PeerAccessLevel::Deny,
+                _ => PeerAccessLevel::None,
+            };
+            client.set_peer_access(node_pubkey, level).await?;
+            println!("Peer access updated");
+        }
+        Commands::ListPeerAccess => {
+            let peers = client.list_peer_access().await?;
+            for peer in peers {
+                println!(
+                    "{}: {:?}{}",
+                    peer.node_pubkey,
+                    peer.access(),
+                    if peer.from_config { " (config)" } else { "" }
+                );
+            }
+        }
     }
 
     Ok(())
 }
+
+fn quote_status(state: &str) -> QuoteStatus {
+    match state {
+        "unpaid" => QuoteStatus::Unpaid,
+        "paid" => QuoteStatus::Paid,
+        "channel_pending" => QuoteStatus::ChannelPending,
+        "channel_open" => QuoteStatus::ChannelOpen,
+        "channel_expired" => QuoteStatus::ChannelExpired,
+        _ => QuoteStatus::Unspecified,
+    }
+}
+
+fn print_fee_revenue(revenue: &FeeRevenue) {
+    println!(
+        "{}-{}: {} quotes, {} sats service fees, {} sats bonds, {} sats onchain, {} sats mint fees, {} sats revenue",
+        revenue.start,
+        revenue.end,
+        revenue.quotes,
+        revenue.service_fee_sats,
+        revenue.bond_sats,
+        revenue.onchain_fee_sats,
+        revenue.mint_fee_sats,
+        revenue.revenue_sats
+    );
+}
+
+fn print_refund(refund: &RefundRecord) {
+    println!(
+        "{} {} sats by {}, {}",
+        refund.quote_id,
+        refund.amount_sats,
+        refund.method,
+        refund
+            .claimed_at
+            .map(|claimed_at| format!("paid out at {}", claimed_at))
+            .unwrap_or_else(|| "not paid out".to_string())
+    );
+    if let Some(token) = &refund.token {
+        println!("  token: {}", token);
+    }
+    if let Some(invoice) = &refund.invoice {
+        println!("  invoice: {}", invoice);
+    }
+}
+
+fn print_quote(quote: &QuoteRecord) {
+    println!(
+        "{} {:?} {}: {} sats channel, {} sats paid, created {}",
+        quote.id,
+        quote.state(),
+        quote.node_pubkey,
+        quote.channel_size_sats,
+        quote.expected_payment_sats,
+        quote.created_at
+    );
+    if let Some(error) = &quote.payment_error {
+        println!("  payment error: {}", error);
+    }
+    if let (Some(txid), Some(vout)) = (&quote.funding_txid, quote.funding_output_index) {
+        println!(
+            "  funded by {}:{}{}",
+            txid,
+            vout,
+            quote
+                .short_channel_id
+                .map(|scid| format!(", scid {}", scid))
+                .unwrap_or_default()
+        );
+    }
+    if let Some(closed_at) = quote.closed_at {
+        println!(
+            "  closed at {}: {}",
+            closed_at,
+            quote.close_reason.as_deref().unwrap_or("unknown reason")
+        );
+    }
+}
@@ -1,25 +1,78 @@
+use std::collections::HashMap;
+use std::io::Write;
 use std::net::SocketAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, bail};
 use bip39::Mnemonic;
+use cdk::cdk_database::{self, WalletDatabase};
 use cdk::mint_url::MintUrl;
 use cdk::nuts::CurrencyUnit;
-use cdk::wallet::{MultiMintWallet, Wallet};
+use cdk::util::unix_time;
+use cdk::wallet::{HttpClient, MultiMintWallet, Wallet};
+use cdk_ldk_node::anti_spam::AntiSpam;
 use cdk_ldk_node::config::AppConfig;
-use cdk_ldk_node::db::Db;
-use cdk_ldk_node::lsp_server::CashuLspInfo;
+use cdk_ldk_node::db::{Db, LspStore};
+use cdk_ldk_node::events::EventJournal;
+use cdk_ldk_node::locks::{HtlcLocks, RefundLocks};
+use cdk_ldk_node::lsp_server::{
+    CashuLspInfo, CashuLspState, ForwardingFeeBounds, HttpLimits, MintFeePolicy,
+};
+use cdk_ldk_node::melt_policy::{MeltPolicy, enforce_melt_policy};
+use cdk_ldk_node::nostr::NostrTransport;
+use cdk_ldk_node::proto::FILE_DESCRIPTOR_SET;
+use cdk_ldk_node::proto::auth::TokenAuth;
 use cdk_ldk_node::proto::cdk_ldk_management_server::CdkLdkManagementServer;
-use cdk_ldk_node::proto::server::CdkLdkServer;
-use cdk_ldk_node::{BitcoinRpcConfig, ChainSource, GossipSource, create_cashu_lsp_router};
+use cdk_ldk_node::proto::server::{CdkLdkServer, Shutdown};
+use cdk_ldk_node::rates::{ExchangeRates, FixedRate, HttpRateSource, RateSource, UnitRate};
+use cdk_ldk_node::retention::{DEFAULT_RETENTION_INTERVAL, RetentionPolicy};
+use cdk_ldk_node::sandbox::Sandbox;
+use cdk_ldk_node::seed;
+use cdk_ldk_node::sqlite_db::SqliteDb;
+use cdk_ldk_node::types::PeerAccess;
+use cdk_ldk_node::{
+    BitcoinRpcConfig, ChainSource, DEFAULT_NODE_ALIAS, GossipSource, NodeConfig, VssConfig,
+    WalletFactory, create_cashu_lsp_router, create_metrics_router,
+};
+use clap::Parser;
+use ldk_node::LogLevel;
+use ldk_node::bitcoin::Network;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::config::AnchorChannelsConfig;
 use ldk_node::lightning::ln::msgs::SocketAddress;
 use tokio::signal;
-use tonic::transport::Server;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic_health::ServingStatus;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::EnvFilter;
 
+const SLA_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_MELT_POLICY_INTERVAL_SECS: u64 = 300;
+const GRPC_HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+/// How long open gRPC streams may delay shutdown
+const GRPC_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Environment variable that overrides the stored wallet mnemonic
+const MNEMONIC_ENV: &str = "CASHU_LSP_MNEMONIC";
+/// Environment variable with the passphrase the stored mnemonic is encrypted with
+const MNEMONIC_PASSPHRASE_ENV: &str = "CASHU_LSP_MNEMONIC_PASSPHRASE";
+const NEW_SEED_FLAG: &str = "--new-seed";
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Replace the stored wallet mnemonic with a new one, the old one is kept as a backup
+    #[arg(long)]
+    new_seed: bool,
+}
+
 fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
@@ -68,14 +121,79 @@ fn main() -> anyhow::Result<()> {
 
         tracing_subscriber::fmt().with_env_filter(env_filter).init();
 
+        let network = match config.bitcoin.network.as_deref() {
+            None => Network::Regtest,
+            Some("mainnet") => Network::Bitcoin,
+            Some(network) => Network::from_str(network)
+                .map_err(|_| anyhow!("Unsupported bitcoin network {}", network))?,
+        };
+
         // Configure Bitcoin chain source from config
-        let chain_source = ChainSource::BitcoinRpc(BitcoinRpcConfig {
-            host: config.bitcoin.rpc_host.clone(),
-            port: config.bitcoin.rpc_port,
-            user: config.bitcoin.rpc_user.clone(),
-            password: config.bitcoin.rpc_password.clone(),
+        let chain_source = match config.chain.backend.as_deref() {
+            None | Some("bitcoind") => ChainSource::BitcoinRpc(BitcoinRpcConfig {
+                host: config.bitcoin.rpc_host.clone(),
+                port: config.bitcoin.rpc_port,
+                user: config.bitcoin.rpc_user.clone(),
+                password: config.bitcoin.rpc_password.clone(),
+            }),
+            Some("esplora") => ChainSource::Esplora(
+                config
+                    .chain
+                    .url
+                    .clone()
+                    .ok_or(anyhow!("Esplora backend requires chain.url"))?,
+            ),
+            Some("electrum") => bail!("Electrum is not supported by this version of ldk-node"),
+            Some(backend) => bail!("Unknown chain backend {}", backend),
+        };
+
+        let gossip_source = match config.ldk.rgs_url.clone() {
+            Some(rgs_url) => GossipSource::RapidGossipSync(rgs_url),
+            None => GossipSource::P2P,
+        };
+
+        let ldk_storage_dir = config
+            .ldk
+            .storage_dir
+            .clone()
+            .unwrap_or_else(|| work_dir.join("ldk-node"));
+
+        let vss = config.ldk.vss_url.clone().map(|url| VssConfig {
+            url,
+            store_id: config
+                .ldk
+                .vss_store_id
+                .clone()
+                .unwrap_or_else(|| network.to_string()),
+            headers: config.ldk.vss_headers.clone(),
         });
 
+        let anchor_channels = if config.ldk.anchor_channels.unwrap_or(true) {
+            let mut anchor_config = AnchorChannelsConfig::default();
+
+            if let Some(reserve_sats) = config.ldk.anchor_reserve_sats {
+                anchor_config.per_channel_reserve_sats = reserve_sats;
+            }
+
+            anchor_config.trusted_peers_no_reserve = config
+                .ldk
+                .anchor_trusted_peers
+                .iter()
+                .map(|peer| PublicKey::from_str(peer))
+                .collect::<Result<_, _>>()?;
+
+            Some(anchor_config)
+        } else {
+            None
+        };
+
+        let trusted_peers_0conf = config
+            .ldk
+            .trusted_peers_0conf
+            .iter()
+            .map(|peer| PublicKey::from_str(peer))
+            .collect::<Result<Vec<_>, _>>()?;
+
         // Configure LDK node
         let ldk_node_listen_addr = SocketAddress::from_str(&format!(
             "{}:{}",
@@ -83,74 +201,594 @@ fn main() -> anyhow::Result<()> {
         ))
         .unwrap();
 
-        let localstore = Arc::new(cdk_redb::WalletRedbDatabase::new(
-            &work_dir.join("cdk-wallet.redb"),
-        )?);
+        let localstore: Arc<dyn WalletDatabase<Err = cdk_database::Error> + Send + Sync> =
+            match config.lsp.wallet_db.as_deref() {
+                None | Some("redb") => Arc::new(cdk_redb::WalletRedbDatabase::new(
+                    &work_dir.join("cdk-wallet.redb"),
+                )?),
+                Some("sqlite") => {
+                    let sqlite =
+                        cdk_sqlite::WalletSqliteDatabase::new(&work_dir.join("cdk-wallet.sqlite"))
+                            .await?;
+                    sqlite.migrate().await;
+                    Arc::new(sqlite)
+                }
+                Some(wallet_db) => bail!("Unknown wallet database {}", wallet_db),
+            };
+
+        let configured_mnemonic = std::env::var(MNEMONIC_ENV)
+            .ok()
+            .or_else(|| config.lsp.mnemonic.clone());
+
+        let mnemonic_passphrase = std::env::var(MNEMONIC_PASSPHRASE_ENV)
+            .ok()
+            .or_else(|| config.lsp.mnemonic_passphrase.clone());
+
+        let seed = load_or_create_mnemonic(
+            &work_dir.join("mnemonic"),
+            configured_mnemonic.as_deref(),
+            mnemonic_passphrase.as_deref(),
+            cli.new_seed,
+        )?;
 
-        let seed = Mnemonic::generate(12)?;
+        let accepted_units = if config.lsp.accepted_units.is_empty() {
+            vec![CurrencyUnit::Sat]
+        } else {
+            config
+                .lsp
+                .accepted_units
+                .iter()
+                .map(|u| CurrencyUnit::from_str(u))
+                .collect::<Result<Vec<CurrencyUnit>, _>>()?
+        };
 
-        let mut wallets = vec![];
+        let mut unit_rates = HashMap::new();
+
+        for rate in config.rates.units.iter() {
+            let unit = CurrencyUnit::from_str(&rate.unit)?;
+
+            let source: Arc<dyn RateSource> = match (&rate.url, &rate.pointer, rate.fixed_price) {
+                (Some(url), Some(pointer), None) => {
+                    Arc::new(HttpRateSource::new(url.clone(), pointer.clone())?)
+                }
+                (None, None, Some(btc_price)) => Arc::new(FixedRate { btc_price }),
+                _ => bail!(
+                    "Rate for {} needs either url and pointer or fixed_price",
+                    unit
+                ),
+            };
+
+            unit_rates.insert(
+                unit,
+                UnitRate {
+                    source,
+                    minor_units: rate.minor_units.unwrap_or(100),
+                },
+            );
+        }
+
+        for unit in accepted_units.iter() {
+            if !matches!(unit, CurrencyUnit::Sat | CurrencyUnit::Msat)
+                && !unit_rates.contains_key(unit)
+            {
+                bail!("Unsupported currency unit {}, configure a rate for it", unit);
+            }
+        }
+
+        let exchange_rates = (!unit_rates.is_empty()).then(|| {
+            ExchangeRates::new(
+                unit_rates,
+                config.rates.slippage_ppk.unwrap_or(10),
+                Duration::from_secs(config.rates.refresh_secs.unwrap_or(60)),
+                Duration::from_secs(config.rates.max_age_secs.unwrap_or(600)),
+            )
+        });
 
-        for mint in config.lsp.accepted_mints.iter() {
-            let wallet = Wallet::new(
-                mint,
-                CurrencyUnit::Sat,
+        let wallet_seed = seed.to_seed_normalized("");
+        let proxy_url = config.proxy.url.clone();
+
+        let wallet_factory: WalletFactory = Arc::new(move |mint_url: &MintUrl, unit: &CurrencyUnit| {
+            let mut wallet = Wallet::new(
+                &mint_url.to_string(),
+                unit.clone(),
                 localstore.clone(),
-                &seed.to_seed_normalized(""),
+                &wallet_seed,
                 None,
             )?;
-            wallets.push(wallet);
+
+            if let Some(proxy_url) = proxy_url.as_ref() {
+                wallet.set_client(HttpClient::with_proxy(
+                    mint_url.clone(),
+                    proxy_url.parse()?,
+                    None,
+                    false,
+                )?);
+            }
+
+            Ok(wallet)
+        });
+
+        let open_store = |name: &str| -> anyhow::Result<Arc<dyn LspStore>> {
+            match config.lsp.quote_db.as_deref() {
+                None | Some("redb") => Ok(Arc::new(Db::new(
+                    work_dir.join(format!("{}.redb", name)),
+                )?)),
+                Some("sqlite") => Ok(Arc::new(SqliteDb::new(
+                    &work_dir.join(format!("{}.sqlite", name)),
+                )?)),
+                Some(quote_db) => bail!("Unknown quote database {}", quote_db),
+            }
+        };
+
+        let db = open_store("cashu-lsp")?;
+
+        let mut accepted_mints = config
+            .lsp
+            .accepted_mints
+            .iter()
+            .map(|s| MintUrl::from_str(s))
+            .collect::<Result<Vec<MintUrl>, _>>()?;
+
+        // Mints removed at runtime keep their wallets so their ecash can be melted
+        let mut wallet_mints = accepted_mints.clone();
+
+        for (mint_url, accepted) in db.list_mint_overrides()? {
+            if accepted {
+                if !accepted_mints.contains(&mint_url) {
+                    accepted_mints.push(mint_url.clone());
+                }
+            } else {
+                accepted_mints.retain(|m| m != &mint_url);
+            }
+
+            if !wallet_mints.contains(&mint_url) {
+                wallet_mints.push(mint_url);
+            }
+        }
+
+        let mut wallets = vec![];
+
+        for mint_url in wallet_mints.iter() {
+            for unit in accepted_units.iter() {
+                wallets.push(wallet_factory(mint_url, unit)?);
+            }
         }
 
         let wallet = MultiMintWallet::new(wallets);
 
+        let log_level = match config.ldk.log_level.as_deref() {
+            None | Some("debug") => LogLevel::Debug,
+            Some("gossip") => LogLevel::Gossip,
+            Some("trace") => LogLevel::Trace,
+            Some("info") => LogLevel::Info,
+            Some("warn") => LogLevel::Warn,
+            Some("error") => LogLevel::Error,
+            Some(level) => bail!("Unknown ldk log level {}", level),
+        };
+
         let cdk_ldk = cdk_ldk_node::CashuLspNode::new(
-            chain_source,
-            GossipSource::P2P,
-            vec![ldk_node_listen_addr],
+            NodeConfig {
+                chain_source,
+                gossip_source,
+                listening_addresses: vec![ldk_node_listen_addr],
+                alias: config
+                    .ldk
+                    .alias
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_NODE_ALIAS.to_string()),
+                network,
+                storage_dir: ldk_storage_dir.clone(),
+                vss,
+                anchor_channels,
+                trusted_peers_0conf,
+                log_level,
+            },
             wallet,
         )?;
 
+        cdk_ldk.check_mint_networks(network).await?;
+
         cdk_ldk.start(Some(runtime_clone))?;
 
         let cdk_ldk = Arc::new(cdk_ldk);
 
+        let log_node = Arc::clone(&cdk_ldk);
+        let node_log_file = ldk_storage_dir.join("logs").join("ldk_node_latest.log");
+        tokio::spawn(async move { log_node.forward_node_logs(node_log_file).await });
+
         let fund_addr = cdk_ldk.inner.onchain_payment().new_address()?;
 
         tracing::info!("Funding addr: {}", fund_addr);
 
-        // Start gRPC management server
-        let grpc_addr =
-            format!("{}:{}", config.grpc.host, config.grpc.port).parse::<SocketAddr>()?;
-        let management_service = CdkLdkServer::new(cdk_ldk.clone());
+        // Sample availability of sold channels for SLA reporting
+        let sla_node = Arc::clone(&cdk_ldk);
+        let sla_db = db.clone();
+        tokio::spawn(async move {
+            sla_node
+                .monitor_channel_sla(sla_db, SLA_SAMPLE_INTERVAL)
+                .await
+        });
 
-        let grpc_server = Server::builder()
-            .add_service(CdkLdkManagementServer::new(management_service))
-            .serve(grpc_addr);
+        // Shared by the LSP and management server so both see every event
+        let events = EventJournal::new(db.clone());
 
-        tokio::spawn(grpc_server);
+        let node_events_node = Arc::clone(&cdk_ldk);
+        let node_events = events.clone();
+        tokio::spawn(async move { node_events_node.journal_node_events(node_events).await });
 
         // Configure LSP server
+        let mut mint_fee_policies = vec![];
+
+        for policy in config.lsp.mint_fee_policies.iter() {
+            let mint_url = MintUrl::from_str(&policy.mint_url)?;
+
+            if !wallet_mints.contains(&mint_url) {
+                bail!("Fee policy configured for unknown mint {}", mint_url);
+            }
+
+            mint_fee_policies.push(MintFeePolicy {
+                mint_url,
+                min_fee: policy.min_fee.unwrap_or(config.lsp.min_fee),
+                fee_ppk: policy.fee_ppk.unwrap_or(config.lsp.fee_ppk),
+            });
+        }
+
+        for policy in config.lsp.melt_policies.iter() {
+            let mint_url = MintUrl::from_str(&policy.mint_url)?;
+
+            if !wallet_mints.contains(&mint_url) {
+                bail!("Melt policy configured for unknown mint {}", mint_url);
+            }
+
+            let policy = MeltPolicy {
+                mint_url,
+                threshold_sats: policy.threshold_sats,
+                target_sats: policy.target_sats.unwrap_or_default(),
+                interval: Duration::from_secs(
+                    policy
+                        .interval_secs
+                        .unwrap_or(DEFAULT_MELT_POLICY_INTERVAL_SECS),
+                ),
+            };
+
+            tokio::spawn(enforce_melt_policy(Arc::clone(&cdk_ldk), db.clone(), policy));
+        }
+
+        let mut exposure_limits = HashMap::new();
+
+        for limit in config.lsp.mint_exposure_limits.iter() {
+            let mint_url = MintUrl::from_str(&limit.mint_url)?;
+
+            if !wallet_mints.contains(&mint_url) {
+                bail!("Exposure limit configured for unknown mint {}", mint_url);
+            }
+
+            if !config
+                .lsp
+                .melt_policies
+                .iter()
+                .any(|p| MintUrl::from_str(&p.mint_url).ok().as_ref() == Some(&mint_url))
+            {
+                tracing::warn!(
+                    "No melt policy for {}, it stays unavailable once at its exposure limit until ecash is melted manually",
+                    mint_url
+                );
+            }
+
+            exposure_limits.insert(mint_url, limit.max_balance_sats);
+        }
+
+        let nostr_transport = match &config.nostr.secret_key {
+            Some(secret_key) if !config.nostr.relays.is_empty() => Some(NostrTransport::new(
+                secret_key,
+                config.nostr.relays.clone(),
+            )?),
+            _ => None,
+        };
+
+        let htlc_locks = config
+            .lsp
+            .htlc_locktime_secs
+            .map(|locktime_secs| HtlcLocks::from_seed(&wallet_seed, locktime_secs))
+            .transpose()?;
+
+        let refund_locks = config
+            .lsp
+            .refund_locktime_secs
+            .map(|locktime_secs| RefundLocks::from_seed(&wallet_seed, locktime_secs))
+            .transpose()?;
+
+        let anti_spam = match config.lsp.anti_spam.as_ref() {
+            None => None,
+            Some(anti_spam) => match anti_spam.mode.as_str() {
+                "pow" => Some(AntiSpam::Pow {
+                    difficulty_bits: anti_spam.pow_difficulty_bits,
+                }),
+                "bond" => Some(AntiSpam::Bond {
+                    amount_sats: anti_spam.bond_sats,
+                }),
+                mode => bail!("Unknown anti-spam mode: {}", mode),
+            },
+        };
+
         let cashu_lsp_info = CashuLspInfo {
             min_channel_size_sat: config.lsp.min_channel_size_sat,
             max_channel_size_sat: config.lsp.max_channel_size_sat,
-            accepted_mints: config
-                .lsp
-                .accepted_mints
-                .clone()
-                .iter()
-                .map(|s| MintUrl::from_str(s))
-                .collect::<Result<Vec<MintUrl>, _>>()?,
+            accepted_mints,
+            accepted_units,
             min_fee: config.lsp.min_fee,
             fee_ppk: config.lsp.fee_ppk,
+            mint_fee_policies,
+            nostr: nostr_transport.as_ref().map(|n| n.info()),
+            htlc: htlc_locks.as_ref().map(|h| h.info()),
+            refund: refund_locks.as_ref().map(|r| r.info()),
+            forwarding_fee_bounds: config.lsp.forwarding_fee_bounds.as_ref().map(|b| {
+                ForwardingFeeBounds {
+                    min_base_msat: b.min_base_msat,
+                    max_base_msat: b.max_base_msat,
+                    min_ppm: b.min_ppm,
+                    max_ppm: b.max_ppm,
+                }
+            }),
+            funding_fee_rate_sat_per_vb: config.lsp.funding_fee_rate_sat_per_vb.unwrap_or(10),
+            bolt12: config.lsp.bolt12,
+            max_channels_per_peer: config.lsp.max_channels_per_peer,
+            max_capacity_per_peer_sat: config.lsp.max_capacity_per_peer_sat,
+            inbound_only: config.lsp.inbound_only,
+            anti_spam,
         };
 
         let payment_url = config.lsp.payment_url.clone();
 
-        let db = Db::new(work_dir.join("cashu-lsp.redb"))?;
+        let default_limits = HttpLimits::default();
+        let http_limits = HttpLimits {
+            request_timeout: config
+                .lsp
+                .request_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default_limits.request_timeout),
+            payment_timeout: config
+                .lsp
+                .payment_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default_limits.payment_timeout),
+            max_body_bytes: config
+                .lsp
+                .max_body_bytes
+                .unwrap_or(default_limits.max_body_bytes),
+            max_payment_body_bytes: config
+                .lsp
+                .max_payment_body_bytes
+                .unwrap_or(default_limits.max_payment_body_bytes),
+        };
+
+        // Kept apart from the runtime lists, a peer on both is denied
+        let mut peer_access = HashMap::new();
+
+        for peer in config.lsp.allowed_peers.iter() {
+            peer_access.insert(PublicKey::from_str(peer)?, PeerAccess::Allow);
+        }
+
+        for peer in config.lsp.denied_peers.iter() {
+            peer_access.insert(PublicKey::from_str(peer)?, PeerAccess::Deny);
+        }
+
+        let mut lsp_state = CashuLspState::new(
+            Arc::clone(&cdk_ldk),
+            cashu_lsp_info,
+            payment_url,
+            db.clone(),
+        )
+        .with_limits(http_limits)
+        .with_exposure_limits(exposure_limits)
+        .with_peer_access(peer_access)
+        .with_events(events.clone());
+
+        if let Some(nostr_transport) = nostr_transport {
+            lsp_state = lsp_state.with_nostr(nostr_transport);
+        }
+
+        if let Some(htlc_locks) = htlc_locks {
+            lsp_state = lsp_state.with_htlc(htlc_locks);
+        }
+
+        if let Some(refund_locks) = refund_locks {
+            lsp_state = lsp_state.with_refunds(refund_locks);
+        }
+
+        if let Some(exchange_rates) = exchange_rates {
+            lsp_state = lsp_state.with_rates(exchange_rates);
+        }
+
+        let days = |days: Option<u64>| {
+            days.map(|d| Duration::from_secs(d.saturating_mul(24 * 60 * 60)))
+        };
 
-        let service =
-            create_cashu_lsp_router(Arc::clone(&cdk_ldk), cashu_lsp_info, payment_url, db).await?;
+        let retention = &config.retention;
+
+        if retention.quote_days.is_some()
+            || retention.unpaid_quote_days.is_some()
+            || retention.event_days.is_some()
+        {
+            lsp_state = lsp_state.with_retention(RetentionPolicy {
+                max_age: days(retention.quote_days),
+                unpaid_max_age: days(retention.unpaid_quote_days),
+                event_max_age: days(retention.event_days),
+                archive_path: config
+                    .retention
+                    .archive_path
+                    .as_ref()
+                    .map(|path| work_dir.join(path)),
+                interval: config
+                    .retention
+                    .interval_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_RETENTION_INTERVAL),
+            });
+        }
+
+        if !config.lsp.sandbox_keys.is_empty() {
+            let sandbox_keys = config
+                .lsp
+                .sandbox_keys
+                .iter()
+                .map(|k| (k.key.clone(), k.balance_sat))
+                .collect();
+
+            lsp_state = lsp_state.with_sandbox(Sandbox::new(
+                open_store("cashu-lsp-sandbox")?,
+                sandbox_keys,
+            ));
+        }
+
+        // Start gRPC management server
+        let grpc_addr =
+            format!("{}:{}", config.grpc.host, config.grpc.port).parse::<SocketAddr>()?;
+        let shutdown = Shutdown::new();
+
+        let signal_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            signal_shutdown.stop();
+        });
+
+        let management_service = CdkLdkServer::new(cdk_ldk.clone(), db.clone())
+            .with_events(events)
+            .with_lsp(lsp_state.clone())
+            .with_shutdown(shutdown.clone())
+            .with_wallet_factory(wallet_factory);
+
+        let mut grpc_builder = Server::builder();
+
+        match (&config.grpc.tls_cert_path, &config.grpc.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let cert = std::fs::read_to_string(cert_path)?;
+                let key = std::fs::read_to_string(key_path)?;
+                let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+
+                if let Some(ca_path) = &config.grpc.tls_client_ca_path {
+                    let ca = std::fs::read_to_string(ca_path)?;
+                    tls = tls.client_ca_root(Certificate::from_pem(ca));
+                    tracing::info!("gRPC server requires client certificates");
+                }
+
+                grpc_builder = grpc_builder.tls_config(tls)?;
+            }
+            (None, None) => {
+                if config.grpc.tls_client_ca_path.is_some() {
+                    bail!("grpc.tls_client_ca_path requires tls_cert_path and tls_key_path");
+                }
+
+                if !grpc_addr.ip().is_loopback() {
+                    tracing::warn!(
+                        "gRPC management server on {} without TLS, anyone who can reach it controls the node",
+                        grpc_addr
+                    );
+                }
+            }
+            _ => bail!("grpc.tls_cert_path and grpc.tls_key_path must be set together"),
+        }
+
+        if config.grpc.auth_tokens.is_empty() {
+            tracing::warn!("No grpc.auth_tokens configured, management API is unauthenticated");
+        }
+
+        let grpc_auth = TokenAuth::new(config.grpc.auth_tokens.clone());
+
+        // Rotate tokens without restarting the node
+        let reload_auth = grpc_auth.clone();
+        let reload_path = config_path.clone();
+        tokio::spawn(async move {
+            let mut hangups = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(err) => {
+                    tracing::warn!("Could not listen for SIGHUP, tokens won't reload: {}", err);
+                    return;
+                }
+            };
+
+            while hangups.recv().await.is_some() {
+                match AppConfig::new(Some(&reload_path)) {
+                    Ok(config) => {
+                        reload_auth.set_tokens(config.grpc.auth_tokens);
+                        tracing::info!("Reloaded gRPC auth tokens");
+                    }
+                    Err(err) => tracing::error!("Could not reload config: {}", err),
+                }
+            }
+        });
+
+        // Standard health and reflection services, left unauthenticated so
+        // probes and grpcurl work without a token
+        let (health_reporter, health_service) = tonic_health::server::health_reporter();
+        health_reporter
+            .set_serving::<CdkLdkManagementServer<CdkLdkServer>>()
+            .await;
+
+        let health_node = Arc::clone(&cdk_ldk);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(GRPC_HEALTH_INTERVAL).await;
+                if health_node.inner.status().is_running {
+                    health_reporter
+                        .set_serving::<CdkLdkManagementServer<CdkLdkServer>>()
+                        .await;
+                    health_reporter
+                        .set_service_status("", ServingStatus::Serving)
+                        .await;
+                } else {
+                    health_reporter
+                        .set_not_serving::<CdkLdkManagementServer<CdkLdkServer>>()
+                        .await;
+                    health_reporter
+                        .set_service_status("", ServingStatus::NotServing)
+                        .await;
+                }
+            }
+        });
+
+        let reflection_service = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+            .build_v1()?;
+
+        let grpc_server = grpc_builder
+            .add_service(health_service)
+            .add_service(reflection_service)
+            .add_service(CdkLdkManagementServer::with_interceptor(
+                management_service,
+                grpc_auth,
+            ))
+            .serve_with_shutdown(grpc_addr, {
+                let shutdown = shutdown.clone();
+                async move { shutdown.requested().await }
+            });
+
+        let grpc_handle = tokio::spawn(grpc_server);
+
+        if let Some(metrics_port) = config.metrics.listen_port {
+            let metrics_addr = SocketAddr::from_str(&format!(
+                "{}:{}",
+                config.metrics.listen_host.as_deref().unwrap_or("127.0.0.1"),
+                metrics_port
+            ))?;
+
+            tracing::info!("Starting metrics server on {}", metrics_addr);
+
+            let metrics_listener = tokio::net::TcpListener::bind(metrics_addr).await?;
+            let shutdown = shutdown.clone();
+
+            tokio::spawn(async move {
+                let metrics_result = axum::serve(metrics_listener, create_metrics_router())
+                    .with_graceful_shutdown(async move { shutdown.requested().await })
+                    .await;
+
+                if let Err(err) = metrics_result {
+                    tracing::error!("Metrics server stopped with error: {}", err);
+                }
+            });
+        }
+
+        let service = create_cashu_lsp_router(lsp_state).await?;
 
         let service = service.layer(CorsLayer::permissive());
 
@@ -164,7 +802,10 @@ fn main() -> anyhow::Result<()> {
 
         let listener = tokio::net::TcpListener::bind(socket_addr).await?;
 
-        let axum_result = axum::serve(listener, service).with_graceful_shutdown(shutdown_signal());
+        let axum_result = axum::serve(listener, service).with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move { shutdown.requested().await }
+        });
 
         match axum_result.await {
             Ok(_) => {
@@ -177,15 +818,137 @@ fn main() -> anyhow::Result<()> {
             }
         }
 
-        // Wait for shutdown signal
-        signal::ctrl_c().await?;
-
         cdk_ldk.stop()?;
 
+        // Let the stop or restart call answer before exiting
+        if tokio::time::timeout(GRPC_SHUTDOWN_TIMEOUT, grpc_handle)
+            .await
+            .is_err()
+        {
+            tracing::warn!("gRPC server did not shut down in time");
+        }
+
+        if shutdown.restart_requested() {
+            restart_process()?;
+        }
+
         Ok(())
     })
 }
 
+/// Wallet mnemonic from config, or the one stored at `path`
+///
+/// A mnemonic is generated and stored on first start so ecash held by the
+/// wallet survives restarts. With `new_seed` the stored mnemonic is moved
+/// aside and replaced. With a `passphrase` the file is encrypted, and a
+/// plaintext file from before the passphrase was set is encrypted in place.
+/// Without one the file is plaintext and only its permissions protect it.
+///
+/// Only the ecash wallet uses this mnemonic. The Lightning node keeps its own
+/// `keys_seed` in its storage dir, so existing nodes keep their identity.
+fn load_or_create_mnemonic(
+    path: &Path,
+    configured: Option<&str>,
+    passphrase: Option<&str>,
+    new_seed: bool,
+) -> anyhow::Result<Mnemonic> {
+    if let Some(mnemonic) = configured {
+        if new_seed {
+            bail!(
+                "{} cannot be used with a configured mnemonic",
+                NEW_SEED_FLAG
+            );
+        }
+        return Ok(Mnemonic::from_str(mnemonic)?);
+    }
+
+    if new_seed && path.exists() {
+        let backup = path.with_extension(format!("{}.bak", unix_time()));
+        std::fs::rename(path, &backup)?;
+        tracing::warn!("Moved previous mnemonic to {}", backup.display());
+    }
+
+    if path.exists() {
+        let stored = std::fs::read_to_string(path)?;
+
+        if seed::is_encrypted(&stored) {
+            let passphrase = passphrase.ok_or_else(|| {
+                anyhow!(
+                    "{} is encrypted, set {} or mnemonic_passphrase",
+                    path.display(),
+                    MNEMONIC_PASSPHRASE_ENV
+                )
+            })?;
+            return seed::decrypt_mnemonic(&stored, passphrase);
+        }
+
+        let mnemonic = Mnemonic::from_str(stored.trim())?;
+
+        if let Some(passphrase) = passphrase {
+            write_mnemonic(path, &seed::encrypt_mnemonic(&mnemonic, passphrase)?)?;
+            tracing::info!("Encrypted wallet mnemonic at {}", path.display());
+        }
+
+        return Ok(mnemonic);
+    }
+
+    let mnemonic = Mnemonic::generate(12)?;
+
+    match passphrase {
+        Some(passphrase) => {
+            write_mnemonic(path, &seed::encrypt_mnemonic(&mnemonic, passphrase)?)?;
+            tracing::info!("Generated new wallet mnemonic at {}", path.display());
+        }
+        None => {
+            write_mnemonic(path, &mnemonic.to_string())?;
+            tracing::warn!(
+                "Generated new wallet mnemonic at {}, stored unencrypted as {} is not set",
+                path.display(),
+                MNEMONIC_PASSPHRASE_ENV
+            );
+        }
+    }
+
+    Ok(mnemonic)
+}
+
+/// Replace the mnemonic file at `path`, readable only by its owner
+fn write_mnemonic(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    writeln!(file, "{}", contents)?;
+    file.sync_all()?;
+
+    std::fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// Replace the process with a fresh copy of itself
+///
+/// `--new-seed` is dropped so a restart keeps the current mnemonic.
+fn restart_process() -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    tracing::info!("Restarting");
+
+    let err = std::process::Command::new(std::env::current_exe()?)
+        .args(
+            std::env::args_os()
+                .skip(1)
+                .filter(|arg| arg != NEW_SEED_FLAG),
+        )
+        .exec();
+
+    bail!("Could not restart: {}", err)
+}
+
 async fn shutdown_signal() {
     tokio::signal::ctrl_c()
         .await
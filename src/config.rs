@@ -1,46 +1,270 @@
 use anyhow::Result;
 use config::{Config, ConfigError, File};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Deserialize, Default, Serialize)]
 pub struct BitcoinConfig {
+    /// One of "bitcoin" (or "mainnet"), "testnet", "signet" or "regtest", defaults to regtest
+    pub network: Option<String>,
     pub rpc_host: String,
     pub rpc_port: u16,
     pub rpc_user: String,
     pub rpc_password: String,
 }
 
+/// Chain data backend for the node
+///
+/// Defaults to the bitcoind RPC set in `[bitcoin]`.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct ChainConfig {
+    /// One of "bitcoind", "esplora" or "electrum"
+    pub backend: Option<String>,
+    /// Server URL for the esplora and electrum backends
+    pub url: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Default, Serialize)]
 pub struct LdkConfig {
     pub listen_host: String,
     pub listen_port: u16,
+    /// Alias announced on the public graph, at most 32 bytes
+    pub alias: Option<String>,
+    /// Most verbose node log level: gossip, trace, debug, info, warn or error, defaults to debug
+    pub log_level: Option<String>,
+    /// Rapid Gossip Sync server, P2P gossip is used when unset
+    pub rgs_url: Option<String>,
+    /// Directory for the node's state, defaults to `ldk-node` in the LSP's work dir
+    pub storage_dir: Option<PathBuf>,
+    /// Versioned Storage Service endpoint, keeps node state remotely when set
+    pub vss_url: Option<String>,
+    /// Store id on the VSS server, defaults to the node's network
+    pub vss_store_id: Option<String>,
+    /// Headers sent with every VSS request, e.g. for authorization
+    pub vss_headers: HashMap<String, String>,
+    /// Open anchor channels, defaults to true
+    pub anchor_channels: Option<bool>,
+    /// Onchain funds kept per anchor channel to CPFP force closes, defaults to 25000 sats
+    pub anchor_reserve_sats: Option<u64>,
+    /// Peers trusted to bump force closes themselves, no reserve is kept for them
+    pub anchor_trusted_peers: Vec<String>,
+    /// Peers whose channels may be used before the funding transaction confirms
+    pub trusted_peers_0conf: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default, Serialize)]
 pub struct GrpcConfig {
     pub host: String,
     pub port: u16,
+    /// PEM certificate chain served by the management server, enables TLS
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM private key for `tls_cert_path`
+    pub tls_key_path: Option<PathBuf>,
+    /// PEM CA that client certificates must chain to, enables mutual TLS
+    pub tls_client_ca_path: Option<PathBuf>,
+    /// Bearer tokens accepted by the management server, reloaded on SIGHUP
+    pub auth_tokens: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default, Serialize)]
 pub struct LspConfig {
     pub listen_host: String,
     pub listen_port: u16,
+    /// Wallet mnemonic, overrides the one stored in the work dir
+    pub mnemonic: Option<String>,
+    /// Passphrase the mnemonic stored in the work dir is encrypted with
+    pub mnemonic_passphrase: Option<String>,
+    /// Ecash wallet store, "redb" or "sqlite", defaults to redb
+    pub wallet_db: Option<String>,
+    /// Quote store, "redb" or "sqlite", defaults to redb
+    pub quote_db: Option<String>,
     pub min_channel_size_sat: u64,
     pub max_channel_size_sat: u64,
     pub min_fee: u64,
     pub fee_ppk: u64,
     pub payment_url: String,
     pub accepted_mints: Vec<String>,
+    /// Currency units accepted for payment, defaults to sat
+    pub accepted_units: Vec<String>,
+    pub mint_fee_policies: Vec<MintFeePolicyConfig>,
+    pub melt_policies: Vec<MeltPolicyConfig>,
+    pub mint_exposure_limits: Vec<MintExposureLimitConfig>,
+    pub sandbox_keys: Vec<SandboxKeyConfig>,
+    /// Timeout for HTTP requests, defaults to 30 seconds
+    pub request_timeout_secs: Option<u64>,
+    /// Timeout for `/payment` requests, defaults to 120 seconds
+    pub payment_timeout_secs: Option<u64>,
+    /// Max HTTP request body size, defaults to 16 KiB
+    pub max_body_bytes: Option<usize>,
+    /// Max `/payment` request body size, defaults to 256 KiB
+    pub max_payment_body_bytes: Option<usize>,
+    /// Range clients may pick the LSP's forwarding fees from, unset disables the option
+    pub forwarding_fee_bounds: Option<ForwardingFeeBoundsConfig>,
+    /// Fee rate used to estimate funding costs in quotes, defaults to 10 sat/vB
+    pub funding_fee_rate_sat_per_vb: Option<u64>,
+    /// Offer a BOLT12 offer with each quote as an alternative to ecash
+    pub bolt12: bool,
+    /// Max channels a single node may buy, unlimited when unset
+    pub max_channels_per_peer: Option<u32>,
+    /// Max total capacity a single node may buy, unlimited when unset
+    pub max_capacity_per_peer_sat: Option<u64>,
+    /// Only sell channels to these nodes, unless set otherwise at runtime
+    pub allowed_peers: Vec<String>,
+    /// Never sell channels to these nodes, unless set otherwise at runtime
+    pub denied_peers: Vec<String>,
+    /// Offer quotes where the client only pays the service fee and the LSP
+    /// funds the whole channel
+    pub inbound_only: bool,
+    /// Require proof of work or an ecash bond to create quotes
+    pub anti_spam: Option<AntiSpamConfig>,
+    /// Accept NUT-14 HTLC locked payments, locked for this long after the quote is created
+    pub htlc_locktime_secs: Option<u64>,
+    /// Require payments locked to the LSP and refundable to the payer after this long
+    pub refund_locktime_secs: Option<u64>,
+}
+
+/// Anti-spam requirement for quote creation
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct AntiSpamConfig {
+    /// Either "pow" or "bond"
+    pub mode: String,
+    pub pow_difficulty_bits: u32,
+    pub bond_sats: u64,
+}
+
+/// Forwarding fees clients may request on the LSP's side of a new channel
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct ForwardingFeeBoundsConfig {
+    pub min_base_msat: u32,
+    pub max_base_msat: u32,
+    pub min_ppm: u32,
+    pub max_ppm: u32,
+}
+
+/// API key routing requests to the sandbox instead of real funds
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct SandboxKeyConfig {
+    pub key: String,
+    /// Fake balance in sats the key can spend on sandbox quotes
+    pub balance_sat: u64,
+}
+
+/// Automatic melting of ecash held at a single mint
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct MeltPolicyConfig {
+    pub mint_url: String,
+    /// Balance in sats above which ecash is melted
+    pub threshold_sats: u64,
+    /// Balance in sats left at the mint after melting, defaults to 0
+    pub target_sats: Option<u64>,
+    /// How often the balance is checked, defaults to 300 seconds
+    pub interval_secs: Option<u64>,
+}
+
+/// Most ecash the LSP will hold at a single mint
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct MintExposureLimitConfig {
+    pub mint_url: String,
+    /// Balance in sats at which quotes using the mint are refused
+    pub max_balance_sats: u64,
+}
+
+/// Fee overrides for a single accepted mint
+///
+/// Unset values fall back to the `[lsp]` defaults.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct MintFeePolicyConfig {
+    pub mint_url: String,
+    pub min_fee: Option<u64>,
+    pub fee_ppk: Option<u64>,
+}
+
+/// Proxy for outbound connections, e.g. a local Tor daemon
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct ProxyConfig {
+    /// Proxy URL such as `socks5h://127.0.0.1:9050`, unset connects directly
+    pub url: Option<String>,
+}
+
+/// Nostr transport for NUT-18 payment requests
+///
+/// Enabled when a secret key and at least one relay are set.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct NostrConfig {
+    pub secret_key: Option<String>,
+    pub relays: Vec<String>,
+}
+
+/// Exchange rates for accepting units other than sat and msat, e.g. usd
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct RatesConfig {
+    /// Parts per thousand a payment may fall short as the rate moves, defaults to 10
+    pub slippage_ppk: Option<u64>,
+    /// How often rates are fetched, defaults to 60 seconds
+    pub refresh_secs: Option<u64>,
+    /// How long a rate is used without a successful fetch, defaults to 600 seconds
+    ///
+    /// Should be longer than `refresh_secs` or rates expire between fetches.
+    pub max_age_secs: Option<u64>,
+    pub units: Vec<UnitRateConfig>,
+}
+
+/// Where the bitcoin price in a unit comes from
+///
+/// Either `url` and `pointer` or `fixed_price` must be set.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct UnitRateConfig {
+    pub unit: String,
+    /// JSON endpoint returning the price of one bitcoin in whole units
+    pub url: Option<String>,
+    /// JSON pointer to the price in the response, e.g. "/data/amount"
+    pub pointer: Option<String>,
+    /// Constant price of one bitcoin in whole units
+    pub fixed_price: Option<f64>,
+    /// Smallest denominations per whole unit, defaults to 100
+    pub minor_units: Option<u64>,
+}
+
+/// Listener for the Prometheus `/metrics` endpoint
+///
+/// Disabled unless `listen_port` is set.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct MetricsConfig {
+    /// Defaults to 127.0.0.1
+    pub listen_host: Option<String>,
+    pub listen_port: Option<u16>,
+}
+
+/// Pruning of old quotes and journal events
+///
+/// Disabled unless one of `quote_days`, `unpaid_quote_days` or `event_days` is set.
+#[derive(Debug, Deserialize, Default, Serialize)]
+pub struct RetentionConfig {
+    /// Days `ChannelOpen` and `ChannelExpired` quotes are kept
+    pub quote_days: Option<u64>,
+    /// Days `Unpaid` quotes are kept after creation or their last renewal
+    pub unpaid_quote_days: Option<u64>,
+    /// Days node and quote events are kept in the journal
+    pub event_days: Option<u64>,
+    /// JSON lines file pruned quotes are appended to, relative to the work dir, unset deletes them
+    pub archive_path: Option<PathBuf>,
+    /// How often quotes are pruned, defaults to 3600 seconds
+    pub interval_secs: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Default, Serialize)]
 pub struct AppConfig {
     pub bitcoin: BitcoinConfig,
+    pub chain: ChainConfig,
     pub ldk: LdkConfig,
     pub grpc: GrpcConfig,
     pub lsp: LspConfig,
+    pub metrics: MetricsConfig,
+    pub nostr: NostrConfig,
+    pub proxy: ProxyConfig,
+    pub rates: RatesConfig,
+    pub retention: RetentionConfig,
 }
 
 impl AppConfig {
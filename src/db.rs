@@ -1,17 +1,293 @@
-use std::{path::PathBuf, sync::Arc};
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
-use anyhow::{Result, anyhow};
-use redb::{Database, ReadableTable, TableDefinition};
+use anyhow::{Result, anyhow, bail};
+use cdk::mint_url::MintUrl;
+use cdk::nuts::PaymentRequestPayload;
+use ldk_node::UserChannelId;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use redb::{
+    Database, Key, MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable,
+    TableDefinition, Value, WriteTransaction,
+};
 use uuid::Uuid;
 
-use crate::types::{QuoteInfo, QuoteState};
+use crate::clock::{Clock, SystemClock};
+use crate::types::{
+    ChannelOpenJob, FeeLedgerEntry, LspEvent, LspEventKind, OfferRecord, PeerAccess, PeerSla,
+    QuoteActor, QuoteAuditEntry, QuoteChannel, QuoteInfo, QuotePayment, QuoteRefund, QuoteState,
+    QuoteStateTransition, SignedReceipt,
+};
 
 // <Y, QuoteInfo>
 const QUOTES_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("quotes");
+// <NodePubkey, QuoteId>
+const NODE_QUOTES_TABLE: MultimapTableDefinition<&[u8], &[u8]> =
+    MultimapTableDefinition::new("node_quotes");
+// <NodePubkey, PeerSla>
+const PEER_SLA_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("peer_sla");
+// <NodePubkey, PeerAccess>
+const PEER_ACCESS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("peer_access");
+// <OfferId, OfferRecord>
+const OFFERS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("offers");
+// <Seq, LspEvent>
+const EVENTS_TABLE: TableDefinition<u64, &str> = TableDefinition::new("events");
+// <QuoteId, PaymentRequestPayload>
+const PENDING_PAYMENTS_TABLE: TableDefinition<&[u8], &str> =
+    TableDefinition::new("pending_payments");
+// <QuoteId, QuotePayment>
+const QUOTE_PAYMENTS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("quote_payments");
+// <QuoteId, PaymentRequestPayload>
+const LOCKED_PAYMENTS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("locked_payments");
+// <QuoteId, SignedReceipt>
+const RECEIPTS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("receipts");
+// <QuoteId, Hex hash of the accepted PaymentRequestPayload>
+const PAYMENT_HASHES_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("payment_hashes");
+// <(Recorded at, QuoteId), FeeLedgerEntry>
+const FEE_LEDGER_TABLE: TableDefinition<(u64, u128), &str> = TableDefinition::new("fee_ledger");
+// <QuoteId, QuoteRefund>
+const REFUNDS_TABLE: TableDefinition<&[u8], &str> = TableDefinition::new("refunds");
+// <QuoteId, ChannelOpenJob>
+const CHANNEL_OPEN_JOBS_TABLE: TableDefinition<&[u8], &str> =
+    TableDefinition::new("channel_open_jobs");
+// <MintUrl, Accepted>
+const MINTS_TABLE: TableDefinition<&str, bool> = TableDefinition::new("mints");
+// Consumer name => last event sequence number it handled
+const EVENT_CURSORS_TABLE: TableDefinition<&str, u64> = TableDefinition::new("event_cursors");
+// <Seq, QuoteAuditEntry>
+const QUOTE_AUDIT_TABLE: TableDefinition<u64, &str> = TableDefinition::new("quote_audit");
+// <(QuoteId, Seq), QuoteStateTransition>
+const QUOTE_HISTORY_TABLE: TableDefinition<(u128, u64), &str> =
+    TableDefinition::new("quote_history");
 
 #[derive(Clone)]
 pub struct Db {
     db: Arc<Database>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Storage for quotes, payments and the rest of the LSP state
+///
+/// [`Db`] is the redb backed implementation used by default.
+pub trait LspStore: Send + Sync {
+    /// Check the database can be read
+    fn check(&self) -> Result<()>;
+
+    /// Insert or replace a quote, auditing a state change as made by `actor`
+    fn add_quote(&self, quote_info: &QuoteInfo, actor: QuoteActor) -> Result<()>;
+
+    fn get_quote(&self, quote_id: Uuid) -> Result<QuoteInfo>;
+
+    fn list_quotes(&self) -> Result<Vec<QuoteInfo>>;
+
+    /// Up to `limit` quotes with an id greater than `after`, ordered by id
+    fn list_quotes_after(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<QuoteInfo>>;
+
+    /// Quotes for channels to `node_pubkey`, without scanning every quote
+    fn list_node_quotes(&self, node_pubkey: PublicKey) -> Result<Vec<QuoteInfo>>;
+
+    /// Delete quotes with their history, audit entries, payments, receipts and refunds
+    fn remove_quotes(&self, quote_ids: &[Uuid]) -> Result<()>;
+
+    /// Write a consistent snapshot of the store to `path`, which must not exist
+    fn backup(&self, path: &Path) -> Result<()>;
+
+    fn update_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo>;
+
+    /// Move a quote from `from` to `to` in a single write transaction
+    ///
+    /// Returns the quote as it was before, its state is only changed if it
+    /// was `from`, so concurrent callers can't both make the same transition.
+    fn transition_quote_state(
+        &self,
+        quote_id: Uuid,
+        from: QuoteState,
+        to: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo>;
+
+    /// Manually set the state of a quote, recording `reason` in its history
+    ///
+    /// Unlike [`LspStore::update_quote_state`] the transition is recorded even if
+    /// the state is unchanged so every override is audited.
+    fn override_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        reason: &str,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo>;
+
+    /// Record why the last payment for a quote failed, or clear it
+    fn set_payment_error(&self, quote_id: Uuid, payment_error: Option<String>) -> Result<()>;
+
+    /// Record the funding details of a quote's channel, leaving the rest of it untouched
+    fn set_quote_channel(&self, quote_id: Uuid, channel: Option<QuoteChannel>) -> Result<()>;
+
+    /// Record the node's id for a quote's channel, leaving the rest of it untouched
+    fn set_quote_channel_id(&self, quote_id: Uuid, channel_id: Option<UserChannelId>)
+    -> Result<()>;
+
+    /// Record the proofs redeemed for a quote
+    fn add_quote_payment(&self, quote_id: Uuid, payment: &QuotePayment) -> Result<()>;
+
+    /// Proofs redeemed for a quote, `None` if it was not paid with ecash
+    fn get_quote_payment(&self, quote_id: Uuid) -> Result<Option<QuotePayment>>;
+
+    /// Store the receipt issued for a quote's payment
+    fn add_receipt(&self, quote_id: Uuid, receipt: &SignedReceipt) -> Result<()>;
+
+    /// Receipt issued for a quote, `None` until its payment is redeemed
+    fn get_receipt(&self, quote_id: Uuid) -> Result<Option<SignedReceipt>>;
+
+    /// Record a refund before paying it out
+    ///
+    /// Returns `false` if the quote already has a refund, so no quote is refunded twice.
+    fn add_refund(&self, refund: &QuoteRefund) -> Result<bool>;
+
+    /// Replace the refund of a quote, e.g. once it has been paid out
+    fn update_refund(&self, refund: &QuoteRefund) -> Result<()>;
+
+    /// Forget a refund that could not be paid out so it can be retried
+    fn remove_refund(&self, quote_id: Uuid) -> Result<()>;
+
+    fn get_refund(&self, quote_id: Uuid) -> Result<Option<QuoteRefund>>;
+
+    fn list_refunds(&self) -> Result<Vec<QuoteRefund>>;
+
+    /// Record the fee earned on a completed quote
+    fn add_fee_entry(&self, entry: &FeeLedgerEntry) -> Result<()>;
+
+    /// Fees recorded at or after `since` and before `until`, oldest first
+    fn list_fee_entries(&self, since: u64, until: u64) -> Result<Vec<FeeLedgerEntry>>;
+
+    /// Keep HTLC locked proofs for a quote until the preimage is revealed
+    fn add_locked_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<()>;
+
+    /// HTLC locked proofs of a quote, `None` once redeemed
+    fn get_locked_payment(&self, quote_id: Uuid) -> Result<Option<PaymentRequestPayload>>;
+
+    fn remove_locked_payment(&self, quote_id: Uuid) -> Result<()>;
+
+    /// Remember the hash of the payload a quote was paid with, to recognise replays
+    fn set_payment_hash(&self, quote_id: Uuid, payload_hash: &str) -> Result<()>;
+
+    /// Hash of the payload a quote was paid with, `None` until paid
+    fn get_payment_hash(&self, quote_id: Uuid) -> Result<Option<String>>;
+
+    /// Persist the channel open job of a quote, replacing the previous attempt
+    fn put_channel_open_job(&self, job: &ChannelOpenJob) -> Result<()>;
+
+    fn get_channel_open_job(&self, quote_id: Uuid) -> Result<Option<ChannelOpenJob>>;
+
+    fn remove_channel_open_job(&self, quote_id: Uuid) -> Result<()>;
+
+    fn list_channel_open_jobs(&self) -> Result<Vec<ChannelOpenJob>>;
+
+    /// Persist an accepted payment until it has been processed
+    ///
+    /// Returns `false` if a payment for the quote is already pending.
+    fn add_pending_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<bool>;
+
+    fn remove_pending_payment(&self, quote_id: Uuid) -> Result<()>;
+
+    fn is_payment_pending(&self, quote_id: Uuid) -> Result<bool>;
+
+    fn list_pending_payments(&self) -> Result<Vec<(Uuid, PaymentRequestPayload)>>;
+
+    /// State transitions of a quote at or after `since`, oldest first
+    fn list_quote_history(
+        &self,
+        quote_id: Uuid,
+        since: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<QuoteStateTransition>>;
+
+    /// Audited state changes after `cursor`, of one quote or all, oldest first
+    fn list_quote_audit(
+        &self,
+        quote_id: Option<Uuid>,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<Vec<QuoteAuditEntry>>;
+
+    /// Record an availability sample for a peer
+    fn add_sla_sample(&self, node_pubkey: PublicKey, connected: bool, usable: bool) -> Result<()>;
+
+    /// Record a payment forwarded out through a channel sold to a peer
+    fn record_sla_forward(&self, node_pubkey: PublicKey, amount_msat: u64) -> Result<()>;
+
+    /// Record a channel sold to a peer closing
+    fn record_sla_close(&self, node_pubkey: PublicKey) -> Result<()>;
+
+    fn list_peer_sla(&self) -> Result<Vec<PeerSla>>;
+
+    /// Put a peer on the runtime allow or deny list, or remove it with `None`
+    fn set_peer_access(&self, node_pubkey: PublicKey, access: Option<PeerAccess>) -> Result<()>;
+
+    fn list_peer_access(&self) -> Result<Vec<(PublicKey, PeerAccess)>>;
+
+    /// Record a mint added or removed at runtime
+    fn set_mint_accepted(&self, mint_url: &MintUrl, accepted: bool) -> Result<()>;
+
+    /// Mints added or removed at runtime, these take precedence over the config
+    fn list_mint_overrides(&self) -> Result<Vec<(MintUrl, bool)>>;
+
+    /// Whether channels may be sold to a peer
+    ///
+    /// Peers set at runtime take precedence over the `configured` lists.
+    /// Denied peers are always refused, and once any peer is allowed only
+    /// allowed peers are served.
+    fn is_peer_allowed(
+        &self,
+        node_pubkey: PublicKey,
+        configured: &HashMap<PublicKey, PeerAccess>,
+    ) -> Result<bool> {
+        let mut peers = configured.clone();
+        peers.extend(self.list_peer_access()?);
+
+        if let Some(access) = peers.get(&node_pubkey) {
+            return Ok(*access == PeerAccess::Allow);
+        }
+
+        Ok(!peers.values().any(|access| *access == PeerAccess::Allow))
+    }
+
+    /// Record an offer so it can be listed later, ldk-node doesn't keep them
+    fn add_offer(
+        &self,
+        offer_id: [u8; 32],
+        offer: String,
+        description: String,
+        amount_msats: Option<u64>,
+    ) -> Result<OfferRecord>;
+
+    fn list_offers(&self) -> Result<Vec<OfferRecord>>;
+
+    /// Append an event to the journal, assigning the next sequence number
+    fn append_event(&self, kind: LspEventKind) -> Result<LspEvent>;
+
+    /// Journal events with a sequence number greater than `cursor`, oldest first
+    fn list_events(&self, cursor: u64, limit: usize) -> Result<Vec<LspEvent>>;
+
+    /// Delete journal events older than `timestamp`, returning how many
+    ///
+    /// The latest event is always kept so sequence numbers keep increasing.
+    fn remove_events_before(&self, timestamp: u64) -> Result<usize>;
+
+    /// Last event `consumer` handled, the latest event if it never recorded one
+    fn get_event_cursor(&self, consumer: &str) -> Result<u64>;
+
+    /// Record that `consumer` handled every event up to `seq`
+    fn set_event_cursor(&self, consumer: &str, seq: u64) -> Result<()>;
 }
 
 impl Db {
@@ -22,23 +298,249 @@ impl Db {
         {
             // Open all tables to init a new db
             let _ = write_txn.open_table(QUOTES_TABLE)?;
+            let _ = write_txn.open_table(PEER_SLA_TABLE)?;
+            let _ = write_txn.open_table(EVENTS_TABLE)?;
+            let _ = write_txn.open_table(EVENT_CURSORS_TABLE)?;
+            let _ = write_txn.open_table(QUOTE_HISTORY_TABLE)?;
+            let _ = write_txn.open_table(QUOTE_AUDIT_TABLE)?;
+            let _ = write_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+            let _ = write_txn.open_table(PEER_ACCESS_TABLE)?;
+            let _ = write_txn.open_table(OFFERS_TABLE)?;
+            let _ = write_txn.open_table(MINTS_TABLE)?;
+            let _ = write_txn.open_table(QUOTE_PAYMENTS_TABLE)?;
+            let _ = write_txn.open_table(LOCKED_PAYMENTS_TABLE)?;
+            let _ = write_txn.open_table(RECEIPTS_TABLE)?;
+            let _ = write_txn.open_table(REFUNDS_TABLE)?;
+            let _ = write_txn.open_table(FEE_LEDGER_TABLE)?;
+            let _ = write_txn.open_table(PAYMENT_HASHES_TABLE)?;
+            let _ = write_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+        }
+
+        Self::index_node_quotes(&write_txn)?;
+
+        write_txn.commit()?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Override the clock used to timestamp records
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Index quotes stored before the node pubkey index existed
+    fn index_node_quotes(write_txn: &WriteTransaction) -> Result<()> {
+        let quote_table = write_txn.open_table(QUOTES_TABLE)?;
+        let mut index_table = write_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+
+        if index_table.iter()?.next().is_some() {
+            return Ok(());
+        }
+
+        for entry in quote_table.iter()? {
+            let (quote_id, quote_value) = entry?;
+            let quote: QuoteInfo = serde_json::from_str(quote_value.value())?;
+            index_table.insert(quote.node_pubkey.serialize().as_slice(), quote_id.value())?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the state of a quote if it is currently `from`, or unconditionally
+    /// without one, returning the quote as it was before
+    fn set_quote_state(
+        &self,
+        quote_id: Uuid,
+        from: Option<QuoteState>,
+        quote_state: QuoteState,
+        reason: Option<&str>,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        let write_txn = self.db.begin_write()?;
+
+        let current_quote;
+
+        {
+            let mut quote: QuoteInfo;
+            let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
+            {
+                let quote_value = quote_table
+                    .get(quote_id.into_bytes().as_slice())?
+                    .ok_or(anyhow!("Unknown quote"))?;
+
+                let quote_value = quote_value.value();
+
+                quote = serde_json::from_str(quote_value)?;
+            }
+
+            current_quote = quote.clone();
+
+            if from.is_some_and(|from| from != current_quote.state) {
+                return Ok(current_quote);
+            }
+
+            quote.state = quote_state;
+
+            quote_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(&quote)?.as_str(),
+            )?;
+        }
+
+        if current_quote.state != quote_state || reason.is_some() {
+            self.record_transition(
+                &write_txn,
+                quote_id,
+                Some(current_quote.state),
+                quote_state,
+                reason,
+                actor,
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(current_quote)
+    }
+
+    /// Append a state transition to the quote's history and the audit log
+    fn record_transition(
+        &self,
+        write_txn: &WriteTransaction,
+        quote_id: Uuid,
+        from: Option<QuoteState>,
+        state: QuoteState,
+        reason: Option<&str>,
+        actor: QuoteActor,
+    ) -> Result<()> {
+        let mut history_table = write_txn.open_table(QUOTE_HISTORY_TABLE)?;
+        let id = quote_id.as_u128();
+
+        let seq = match history_table.range((id, 0)..=(id, u64::MAX))?.next_back() {
+            Some(entry) => entry?.0.value().1 + 1,
+            None => 0,
+        };
+
+        let transition = QuoteStateTransition {
+            state,
+            timestamp: self.clock.now(),
+            reason: reason.map(str::to_string),
+        };
+
+        history_table.insert((id, seq), serde_json::to_string(&transition)?.as_str())?;
+
+        let mut audit_table = write_txn.open_table(QUOTE_AUDIT_TABLE)?;
+
+        let audit_seq = match audit_table.last()? {
+            Some((last_seq, _)) => last_seq.value() + 1,
+            None => 1,
+        };
+
+        let entry = QuoteAuditEntry {
+            seq: audit_seq,
+            quote_id,
+            from,
+            to: state,
+            timestamp: transition.timestamp,
+            reason: transition.reason,
+            actor,
+        };
+
+        audit_table.insert(audit_seq, serde_json::to_string(&entry)?.as_str())?;
+
+        Ok(())
+    }
+
+    /// Read, update and write back a peer's SLA in one transaction
+    fn update_peer_sla(
+        &self,
+        node_pubkey: PublicKey,
+        update: impl FnOnce(&mut PeerSla),
+    ) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut sla_table = write_txn.open_table(PEER_SLA_TABLE)?;
+            let key = node_pubkey.serialize();
+
+            let mut sla = match sla_table.get(key.as_slice())? {
+                Some(value) => serde_json::from_str(value.value())?,
+                None => PeerSla::new(node_pubkey),
+            };
+
+            update(&mut sla);
+
+            sla_table.insert(key.as_slice(), serde_json::to_string(&sla)?.as_str())?;
         }
 
         write_txn.commit()?;
 
-        Ok(Self { db: Arc::new(db) })
+        Ok(())
+    }
+}
+
+/// Copy every entry of `table` in `read_txn` into another database
+fn copy_table<K: Key + 'static, V: Value + 'static>(
+    read_txn: &ReadTransaction,
+    write_txn: &WriteTransaction,
+    table: TableDefinition<K, V>,
+) -> Result<()> {
+    let source = read_txn.open_table(table)?;
+    let mut target = write_txn.open_table(table)?;
+
+    for entry in source.iter()? {
+        let (key, value) = entry?;
+        target.insert(key.value(), value.value())?;
+    }
+
+    Ok(())
+}
+
+impl LspStore for Db {
+    fn check(&self) -> Result<()> {
+        let read_txn = self.db.begin_read()?;
+        let _ = read_txn.open_table(QUOTES_TABLE)?;
+
+        Ok(())
     }
 
-    pub fn add_quote(&self, quote_info: &QuoteInfo) -> Result<()> {
+    fn add_quote(&self, quote_info: &QuoteInfo, actor: QuoteActor) -> Result<()> {
         let write_txn = self.db.begin_write()?;
 
+        let previous_state;
+
         {
             let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
 
-            let _ = quote_table.insert(
+            previous_state = quote_table
+                .insert(
+                    quote_info.id.into_bytes().as_slice(),
+                    serde_json::to_string(quote_info)?.as_str(),
+                )?
+                .map(|value| serde_json::from_str::<QuoteInfo>(value.value()))
+                .transpose()?
+                .map(|quote| quote.state);
+
+            let mut index_table = write_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+            index_table.insert(
+                quote_info.node_pubkey.serialize().as_slice(),
                 quote_info.id.into_bytes().as_slice(),
-                serde_json::to_string(quote_info)?.as_str(),
-            );
+            )?;
+        }
+
+        if previous_state != Some(quote_info.state) {
+            self.record_transition(
+                &write_txn,
+                quote_info.id,
+                previous_state,
+                quote_info.state,
+                None,
+                actor,
+            )?;
         }
 
         write_txn.commit()?;
@@ -46,7 +548,7 @@ impl Db {
         Ok(())
     }
 
-    pub fn get_quote(&self, quote_id: Uuid) -> Result<QuoteInfo> {
+    fn get_quote(&self, quote_id: Uuid) -> Result<QuoteInfo> {
         let read_txn = self.db.begin_read()?;
 
         let quote_table = read_txn.open_table(QUOTES_TABLE)?;
@@ -60,27 +562,236 @@ impl Db {
         Ok(quote)
     }
 
-    pub fn update_quote_state(&self, quote_id: Uuid, quote_state: QuoteState) -> Result<QuoteInfo> {
+    fn list_quotes(&self) -> Result<Vec<QuoteInfo>> {
+        let read_txn = self.db.begin_read()?;
+
+        let quote_table = read_txn.open_table(QUOTES_TABLE)?;
+
+        let mut quotes = vec![];
+
+        for entry in quote_table.iter()? {
+            let (_, quote_value) = entry?;
+            let quote: QuoteInfo = serde_json::from_str(quote_value.value())?;
+            quotes.push(quote);
+        }
+
+        Ok(quotes)
+    }
+
+    fn list_quotes_after(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<QuoteInfo>> {
+        let read_txn = self.db.begin_read()?;
+
+        let quote_table = read_txn.open_table(QUOTES_TABLE)?;
+
+        let after = after.map(|id| id.into_bytes());
+        let start = match &after {
+            Some(id) => Bound::Excluded(id.as_slice()),
+            None => Bound::Unbounded,
+        };
+
+        let mut quotes = vec![];
+
+        for entry in quote_table
+            .range::<&[u8]>((start, Bound::Unbounded))?
+            .take(limit)
+        {
+            let (_, quote_value) = entry?;
+            let quote: QuoteInfo = serde_json::from_str(quote_value.value())?;
+            quotes.push(quote);
+        }
+
+        Ok(quotes)
+    }
+
+    fn list_node_quotes(&self, node_pubkey: PublicKey) -> Result<Vec<QuoteInfo>> {
+        let read_txn = self.db.begin_read()?;
+
+        let index_table = read_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+        let quote_table = read_txn.open_table(QUOTES_TABLE)?;
+
+        let mut quotes = vec![];
+
+        for quote_id in index_table.get(node_pubkey.serialize().as_slice())? {
+            let quote_id = quote_id?;
+
+            if let Some(quote_value) = quote_table.get(quote_id.value())? {
+                let quote: QuoteInfo = serde_json::from_str(quote_value.value())?;
+                quotes.push(quote);
+            }
+        }
+
+        Ok(quotes)
+    }
+
+    fn remove_quotes(&self, quote_ids: &[Uuid]) -> Result<()> {
         let write_txn = self.db.begin_write()?;
 
-        let current_quote;
+        {
+            let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
+            let mut index_table = write_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+            let mut history_table = write_txn.open_table(QUOTE_HISTORY_TABLE)?;
+            let mut payments_table = write_txn.open_table(QUOTE_PAYMENTS_TABLE)?;
+            let mut receipts_table = write_txn.open_table(RECEIPTS_TABLE)?;
+            let mut refunds_table = write_txn.open_table(REFUNDS_TABLE)?;
+            let mut hashes_table = write_txn.open_table(PAYMENT_HASHES_TABLE)?;
+            let mut locked_table = write_txn.open_table(LOCKED_PAYMENTS_TABLE)?;
+            let mut pending_table = write_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+            let mut jobs_table = write_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+
+            for quote_id in quote_ids {
+                let key = quote_id.into_bytes();
+
+                let quote = quote_table
+                    .remove(key.as_slice())?
+                    .map(|value| serde_json::from_str::<QuoteInfo>(value.value()))
+                    .transpose()?;
+
+                if let Some(quote) = quote {
+                    index_table.remove(quote.node_pubkey.serialize().as_slice(), key.as_slice())?;
+                }
+
+                let id = quote_id.as_u128();
+                history_table.retain_in((id, 0)..=(id, u64::MAX), |_, _| false)?;
+
+                payments_table.remove(key.as_slice())?;
+                receipts_table.remove(key.as_slice())?;
+                refunds_table.remove(key.as_slice())?;
+                hashes_table.remove(key.as_slice())?;
+                locked_table.remove(key.as_slice())?;
+                pending_table.remove(key.as_slice())?;
+                jobs_table.remove(key.as_slice())?;
+            }
+
+            let mut audit_table = write_txn.open_table(QUOTE_AUDIT_TABLE)?;
+            audit_table.retain(|_, entry_value| {
+                serde_json::from_str::<QuoteAuditEntry>(entry_value)
+                    .map_or(true, |entry| !quote_ids.contains(&entry.quote_id))
+            })?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn backup(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("{} already exists", path.display());
+        }
+
+        // Writes committed after this are not part of the snapshot
+        let read_txn = self.db.begin_read()?;
+
+        let backup = Database::create(path)?;
+        let write_txn = backup.begin_write()?;
+
+        copy_table(&read_txn, &write_txn, QUOTES_TABLE)?;
+        copy_table(&read_txn, &write_txn, PEER_SLA_TABLE)?;
+        copy_table(&read_txn, &write_txn, PEER_ACCESS_TABLE)?;
+        copy_table(&read_txn, &write_txn, OFFERS_TABLE)?;
+        copy_table(&read_txn, &write_txn, EVENTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, EVENT_CURSORS_TABLE)?;
+        copy_table(&read_txn, &write_txn, PENDING_PAYMENTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, QUOTE_PAYMENTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, LOCKED_PAYMENTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, RECEIPTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, REFUNDS_TABLE)?;
+        copy_table(&read_txn, &write_txn, FEE_LEDGER_TABLE)?;
+        copy_table(&read_txn, &write_txn, PAYMENT_HASHES_TABLE)?;
+        copy_table(&read_txn, &write_txn, CHANNEL_OPEN_JOBS_TABLE)?;
+        copy_table(&read_txn, &write_txn, MINTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, QUOTE_AUDIT_TABLE)?;
+        copy_table(&read_txn, &write_txn, QUOTE_HISTORY_TABLE)?;
+
+        {
+            let index_table = read_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+            let mut backup_index_table = write_txn.open_multimap_table(NODE_QUOTES_TABLE)?;
+
+            for entry in index_table.iter()? {
+                let (node_pubkey, quote_ids) = entry?;
+
+                for quote_id in quote_ids {
+                    backup_index_table.insert(node_pubkey.value(), quote_id?.value())?;
+                }
+            }
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn update_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, None, quote_state, None, actor)
+    }
+
+    fn transition_quote_state(
+        &self,
+        quote_id: Uuid,
+        from: QuoteState,
+        to: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, Some(from), to, None, actor)
+    }
+
+    fn override_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        reason: &str,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, None, quote_state, Some(reason), actor)
+    }
+
+    fn set_payment_error(&self, quote_id: Uuid, payment_error: Option<String>) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
 
         {
-            let mut quote: QuoteInfo;
             let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
-            {
+
+            let mut quote: QuoteInfo = {
                 let quote_value = quote_table
                     .get(quote_id.into_bytes().as_slice())?
                     .ok_or(anyhow!("Unknown quote"))?;
 
-                let quote_value = quote_value.value();
+                serde_json::from_str(quote_value.value())?
+            };
 
-                quote = serde_json::from_str(quote_value)?;
-            }
+            quote.payment_error = payment_error;
 
-            current_quote = quote.clone();
+            quote_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(&quote)?.as_str(),
+            )?;
+        }
 
-            quote.state = quote_state;
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn set_quote_channel(&self, quote_id: Uuid, channel: Option<QuoteChannel>) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
+
+            let mut quote: QuoteInfo = {
+                let quote_value = quote_table
+                    .get(quote_id.into_bytes().as_slice())?
+                    .ok_or(anyhow!("Unknown quote"))?;
+
+                serde_json::from_str(quote_value.value())?
+            };
+
+            quote.channel = channel;
 
             quote_table.insert(
                 quote_id.into_bytes().as_slice(),
@@ -90,6 +801,1007 @@ impl Db {
 
         write_txn.commit()?;
 
-        Ok(current_quote)
+        Ok(())
+    }
+
+    fn set_quote_channel_id(
+        &self,
+        quote_id: Uuid,
+        channel_id: Option<UserChannelId>,
+    ) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut quote_table = write_txn.open_table(QUOTES_TABLE)?;
+
+            let mut quote: QuoteInfo = {
+                let quote_value = quote_table
+                    .get(quote_id.into_bytes().as_slice())?
+                    .ok_or(anyhow!("Unknown quote"))?;
+
+                serde_json::from_str(quote_value.value())?
+            };
+
+            quote.channel_id = channel_id;
+
+            quote_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(&quote)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn add_quote_payment(&self, quote_id: Uuid, payment: &QuotePayment) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut payments_table = write_txn.open_table(QUOTE_PAYMENTS_TABLE)?;
+            payments_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(payment)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_quote_payment(&self, quote_id: Uuid) -> Result<Option<QuotePayment>> {
+        let read_txn = self.db.begin_read()?;
+        let payments_table = read_txn.open_table(QUOTE_PAYMENTS_TABLE)?;
+
+        payments_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| serde_json::from_str(value.value()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn add_receipt(&self, quote_id: Uuid, receipt: &SignedReceipt) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut receipts_table = write_txn.open_table(RECEIPTS_TABLE)?;
+            receipts_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(receipt)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_receipt(&self, quote_id: Uuid) -> Result<Option<SignedReceipt>> {
+        let read_txn = self.db.begin_read()?;
+        let receipts_table = read_txn.open_table(RECEIPTS_TABLE)?;
+
+        receipts_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| serde_json::from_str(value.value()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn add_refund(&self, refund: &QuoteRefund) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut refunds_table = write_txn.open_table(REFUNDS_TABLE)?;
+            let key = refund.quote_id.into_bytes();
+
+            if refunds_table.get(key.as_slice())?.is_some() {
+                return Ok(false);
+            }
+
+            refunds_table.insert(key.as_slice(), serde_json::to_string(refund)?.as_str())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(true)
+    }
+
+    fn update_refund(&self, refund: &QuoteRefund) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut refunds_table = write_txn.open_table(REFUNDS_TABLE)?;
+            refunds_table.insert(
+                refund.quote_id.into_bytes().as_slice(),
+                serde_json::to_string(refund)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn remove_refund(&self, quote_id: Uuid) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut refunds_table = write_txn.open_table(REFUNDS_TABLE)?;
+            refunds_table.remove(quote_id.into_bytes().as_slice())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_refund(&self, quote_id: Uuid) -> Result<Option<QuoteRefund>> {
+        let read_txn = self.db.begin_read()?;
+        let refunds_table = read_txn.open_table(REFUNDS_TABLE)?;
+
+        refunds_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| serde_json::from_str(value.value()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn list_refunds(&self) -> Result<Vec<QuoteRefund>> {
+        let read_txn = self.db.begin_read()?;
+        let refunds_table = read_txn.open_table(REFUNDS_TABLE)?;
+
+        let mut refunds = vec![];
+
+        for entry in refunds_table.iter()? {
+            let (_, refund_value) = entry?;
+            refunds.push(serde_json::from_str(refund_value.value())?);
+        }
+
+        Ok(refunds)
+    }
+
+    fn add_fee_entry(&self, entry: &FeeLedgerEntry) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut ledger_table = write_txn.open_table(FEE_LEDGER_TABLE)?;
+            ledger_table.insert(
+                (entry.recorded_at, entry.quote_id.as_u128()),
+                serde_json::to_string(entry)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn list_fee_entries(&self, since: u64, until: u64) -> Result<Vec<FeeLedgerEntry>> {
+        let read_txn = self.db.begin_read()?;
+        let ledger_table = read_txn.open_table(FEE_LEDGER_TABLE)?;
+
+        let mut entries = vec![];
+
+        for entry in ledger_table.range((since, 0)..(until, 0))? {
+            let (_, entry_value) = entry?;
+            entries.push(serde_json::from_str(entry_value.value())?);
+        }
+
+        Ok(entries)
+    }
+
+    fn add_locked_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut locked_table = write_txn.open_table(LOCKED_PAYMENTS_TABLE)?;
+            locked_table.insert(
+                quote_id.into_bytes().as_slice(),
+                serde_json::to_string(payload)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_locked_payment(&self, quote_id: Uuid) -> Result<Option<PaymentRequestPayload>> {
+        let read_txn = self.db.begin_read()?;
+        let locked_table = read_txn.open_table(LOCKED_PAYMENTS_TABLE)?;
+
+        locked_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| serde_json::from_str(value.value()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn remove_locked_payment(&self, quote_id: Uuid) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut locked_table = write_txn.open_table(LOCKED_PAYMENTS_TABLE)?;
+            locked_table.remove(quote_id.into_bytes().as_slice())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn set_payment_hash(&self, quote_id: Uuid, payload_hash: &str) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut hashes_table = write_txn.open_table(PAYMENT_HASHES_TABLE)?;
+            hashes_table.insert(quote_id.into_bytes().as_slice(), payload_hash)?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_payment_hash(&self, quote_id: Uuid) -> Result<Option<String>> {
+        let read_txn = self.db.begin_read()?;
+        let hashes_table = read_txn.open_table(PAYMENT_HASHES_TABLE)?;
+
+        Ok(hashes_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| value.value().to_string()))
+    }
+
+    fn put_channel_open_job(&self, job: &ChannelOpenJob) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut jobs_table = write_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+            jobs_table.insert(
+                job.quote_id.into_bytes().as_slice(),
+                serde_json::to_string(job)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn get_channel_open_job(&self, quote_id: Uuid) -> Result<Option<ChannelOpenJob>> {
+        let read_txn = self.db.begin_read()?;
+        let jobs_table = read_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+
+        jobs_table
+            .get(quote_id.into_bytes().as_slice())?
+            .map(|value| serde_json::from_str(value.value()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn remove_channel_open_job(&self, quote_id: Uuid) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut jobs_table = write_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+            jobs_table.remove(quote_id.into_bytes().as_slice())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn list_channel_open_jobs(&self) -> Result<Vec<ChannelOpenJob>> {
+        let read_txn = self.db.begin_read()?;
+        let jobs_table = read_txn.open_table(CHANNEL_OPEN_JOBS_TABLE)?;
+
+        let mut jobs = vec![];
+
+        for entry in jobs_table.iter()? {
+            let (_, job_value) = entry?;
+            jobs.push(serde_json::from_str(job_value.value())?);
+        }
+
+        Ok(jobs)
+    }
+
+    fn add_pending_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut pending_table = write_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+            let key = quote_id.into_bytes();
+
+            if pending_table.get(key.as_slice())?.is_some() {
+                return Ok(false);
+            }
+
+            pending_table.insert(key.as_slice(), serde_json::to_string(payload)?.as_str())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(true)
+    }
+
+    fn remove_pending_payment(&self, quote_id: Uuid) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut pending_table = write_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+            pending_table.remove(quote_id.into_bytes().as_slice())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn is_payment_pending(&self, quote_id: Uuid) -> Result<bool> {
+        let read_txn = self.db.begin_read()?;
+        let pending_table = read_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+
+        Ok(pending_table
+            .get(quote_id.into_bytes().as_slice())?
+            .is_some())
+    }
+
+    fn list_pending_payments(&self) -> Result<Vec<(Uuid, PaymentRequestPayload)>> {
+        let read_txn = self.db.begin_read()?;
+
+        let pending_table = read_txn.open_table(PENDING_PAYMENTS_TABLE)?;
+
+        let mut payments = vec![];
+
+        for entry in pending_table.iter()? {
+            let (key, payload_value) = entry?;
+            let quote_id = Uuid::from_slice(key.value())?;
+            let payload: PaymentRequestPayload = serde_json::from_str(payload_value.value())?;
+            payments.push((quote_id, payload));
+        }
+
+        Ok(payments)
+    }
+
+    fn list_quote_history(
+        &self,
+        quote_id: Uuid,
+        since: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<QuoteStateTransition>> {
+        let read_txn = self.db.begin_read()?;
+
+        let history_table = read_txn.open_table(QUOTE_HISTORY_TABLE)?;
+        let id = quote_id.as_u128();
+
+        let mut history = vec![];
+
+        for entry in history_table.range((id, 0)..=(id, u64::MAX))? {
+            if limit.is_some_and(|limit| history.len() >= limit) {
+                break;
+            }
+
+            let (_, transition_value) = entry?;
+            let transition: QuoteStateTransition = serde_json::from_str(transition_value.value())?;
+
+            if transition.timestamp >= since {
+                history.push(transition);
+            }
+        }
+
+        Ok(history)
+    }
+
+    fn list_quote_audit(
+        &self,
+        quote_id: Option<Uuid>,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<Vec<QuoteAuditEntry>> {
+        let read_txn = self.db.begin_read()?;
+
+        let audit_table = read_txn.open_table(QUOTE_AUDIT_TABLE)?;
+
+        let mut entries = vec![];
+
+        for entry in audit_table.range(cursor.saturating_add(1)..)? {
+            if entries.len() >= limit {
+                break;
+            }
+
+            let (_, entry_value) = entry?;
+            let entry: QuoteAuditEntry = serde_json::from_str(entry_value.value())?;
+
+            if quote_id.is_none_or(|id| entry.quote_id == id) {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn add_sla_sample(&self, node_pubkey: PublicKey, connected: bool, usable: bool) -> Result<()> {
+        let now = self.clock.now();
+
+        self.update_peer_sla(node_pubkey, |sla| {
+            sla.samples += 1;
+            if connected {
+                sla.connected_samples += 1;
+            }
+            if usable {
+                sla.usable_samples += 1;
+            }
+            sla.last_sample = now;
+        })
+    }
+
+    fn record_sla_forward(&self, node_pubkey: PublicKey, amount_msat: u64) -> Result<()> {
+        self.update_peer_sla(node_pubkey, |sla| {
+            sla.forwarded_payments += 1;
+            sla.forwarded_msats = sla.forwarded_msats.saturating_add(amount_msat);
+        })
+    }
+
+    fn record_sla_close(&self, node_pubkey: PublicKey) -> Result<()> {
+        self.update_peer_sla(node_pubkey, |sla| sla.closed_channels += 1)
+    }
+
+    fn list_peer_sla(&self) -> Result<Vec<PeerSla>> {
+        let read_txn = self.db.begin_read()?;
+
+        let sla_table = read_txn.open_table(PEER_SLA_TABLE)?;
+
+        let mut peers = vec![];
+
+        for entry in sla_table.iter()? {
+            let (_, sla_value) = entry?;
+            let sla: PeerSla = serde_json::from_str(sla_value.value())?;
+            peers.push(sla);
+        }
+
+        Ok(peers)
+    }
+
+    fn set_peer_access(&self, node_pubkey: PublicKey, access: Option<PeerAccess>) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut access_table = write_txn.open_table(PEER_ACCESS_TABLE)?;
+            let key = node_pubkey.serialize();
+
+            match access {
+                Some(access) => {
+                    access_table
+                        .insert(key.as_slice(), serde_json::to_string(&access)?.as_str())?;
+                }
+                None => {
+                    access_table.remove(key.as_slice())?;
+                }
+            }
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn list_peer_access(&self) -> Result<Vec<(PublicKey, PeerAccess)>> {
+        let read_txn = self.db.begin_read()?;
+
+        let access_table = read_txn.open_table(PEER_ACCESS_TABLE)?;
+
+        let mut peers = vec![];
+
+        for entry in access_table.iter()? {
+            let (key, access_value) = entry?;
+            let node_pubkey = PublicKey::from_slice(key.value())?;
+            let access: PeerAccess = serde_json::from_str(access_value.value())?;
+            peers.push((node_pubkey, access));
+        }
+
+        Ok(peers)
+    }
+
+    fn set_mint_accepted(&self, mint_url: &MintUrl, accepted: bool) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut mints_table = write_txn.open_table(MINTS_TABLE)?;
+            mints_table.insert(mint_url.to_string().as_str(), accepted)?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn list_mint_overrides(&self) -> Result<Vec<(MintUrl, bool)>> {
+        let read_txn = self.db.begin_read()?;
+
+        let mints_table = read_txn.open_table(MINTS_TABLE)?;
+
+        let mut mints = vec![];
+
+        for entry in mints_table.iter()? {
+            let (key, accepted) = entry?;
+            mints.push((MintUrl::from_str(key.value())?, accepted.value()));
+        }
+
+        Ok(mints)
+    }
+
+    fn add_offer(
+        &self,
+        offer_id: [u8; 32],
+        offer: String,
+        description: String,
+        amount_msats: Option<u64>,
+    ) -> Result<OfferRecord> {
+        let write_txn = self.db.begin_write()?;
+
+        let record = OfferRecord {
+            offer_id,
+            offer,
+            description,
+            amount_msats,
+            created_at: self.clock.now(),
+        };
+
+        {
+            let mut offers_table = write_txn.open_table(OFFERS_TABLE)?;
+            offers_table.insert(
+                offer_id.as_slice(),
+                serde_json::to_string(&record)?.as_str(),
+            )?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(record)
+    }
+
+    fn list_offers(&self) -> Result<Vec<OfferRecord>> {
+        let read_txn = self.db.begin_read()?;
+
+        let offers_table = read_txn.open_table(OFFERS_TABLE)?;
+
+        let mut offers = vec![];
+
+        for entry in offers_table.iter()? {
+            let (_, offer_value) = entry?;
+            let offer: OfferRecord = serde_json::from_str(offer_value.value())?;
+            offers.push(offer);
+        }
+
+        Ok(offers)
+    }
+
+    fn append_event(&self, kind: LspEventKind) -> Result<LspEvent> {
+        let write_txn = self.db.begin_write()?;
+
+        let event;
+
+        {
+            let mut events_table = write_txn.open_table(EVENTS_TABLE)?;
+
+            let seq = match events_table.last()? {
+                Some((last_seq, _)) => last_seq.value() + 1,
+                None => 1,
+            };
+
+            event = LspEvent {
+                seq,
+                timestamp: self.clock.now(),
+                kind,
+            };
+
+            events_table.insert(seq, serde_json::to_string(&event)?.as_str())?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(event)
+    }
+
+    fn list_events(&self, cursor: u64, limit: usize) -> Result<Vec<LspEvent>> {
+        let read_txn = self.db.begin_read()?;
+
+        let events_table = read_txn.open_table(EVENTS_TABLE)?;
+
+        let mut events = vec![];
+
+        for entry in events_table.range(cursor.saturating_add(1)..)?.take(limit) {
+            let (_, event_value) = entry?;
+            let event: LspEvent = serde_json::from_str(event_value.value())?;
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+
+    fn remove_events_before(&self, timestamp: u64) -> Result<usize> {
+        let write_txn = self.db.begin_write()?;
+
+        let removed;
+
+        {
+            let mut events_table = write_txn.open_table(EVENTS_TABLE)?;
+
+            let Some(last_seq) = events_table.last()?.map(|(seq, _)| seq.value()) else {
+                return Ok(0);
+            };
+
+            // Events are appended in time order, stop at the first one to keep
+            let mut expired = vec![];
+
+            for entry in events_table.range(..last_seq)? {
+                let (seq, event_value) = entry?;
+                let event: LspEvent = serde_json::from_str(event_value.value())?;
+
+                if event.timestamp >= timestamp {
+                    break;
+                }
+
+                expired.push(seq.value());
+            }
+
+            for seq in expired.iter() {
+                events_table.remove(*seq)?;
+            }
+
+            removed = expired.len();
+        }
+
+        write_txn.commit()?;
+
+        Ok(removed)
+    }
+
+    fn get_event_cursor(&self, consumer: &str) -> Result<u64> {
+        let read_txn = self.db.begin_read()?;
+
+        let cursors_table = read_txn.open_table(EVENT_CURSORS_TABLE)?;
+
+        if let Some(seq) = cursors_table.get(consumer)? {
+            return Ok(seq.value());
+        }
+
+        let events_table = read_txn.open_table(EVENTS_TABLE)?;
+
+        Ok(events_table
+            .last()?
+            .map(|(seq, _)| seq.value())
+            .unwrap_or_default())
+    }
+
+    fn set_event_cursor(&self, consumer: &str, seq: u64) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+
+        {
+            let mut cursors_table = write_txn.open_table(EVENT_CURSORS_TABLE)?;
+            cursors_table.insert(consumer, seq)?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Temporary file removed on drop, with SQLite's WAL and shared memory files
+#[cfg(test)]
+pub(crate) struct TempPath(PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("cashu-lsp-{}-{}", Uuid::new_v4(), name)))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cdk::nuts::CurrencyUnit;
+
+    use super::*;
+    use crate::sqlite_db::SqliteDb;
+    use crate::types::RefundMethod;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Backend {
+        Redb,
+        Sqlite,
+    }
+
+    impl Backend {
+        fn open(self, path: &Path) -> Box<dyn LspStore> {
+            match self {
+                Backend::Redb => Box::new(Db::new(path.to_path_buf()).unwrap()),
+                Backend::Sqlite => Box::new(SqliteDb::new(path).unwrap()),
+            }
+        }
+    }
+
+    // Fields drop in order, so the store is closed before its file is removed
+    struct TestStore {
+        db: Box<dyn LspStore>,
+        backend: Backend,
+        _file: TempPath,
+    }
+
+    /// A fresh store of every backend
+    fn stores() -> Vec<TestStore> {
+        [Backend::Redb, Backend::Sqlite]
+            .into_iter()
+            .map(|backend| {
+                let file = TempPath::new("conformance.db");
+
+                TestStore {
+                    db: backend.open(file.path()),
+                    backend,
+                    _file: file,
+                }
+            })
+            .collect()
+    }
+
+    fn add_quote(db: &dyn LspStore, id: u128) -> Uuid {
+        let id = Uuid::from_u128(id);
+
+        db.add_quote(&QuoteInfo::for_test(id, 0), QuoteActor::Client)
+            .unwrap();
+
+        id
+    }
+
+    fn refund(quote_id: Uuid, amount_sats: u64) -> QuoteRefund {
+        QuoteRefund {
+            quote_id,
+            amount_sats,
+            method: RefundMethod::Ecash,
+            token: None,
+            invoice: None,
+            created_at: 0,
+            claimed_at: None,
+        }
+    }
+
+    fn payload(quote_id: Uuid) -> PaymentRequestPayload {
+        PaymentRequestPayload {
+            id: Some(quote_id.to_string()),
+            memo: None,
+            mint: MintUrl::from_str("https://mint.example.com").unwrap(),
+            unit: CurrencyUnit::Sat,
+            proofs: vec![],
+        }
+    }
+
+    #[test]
+    fn transition_only_applies_from_the_expected_state() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            let id = add_quote(db, 1);
+
+            let before = db
+                .transition_quote_state(id, QuoteState::Unpaid, QuoteState::Paid, QuoteActor::Lsp)
+                .unwrap();
+            assert_eq!(before.state, QuoteState::Unpaid, "{:?}", store.backend);
+
+            // A second caller sees the quote already moved on
+            let before = db
+                .transition_quote_state(id, QuoteState::Unpaid, QuoteState::Paid, QuoteActor::Lsp)
+                .unwrap();
+            assert_eq!(before.state, QuoteState::Paid, "{:?}", store.backend);
+
+            assert_eq!(db.get_quote(id).unwrap().state, QuoteState::Paid);
+            assert_eq!(
+                db.list_quote_history(id, 0, None)
+                    .unwrap()
+                    .iter()
+                    .filter(|t| t.state == QuoteState::Paid)
+                    .count(),
+                1,
+                "{:?}",
+                store.backend
+            );
+        }
+    }
+
+    #[test]
+    fn refund_is_only_added_once() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            let id = add_quote(db, 1);
+
+            assert!(db.add_refund(&refund(id, 1_000)).unwrap());
+            assert!(!db.add_refund(&refund(id, 2_000)).unwrap());
+
+            assert_eq!(
+                db.get_refund(id).unwrap().unwrap().amount_sats,
+                1_000,
+                "{:?}",
+                store.backend
+            );
+        }
+    }
+
+    #[test]
+    fn pending_payment_is_only_added_once() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            let id = add_quote(db, 1);
+
+            assert!(db.add_pending_payment(id, &payload(id)).unwrap());
+            assert!(!db.add_pending_payment(id, &payload(id)).unwrap());
+            assert!(db.is_payment_pending(id).unwrap());
+
+            db.remove_pending_payment(id).unwrap();
+
+            assert!(!db.is_payment_pending(id).unwrap(), "{:?}", store.backend);
+            assert!(db.add_pending_payment(id, &payload(id)).unwrap());
+        }
+    }
+
+    #[test]
+    fn backup_is_a_readable_copy() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            let id = add_quote(db, 1);
+
+            let backup = TempPath::new("conformance-backup.db");
+            db.backup(backup.path()).unwrap();
+
+            // Never overwrites an existing file
+            assert!(db.backup(backup.path()).is_err(), "{:?}", store.backend);
+
+            let restored = store.backend.open(backup.path());
+            assert_eq!(restored.get_quote(id).unwrap().id, id);
+            assert_eq!(
+                restored
+                    .list_node_quotes(QuoteInfo::for_test(id, 0).node_pubkey)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn node_index_follows_quotes() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            let first = add_quote(db, 1);
+            add_quote(db, 2);
+
+            let mut other = QuoteInfo::for_test(Uuid::from_u128(3), 0);
+            other.node_pubkey = PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap();
+            db.add_quote(&other, QuoteActor::Client).unwrap();
+
+            let node_pubkey = db.get_quote(first).unwrap().node_pubkey;
+
+            assert_eq!(db.list_node_quotes(node_pubkey).unwrap().len(), 2);
+            assert_eq!(db.list_node_quotes(other.node_pubkey).unwrap().len(), 1);
+
+            db.remove_quotes(&[first]).unwrap();
+
+            let remaining = db.list_node_quotes(node_pubkey).unwrap();
+            assert_eq!(remaining.len(), 1, "{:?}", store.backend);
+            assert_eq!(remaining[0].id, Uuid::from_u128(2));
+        }
+    }
+
+    #[test]
+    fn quotes_are_paged_by_id() {
+        for store in stores() {
+            let db = store.db.as_ref();
+            for id in [3, 1, 2] {
+                add_quote(db, id);
+            }
+
+            let first = db.list_quotes_after(None, 2).unwrap();
+            let ids: Vec<_> = first.iter().map(|quote| quote.id).collect();
+            assert_eq!(
+                ids,
+                [Uuid::from_u128(1), Uuid::from_u128(2)],
+                "{:?}",
+                store.backend
+            );
+
+            let rest = db.list_quotes_after(Some(ids[1]), 2).unwrap();
+            assert_eq!(rest.len(), 1);
+            assert_eq!(rest[0].id, Uuid::from_u128(3));
+
+            assert!(
+                db.list_quotes_after(Some(rest[0].id), 2)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn events_are_paged_and_cursors_start_at_the_latest() {
+        for store in stores() {
+            let db = store.db.as_ref();
+
+            assert_eq!(db.get_event_cursor("test").unwrap(), 0);
+
+            for _ in 0..3 {
+                db.append_event(LspEventKind::QuoteStateChanged {
+                    quote_id: Uuid::from_u128(1),
+                    state: QuoteState::Paid,
+                })
+                .unwrap();
+            }
+
+            let page = db.list_events(0, 2).unwrap();
+            assert_eq!(page.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2]);
+            assert_eq!(db.list_events(2, 2).unwrap().len(), 1);
+
+            assert_eq!(
+                db.get_event_cursor("test").unwrap(),
+                3,
+                "{:?}",
+                store.backend
+            );
+
+            db.set_event_cursor("test", 1).unwrap();
+            assert_eq!(db.get_event_cursor("test").unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn runtime_peer_access_overrides_the_config() {
+        let configured_peer = QuoteInfo::for_test(Uuid::from_u128(1), 0).node_pubkey;
+        let other_peer = PublicKey::from_str(
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        )
+        .unwrap();
+
+        let configured = HashMap::from([(configured_peer, PeerAccess::Deny)]);
+
+        for store in stores() {
+            let db = store.db.as_ref();
+
+            assert!(!db.is_peer_allowed(configured_peer, &configured).unwrap());
+            assert!(db.is_peer_allowed(other_peer, &configured).unwrap());
+
+            db.set_peer_access(configured_peer, Some(PeerAccess::Allow))
+                .unwrap();
+
+            assert!(db.is_peer_allowed(configured_peer, &configured).unwrap());
+            // Once any peer is allowed only allowed peers are served
+            assert!(!db.is_peer_allowed(other_peer, &configured).unwrap());
+
+            db.set_peer_access(configured_peer, None).unwrap();
+
+            assert!(
+                !db.is_peer_allowed(configured_peer, &configured).unwrap(),
+                "{:?}",
+                store.backend
+            );
+            assert!(db.list_peer_access().unwrap().is_empty());
+        }
     }
 }
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use ldk_node::Event;
+use ldk_node::bitcoin::hex::DisplayHex;
+use tokio::sync::broadcast;
+use tokio_stream::Stream;
+
+use crate::db::LspStore;
+use crate::types::{LspEvent, LspEventKind};
+
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Journal events read at once when replaying
+const EVENT_PAGE_SIZE: usize = 1000;
+
+/// Append-only journal of LSP events
+///
+/// Events are persisted before being broadcast so subscribers can resume
+/// from the last sequence number they saw.
+#[derive(Clone)]
+pub struct EventJournal {
+    db: Arc<dyn LspStore>,
+    sender: broadcast::Sender<LspEvent>,
+    // Keeps broadcast order consistent with sequence numbers
+    publish_lock: Arc<Mutex<()>>,
+}
+
+impl EventJournal {
+    pub fn new(db: Arc<dyn LspStore>) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        Self {
+            db,
+            sender,
+            publish_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Persist and broadcast an event
+    pub fn publish(&self, kind: LspEventKind) -> Result<LspEvent> {
+        let _guard = self
+            .publish_lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Event journal lock poisoned"))?;
+
+        let event = self.db.append_event(kind)?;
+
+        // No subscribers is not an error
+        let _ = self.sender.send(event.clone());
+
+        Ok(event)
+    }
+
+    /// Stream of events after `cursor`, replaying the journal before live events
+    ///
+    /// The stream ends if the subscriber falls behind the live channel,
+    /// the subscriber should then resume from the last sequence number it saw.
+    pub fn subscribe(&self, cursor: u64) -> Result<impl Stream<Item = LspEvent> + Send + 'static> {
+        // Subscribe before reading the backlog so no event is missed in between
+        let receiver = self.sender.subscribe();
+
+        let page = self.db.list_events(cursor, EVENT_PAGE_SIZE)?;
+
+        let replay = Replay {
+            db: Arc::clone(&self.db),
+            more: page.len() == EVENT_PAGE_SIZE,
+            page: page.into(),
+            cursor,
+            receiver,
+        };
+
+        Ok(futures::stream::unfold(replay, Replay::next))
+    }
+}
+
+/// Subscription state, the journal is read a page at a time
+struct Replay {
+    db: Arc<dyn LspStore>,
+    page: VecDeque<LspEvent>,
+    /// Whether the journal may have events after `page`
+    more: bool,
+    /// Last event returned
+    cursor: u64,
+    receiver: broadcast::Receiver<LspEvent>,
+}
+
+impl Replay {
+    async fn next(mut self) -> Option<(LspEvent, Self)> {
+        loop {
+            if let Some(event) = self.page.pop_front() {
+                self.cursor = event.seq;
+                return Some((event, self));
+            }
+
+            if self.more {
+                let page = match self.db.list_events(self.cursor, EVENT_PAGE_SIZE) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        tracing::error!("Could not read event journal: {}", err);
+                        return None;
+                    }
+                };
+
+                self.more = page.len() == EVENT_PAGE_SIZE;
+                self.page = page.into();
+                continue;
+            }
+
+            // Lagging behind ends the stream, as does the journal being dropped
+            let event = self.receiver.recv().await.ok()?;
+
+            // Already read from the journal
+            if event.seq > self.cursor {
+                self.cursor = event.seq;
+                return Some((event, self));
+            }
+        }
+    }
+}
+
+/// Journal entry for an LDK node event operators care about
+pub fn node_event_kind(event: &Event) -> Option<LspEventKind> {
+    match event {
+        Event::PaymentReceived {
+            payment_hash,
+            amount_msat,
+            ..
+        } => Some(LspEventKind::PaymentReceived {
+            payment_hash: payment_hash.0.to_lower_hex_string(),
+            amount_msat: *amount_msat,
+        }),
+        Event::PaymentFailed {
+            payment_hash,
+            reason,
+            ..
+        } => Some(LspEventKind::PaymentFailed {
+            payment_hash: payment_hash.map(|h| h.0.to_lower_hex_string()),
+            reason: reason.map(|r| format!("{:?}", r)),
+        }),
+        Event::ChannelPending {
+            channel_id,
+            user_channel_id,
+            counterparty_node_id,
+            funding_txo,
+            ..
+        } => Some(LspEventKind::ChannelPending {
+            channel_id: channel_id.to_string(),
+            user_channel_id: user_channel_id.0.to_string(),
+            counterparty_node_id: counterparty_node_id.to_string(),
+            funding_txid: funding_txo.txid.to_string(),
+            funding_output_index: funding_txo.vout,
+        }),
+        Event::ChannelReady {
+            channel_id,
+            user_channel_id,
+            counterparty_node_id,
+        } => Some(LspEventKind::ChannelReady {
+            channel_id: channel_id.to_string(),
+            user_channel_id: user_channel_id.0.to_string(),
+            counterparty_node_id: counterparty_node_id.map(|pk| pk.to_string()),
+        }),
+        Event::ChannelClosed {
+            channel_id,
+            user_channel_id,
+            counterparty_node_id,
+            reason,
+        } => Some(LspEventKind::ChannelClosed {
+            channel_id: channel_id.to_string(),
+            user_channel_id: user_channel_id.0.to_string(),
+            counterparty_node_id: counterparty_node_id.map(|pk| pk.to_string()),
+            reason: reason.as_ref().map(|r| r.to_string()),
+        }),
+        Event::PaymentForwarded {
+            prev_user_channel_id,
+            next_user_channel_id,
+            outbound_amount_forwarded_msat,
+            total_fee_earned_msat,
+            ..
+        } => Some(LspEventKind::PaymentForwarded {
+            prev_user_channel_id: prev_user_channel_id.map(|id| id.0.to_string()),
+            next_user_channel_id: next_user_channel_id.map(|id| id.0.to_string()),
+            outbound_amount_forwarded_msat: *outbound_amount_forwarded_msat,
+            total_fee_earned_msat: *total_fee_earned_msat,
+        }),
+        _ => None,
+    }
+}
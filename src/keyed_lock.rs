@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Serializes work per key while letting different keys proceed concurrently
+#[derive(Clone)]
+pub struct KeyedLock<K> {
+    locks: Arc<Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl<K> Default for KeyedLock<K> {
+    fn default() -> Self {
+        Self {
+            locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<K> KeyedLock<K>
+where
+    K: Eq + Hash,
+{
+    /// Wait for exclusive access to `key`, held until the guard is dropped
+    pub async fn lock(&self, key: K) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().expect("Keyed lock poisoned");
+
+            // Drop locks nobody is holding or waiting on
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+            locks.entry(key).or_default().clone()
+        };
+
+        lock.lock_owned().await
+    }
+}
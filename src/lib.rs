@@ -1,24 +1,60 @@
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use cdk::wallet::MultiMintWallet;
+use cdk::Amount;
+use cdk::mint_url::MintUrl;
+use cdk::nuts::CurrencyUnit;
+use cdk::wallet::{MultiMintWallet, Wallet};
+use db::LspStore;
+use events::{EventJournal, node_event_kind};
 use ldk_node::bitcoin::Network;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::config::{AnchorChannelsConfig, Config};
 use ldk_node::lightning::ln::msgs::SocketAddress;
-use ldk_node::{Builder, Node};
+use ldk_node::lightning_invoice::Bolt11Invoice;
+use ldk_node::{Builder, LogLevel, Node};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
 use tokio::runtime::Runtime;
 use tokio_util::sync::CancellationToken;
 
+/// Alias announced when none is configured
+pub const DEFAULT_NODE_ALIAS: &str = "Cdk-mint-node";
+
+const NODE_LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const EVENT_JOURNAL_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+pub mod anti_spam;
+pub mod clock;
 pub mod config;
 pub mod db;
+pub mod events;
+pub mod keyed_lock;
+pub mod locks;
 pub mod lsp_server;
+pub mod melt_policy;
+pub mod metrics;
+pub mod mint_health;
+pub mod nostr;
 pub mod proto;
+pub mod rates;
+pub mod retention;
+pub mod sandbox;
+pub mod seed;
+pub mod sqlite_db;
 pub mod types;
 
-pub use lsp_server::create_cashu_lsp_router;
+pub use lsp_server::{create_cashu_lsp_router, create_metrics_router};
 
 pub struct CashuLspNode {
     pub inner: Arc<Node>,
     events_cancel_token: CancellationToken,
     wallet: MultiMintWallet,
+    /// Whether the node lifecycle is managed by us or by the embedding application
+    owns_node: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +65,14 @@ pub struct BitcoinRpcConfig {
     pub password: String,
 }
 
+/// Versioned Storage Service the node persists its state to
+#[derive(Debug, Clone)]
+pub struct VssConfig {
+    pub url: String,
+    pub store_id: String,
+    pub headers: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone)]
 pub enum ChainSource {
     Esplora(String),
@@ -41,17 +85,49 @@ pub enum GossipSource {
     RapidGossipSync(String),
 }
 
+/// Creates the wallet for a mint and unit, used to accept mints at runtime
+pub type WalletFactory =
+    Arc<dyn Fn(&MintUrl, &CurrencyUnit) -> anyhow::Result<Wallet> + Send + Sync>;
+
+/// Network a mint issues invoices for, found by requesting a mint quote
+pub async fn mint_network(wallet: &Wallet) -> anyhow::Result<Network> {
+    let quote = wallet.mint_quote(Amount::from(1), None).await?;
+
+    let invoice = Bolt11Invoice::from_str(&quote.request).map_err(|err| {
+        anyhow::anyhow!("Mint {} returned invalid invoice: {}", wallet.mint_url, err)
+    })?;
+
+    Ok(invoice.network())
+}
+
+/// Settings for the ldk-node built by [`CashuLspNode::new`]
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub chain_source: ChainSource,
+    pub gossip_source: GossipSource,
+    pub listening_addresses: Vec<SocketAddress>,
+    pub alias: String,
+    pub network: Network,
+    pub storage_dir: PathBuf,
+    pub vss: Option<VssConfig>,
+    pub anchor_channels: Option<AnchorChannelsConfig>,
+    pub trusted_peers_0conf: Vec<PublicKey>,
+    /// Most verbose level written to the node's log file
+    pub log_level: LogLevel,
+}
+
 impl CashuLspNode {
-    pub fn new(
-        chain_source: ChainSource,
-        gossip_source: GossipSource,
-        listening_address: Vec<SocketAddress>,
-        wallet: MultiMintWallet,
-    ) -> anyhow::Result<Self> {
-        let builder = Builder::new();
-        builder.set_network(Network::Regtest);
-
-        match chain_source {
+    pub fn new(config: NodeConfig, wallet: MultiMintWallet) -> anyhow::Result<Self> {
+        let builder = Builder::from_config(Config {
+            anchor_channels_config: config.anchor_channels,
+            trusted_peers_0conf: config.trusted_peers_0conf,
+            ..Default::default()
+        });
+        builder.set_network(config.network);
+        builder.set_storage_dir_path(config.storage_dir.to_string_lossy().into_owned());
+        builder.set_log_level(config.log_level);
+
+        match config.chain_source {
             ChainSource::Esplora(esplora_url) => {
                 builder.set_chain_source_esplora(esplora_url, None);
             }
@@ -65,7 +141,7 @@ impl CashuLspNode {
             }
         }
 
-        match gossip_source {
+        match config.gossip_source {
             GossipSource::P2P => {
                 builder.set_gossip_source_p2p();
             }
@@ -74,20 +150,77 @@ impl CashuLspNode {
             }
         }
 
-        builder.set_listening_addresses(listening_address)?;
+        builder.set_listening_addresses(config.listening_addresses)?;
 
-        builder.set_node_alias("Cdk-mint-node".to_string())?;
+        builder.set_node_alias(config.alias)?;
 
-        let node = builder.build()?;
+        let node = match config.vss {
+            Some(vss) => builder.build_with_vss_store_and_fixed_headers(
+                vss.url,
+                vss.store_id,
+                vss.headers,
+            )?,
+            None => builder.build()?,
+        };
 
         Ok(Self {
             inner: node,
             events_cancel_token: CancellationToken::new(),
             wallet,
+            owns_node: true,
         })
     }
 
+    /// Wrap an already built, and possibly running, ldk-node
+    ///
+    /// The node lifecycle stays with the caller, [`CashuLspNode::stop`] only
+    /// stops the LSP tasks and leaves the node running.
+    pub fn from_node(node: Arc<Node>, wallet: MultiMintWallet) -> Self {
+        Self {
+            inner: node,
+            events_cancel_token: CancellationToken::new(),
+            wallet,
+            owns_node: false,
+        }
+    }
+
+    /// Check every accepted mint issues invoices for `network`
+    ///
+    /// A mint on another network could never pay or be paid by this node, so
+    /// a mismatch is an error. Mints that cannot be reached are only logged.
+    pub async fn check_mint_networks(&self, network: Network) -> anyhow::Result<()> {
+        for wallet in self.wallet.get_wallets().await {
+            let mint_network = match mint_network(&wallet).await {
+                Ok(mint_network) => mint_network,
+                Err(err) => {
+                    tracing::warn!(
+                        "Could not check network of mint {}: {}",
+                        wallet.mint_url,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            if mint_network != network {
+                anyhow::bail!(
+                    "Mint {} is on {} but node is configured for {}",
+                    wallet.mint_url,
+                    mint_network,
+                    network
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn start(&self, runtime: Option<Arc<Runtime>>) -> anyhow::Result<()> {
+        if self.inner.status().is_running {
+            tracing::info!("Ldk node already running");
+            return Ok(());
+        }
+
         match runtime {
             Some(runtime) => self.inner.start_with_runtime(runtime)?,
             None => self.inner.start()?,
@@ -97,9 +230,163 @@ impl CashuLspNode {
         Ok(())
     }
 
+    /// Periodically sample the availability of channels sold to clients
+    ///
+    /// Forwards and closes are recorded from node events as they happen.
+    /// ldk-node 0.4 does not surface forwarding failures, so availability is
+    /// measured by peer connectivity and channel usability instead.
+    pub async fn monitor_channel_sla(&self, db: Arc<dyn LspStore>, interval: Duration) {
+        loop {
+            tokio::select! {
+                _ = self.events_cancel_token.cancelled() => break,
+                _ = tokio::time::sleep(interval) => {
+                    if let Err(err) = self.sample_channel_sla(&db) {
+                        tracing::error!("Could not sample channel SLA: {}", err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Journal node events until stopped
+    ///
+    /// Consumes the node's event queue, so must not be used when the embedding
+    /// application handles node events itself. An event is only marked handled
+    /// once it is in the journal, the LSP acts on it from there.
+    pub async fn journal_node_events(&self, events: EventJournal) {
+        loop {
+            let event = tokio::select! {
+                _ = self.events_cancel_token.cancelled() => break,
+                event = self.inner.next_event_async() => event,
+            };
+
+            tracing::debug!("Node event: {:?}", event);
+
+            if let Some(kind) = node_event_kind(&event) {
+                if let Err(err) = events.publish(kind) {
+                    tracing::error!("Failed to journal node event: {}", err);
+
+                    // Not marked handled, so the node returns the same event again
+                    tokio::select! {
+                        _ = self.events_cancel_token.cancelled() => break,
+                        _ = tokio::time::sleep(EVENT_JOURNAL_RETRY_INTERVAL) => continue,
+                    }
+                }
+            }
+
+            self.inner.event_handled();
+        }
+    }
+
+    /// Re-emit the node's log file through `tracing`
+    ///
+    /// ldk-node 0.4 only logs to a file. Following it from the end puts node
+    /// logs in the same stream as the LSP's, under the `ldk_node` target.
+    pub async fn forward_node_logs(&self, log_file: PathBuf) {
+        let mut file = match tokio::fs::File::open(&log_file).await {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::warn!("Could not open node log {}: {}", log_file.display(), err);
+                return;
+            }
+        };
+
+        if let Err(err) = file.seek(SeekFrom::End(0)).await {
+            tracing::warn!("Could not seek node log {}: {}", log_file.display(), err);
+            return;
+        }
+
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+
+        loop {
+            let read = tokio::select! {
+                _ = self.events_cancel_token.cancelled() => break,
+                read = reader.read_line(&mut line) => read,
+            };
+
+            match read {
+                // A partial line is kept until the rest is written
+                Ok(_) if line.ends_with('\n') => {
+                    log_node_line(line.trim_end());
+                    line.clear();
+                }
+                Ok(_) => {
+                    tokio::select! {
+                        _ = self.events_cancel_token.cancelled() => break,
+                        _ = tokio::time::sleep(NODE_LOG_POLL_INTERVAL) => {}
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!("Could not read node log: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn sample_channel_sla(&self, db: &dyn LspStore) -> anyhow::Result<()> {
+        let sold_channels: HashSet<u128> = db
+            .list_quotes()?
+            .into_iter()
+            .filter_map(|q| q.channel_id.map(|id| id.0))
+            .collect();
+
+        let connected_peers: HashSet<PublicKey> = self
+            .inner
+            .list_peers()
+            .into_iter()
+            .filter(|p| p.is_connected)
+            .map(|p| p.node_id)
+            .collect();
+
+        // A peer is only usable if every channel we sold them is usable
+        let mut peers: HashMap<PublicKey, bool> = HashMap::new();
+
+        for channel in self
+            .inner
+            .list_channels()
+            .iter()
+            .filter(|c| sold_channels.contains(&c.user_channel_id.0))
+        {
+            let usable = peers.entry(channel.counterparty_node_id).or_insert(true);
+            *usable &= channel.is_usable;
+        }
+
+        for (node_pubkey, usable) in peers {
+            db.add_sla_sample(node_pubkey, connected_peers.contains(&node_pubkey), usable)?;
+        }
+
+        Ok(())
+    }
+
     pub fn stop(&self) -> anyhow::Result<()> {
         self.events_cancel_token.cancel();
-        self.inner.stop()?;
+        if self.owns_node {
+            self.inner.stop()?;
+        }
         Ok(())
     }
 }
+
+/// Log a line of the form `<date> <time> <LEVEL> [<module>:<line>] <message>`
+fn log_node_line(line: &str) {
+    let Some((level, message)) = line
+        .splitn(3, ' ')
+        .nth(2)
+        .and_then(|rest| rest.trim_start().split_once(' '))
+    else {
+        tracing::debug!(target: "ldk_node", "{}", line);
+        return;
+    };
+
+    let message = message.trim_start();
+
+    match level {
+        "ERROR" => tracing::error!(target: "ldk_node", "{}", message),
+        "WARN" => tracing::warn!(target: "ldk_node", "{}", message),
+        "INFO" => tracing::info!(target: "ldk_node", "{}", message),
+        "DEBUG" => tracing::debug!(target: "ldk_node", "{}", message),
+        _ => tracing::trace!(target: "ldk_node", "{}", message),
+    }
+}
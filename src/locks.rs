@@ -0,0 +1,399 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use cdk::nuts::nut10::{Kind, Secret as Nut10Secret};
+use cdk::nuts::nut11::Conditions;
+use cdk::nuts::{Proof, PublicKey, SecretKey};
+use ldk_node::bitcoin::hashes::{Hash, sha256};
+use ldk_node::bitcoin::hex::FromHex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Domain separating the lock key from other keys derived from the wallet seed
+const LOCK_KEY_TAG: &[u8] = b"cashu-lsp/locks";
+
+/// Key proofs are locked to, derived from the wallet seed so it survives restarts
+pub fn lock_key_from_seed(seed: &[u8]) -> Result<SecretKey> {
+    let digest = sha256::Hash::hash(&[seed, LOCK_KEY_TAG].concat());
+
+    Ok(SecretKey::from_slice(digest.as_byte_array())?)
+}
+
+/// Key and lock duration for quotes paid with NUT-14 HTLC locked proofs
+///
+/// Locked proofs must require a signature from `key` alongside the preimage,
+/// so the payer can't spend them once the preimage is revealed and the LSP
+/// can't spend them before it is.
+#[derive(Debug, Clone)]
+pub struct HtlcLocks {
+    pub key: SecretKey,
+    /// How long after the quote is created the proofs must stay locked
+    pub locktime_secs: u64,
+}
+
+/// HTLC payment details advertised in `/info`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HtlcInfo {
+    /// Pubkey the proofs must be locked to
+    pub pubkey: String,
+    pub locktime_secs: u64,
+}
+
+impl HtlcLocks {
+    pub fn from_seed(seed: &[u8], locktime_secs: u64) -> Result<Self> {
+        Ok(Self {
+            key: lock_key_from_seed(seed)?,
+            locktime_secs,
+        })
+    }
+
+    pub fn pubkey(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    pub fn info(&self) -> HtlcInfo {
+        HtlcInfo {
+            pubkey: self.pubkey().to_hex(),
+            locktime_secs: self.locktime_secs,
+        }
+    }
+}
+
+/// Key and lock duration for payments refundable to the payer
+///
+/// Proofs are locked to `key` with a NUT-11 refund path to the payer's
+/// pubkey, so a payment the LSP never claims returns to the payer once the
+/// locktime passes.
+#[derive(Debug, Clone)]
+pub struct RefundLocks {
+    pub key: SecretKey,
+    /// How long after the quote is created the refund path must stay closed
+    pub locktime_secs: u64,
+}
+
+/// Refundable payment details advertised in `/info`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RefundInfo {
+    /// Pubkey the proofs must be locked to
+    pub pubkey: String,
+    pub locktime_secs: u64,
+}
+
+impl RefundLocks {
+    pub fn from_seed(seed: &[u8], locktime_secs: u64) -> Result<Self> {
+        Ok(Self {
+            key: lock_key_from_seed(seed)?,
+            locktime_secs,
+        })
+    }
+
+    pub fn pubkey(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    pub fn info(&self) -> RefundInfo {
+        RefundInfo {
+            pubkey: self.pubkey().to_hex(),
+            locktime_secs: self.locktime_secs,
+        }
+    }
+}
+
+/// Whether `hash` is a hex encoded sha256 hash
+pub fn is_valid_hash(hash: &str) -> bool {
+    sha256::Hash::from_str(hash).is_ok()
+}
+
+/// Whether the hex encoded `preimage` hashes to `hash`
+pub fn verify_preimage(preimage: &str, hash: &str) -> bool {
+    let (Ok(preimage), Ok(hash)) = (Vec::<u8>::from_hex(preimage), sha256::Hash::from_str(hash))
+    else {
+        return false;
+    };
+
+    sha256::Hash::hash(&preimage) == hash
+}
+
+/// Check `proof` is locked to `hash` and `pubkey` until at least `locktime`
+pub fn check_htlc_proof(
+    proof: &Proof,
+    hash: &str,
+    pubkey: &PublicKey,
+    locktime: u64,
+) -> Result<(), String> {
+    let secret =
+        Nut10Secret::try_from(&proof.secret).map_err(|_| "Proof is not HTLC locked".to_string())?;
+
+    if !matches!(secret.kind(), Kind::HTLC) {
+        return Err("Proof is not HTLC locked".to_string());
+    }
+
+    if !secret.secret_data().data().eq_ignore_ascii_case(hash) {
+        return Err("Proof is locked to a different hash".to_string());
+    }
+
+    let conditions = Conditions::try_from(secret.secret_data().tags().cloned().unwrap_or_default())
+        .map_err(|e| format!("Invalid lock conditions: {}", e))?;
+
+    if conditions.locktime.is_none_or(|l| l < locktime) {
+        return Err(format!("Proof must stay locked until {}", locktime));
+    }
+
+    let signer = conditions
+        .pubkeys
+        .as_ref()
+        .is_some_and(|keys| keys.contains(pubkey));
+
+    if !signer || conditions.num_sigs.unwrap_or(1) != 1 {
+        return Err(format!("Proof must require one signature from {}", pubkey));
+    }
+
+    Ok(())
+}
+
+/// Check `proof` is locked to `pubkey` alone and refundable to `refund_pubkey`
+/// no earlier than `locktime`
+pub fn check_refund_proof(
+    proof: &Proof,
+    pubkey: &PublicKey,
+    refund_pubkey: &PublicKey,
+    locktime: u64,
+) -> Result<(), String> {
+    let secret =
+        Nut10Secret::try_from(&proof.secret).map_err(|_| "Proof is not P2PK locked".to_string())?;
+
+    if !matches!(secret.kind(), Kind::P2PK) {
+        return Err("Proof is not P2PK locked".to_string());
+    }
+
+    if !secret
+        .secret_data()
+        .data()
+        .eq_ignore_ascii_case(&pubkey.to_hex())
+    {
+        return Err(format!("Proof must be locked to {}", pubkey));
+    }
+
+    let conditions = Conditions::try_from(secret.secret_data().tags().cloned().unwrap_or_default())
+        .map_err(|e| format!("Invalid lock conditions: {}", e))?;
+
+    // Any extra key could spend the proofs before the LSP claims them
+    if conditions
+        .pubkeys
+        .as_ref()
+        .is_some_and(|keys| !keys.is_empty())
+        || conditions.num_sigs.unwrap_or(1) != 1
+    {
+        return Err(format!(
+            "Proof must only require a signature from {}",
+            pubkey
+        ));
+    }
+
+    if conditions.locktime.is_none_or(|l| l < locktime) {
+        return Err(format!("Refund path must stay closed until {}", locktime));
+    }
+
+    let refundable = conditions
+        .refund_keys
+        .as_ref()
+        .is_some_and(|keys| keys.contains(refund_pubkey));
+
+    if !refundable {
+        return Err(format!("Proof must be refundable to {}", refund_pubkey));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cdk::Amount;
+    use cdk::nuts::Id;
+
+    use super::*;
+
+    const HASH: &str = "6d7a2f4d4b8a1e4f0c2b9a8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f";
+    const LOCKTIME: u64 = 1_000_000;
+
+    fn locked_proof(kind: Kind, data: &str, tags: Vec<Vec<String>>) -> Proof {
+        let secret = Nut10Secret::new(kind, data, Some(tags));
+
+        Proof::new(
+            Amount::from(1),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            secret.try_into().unwrap(),
+            SecretKey::generate().public_key(),
+        )
+    }
+
+    fn tag(name: &str, values: &[String]) -> Vec<String> {
+        [name.to_string()]
+            .into_iter()
+            .chain(values.iter().cloned())
+            .collect()
+    }
+
+    fn htlc_tags(pubkeys: &[PublicKey], locktime: u64, num_sigs: u64) -> Vec<Vec<String>> {
+        vec![
+            tag("locktime", &[locktime.to_string()]),
+            tag(
+                "pubkeys",
+                &pubkeys.iter().map(|pk| pk.to_hex()).collect::<Vec<_>>(),
+            ),
+            tag("n_sigs", &[num_sigs.to_string()]),
+        ]
+    }
+
+    #[test]
+    fn htlc_proof_is_accepted() {
+        let lsp = SecretKey::generate().public_key();
+        let proof = locked_proof(Kind::HTLC, HASH, htlc_tags(&[lsp], LOCKTIME, 1));
+
+        assert_eq!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME), Ok(()));
+    }
+
+    #[test]
+    fn htlc_proof_of_another_kind_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let proof = locked_proof(Kind::P2PK, &lsp.to_hex(), htlc_tags(&[lsp], LOCKTIME, 1));
+
+        assert!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn htlc_proof_with_another_hash_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let other_hash = "00".repeat(32);
+        let proof = locked_proof(Kind::HTLC, &other_hash, htlc_tags(&[lsp], LOCKTIME, 1));
+
+        assert!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn htlc_proof_unlocking_early_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let proof = locked_proof(Kind::HTLC, HASH, htlc_tags(&[lsp], LOCKTIME - 1, 1));
+
+        assert!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn htlc_proof_without_lsp_key_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let other = SecretKey::generate().public_key();
+        let proof = locked_proof(Kind::HTLC, HASH, htlc_tags(&[other], LOCKTIME, 1));
+
+        assert!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn htlc_proof_requiring_more_signatures_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let other = SecretKey::generate().public_key();
+        let proof = locked_proof(Kind::HTLC, HASH, htlc_tags(&[lsp, other], LOCKTIME, 2));
+
+        assert!(check_htlc_proof(&proof, HASH, &lsp, LOCKTIME).is_err());
+    }
+
+    fn refund_tags(
+        pubkeys: &[PublicKey],
+        refund_keys: &[PublicKey],
+        locktime: u64,
+        num_sigs: u64,
+    ) -> Vec<Vec<String>> {
+        let mut tags = vec![
+            tag("locktime", &[locktime.to_string()]),
+            tag("n_sigs", &[num_sigs.to_string()]),
+        ];
+
+        if !pubkeys.is_empty() {
+            tags.push(tag(
+                "pubkeys",
+                &pubkeys.iter().map(|pk| pk.to_hex()).collect::<Vec<_>>(),
+            ));
+        }
+
+        if !refund_keys.is_empty() {
+            tags.push(tag(
+                "refund",
+                &refund_keys.iter().map(|pk| pk.to_hex()).collect::<Vec<_>>(),
+            ));
+        }
+
+        tags
+    }
+
+    #[test]
+    fn refund_proof_is_accepted() {
+        let lsp = SecretKey::generate().public_key();
+        let payer = SecretKey::generate().public_key();
+        let proof = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[], &[payer], LOCKTIME, 1),
+        );
+
+        assert_eq!(check_refund_proof(&proof, &lsp, &payer, LOCKTIME), Ok(()));
+    }
+
+    #[test]
+    fn refund_proof_with_extra_keys_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let payer = SecretKey::generate().public_key();
+        let proof = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[payer], &[payer], LOCKTIME, 1),
+        );
+
+        assert!(check_refund_proof(&proof, &lsp, &payer, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn refund_proof_requiring_more_signatures_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let payer = SecretKey::generate().public_key();
+        let proof = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[], &[payer], LOCKTIME, 2),
+        );
+
+        assert!(check_refund_proof(&proof, &lsp, &payer, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn refund_proof_refundable_early_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let payer = SecretKey::generate().public_key();
+        let proof = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[], &[payer], LOCKTIME - 1, 1),
+        );
+
+        assert!(check_refund_proof(&proof, &lsp, &payer, LOCKTIME).is_err());
+    }
+
+    #[test]
+    fn refund_proof_without_refund_key_is_rejected() {
+        let lsp = SecretKey::generate().public_key();
+        let payer = SecretKey::generate().public_key();
+        let other = SecretKey::generate().public_key();
+
+        let missing = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[], &[], LOCKTIME, 1),
+        );
+        assert!(check_refund_proof(&missing, &lsp, &payer, LOCKTIME).is_err());
+
+        let wrong = locked_proof(
+            Kind::P2PK,
+            &lsp.to_hex(),
+            refund_tags(&[], &[other], LOCKTIME, 1),
+        );
+        assert!(check_refund_proof(&wrong, &lsp, &payer, LOCKTIME).is_err());
+    }
+}
@@ -1,72 +1,653 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use axum::http::StatusCode;
+use axum::extract::{DefaultBodyLimit, MatchedPath, Query, Request};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{Router, extract::Json, extract::State};
 use cdk::amount::{Amount, SplitTarget};
 use cdk::mint_url::MintUrl;
-use cdk::nuts::CurrencyUnit;
+use cdk::nuts::State as ProofState;
+use cdk::nuts::{CurrencyUnit, Token};
 use cdk::nuts::{PaymentRequest, PaymentRequestPayload, Transport, TransportType};
 use cdk::wallet::types::WalletKey;
+use cdk::wallet::{SendKind, Wallet};
+use ldk_node::bitcoin::hashes::{Hash, sha256};
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::config::ChannelConfig;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning_invoice::Bolt11Invoice;
+use ldk_node::payment::{PaymentDirection, PaymentKind, PaymentStatus};
+use ldk_node::{ChannelDetails, UserChannelId};
 use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+use tokio_stream::StreamExt;
+use tower_http::timeout::TimeoutLayer;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use uuid::Uuid;
 
 use crate::CashuLspNode;
-use crate::db::Db;
-use crate::types::{ChannelQuoteRequest, QuoteInfo, QuoteState};
+use crate::anti_spam::{AntiSpam, PowStamp, SpentStamps, verify_pow};
+use crate::clock::{Clock, IdGenerator, RandomIds, SystemClock};
+use crate::db::LspStore;
+use crate::events::EventJournal;
+use crate::keyed_lock::KeyedLock;
+use crate::locks::{
+    HtlcInfo, HtlcLocks, RefundInfo, RefundLocks, check_htlc_proof, check_refund_proof,
+    is_valid_hash, verify_preimage,
+};
+use crate::metrics::METRICS;
+use crate::mint_health::{MINT_HEALTH_CHECK_INTERVAL, MintHealth, monitor_mint_health};
+use crate::nostr::{self, NostrInfo, NostrTransport};
+use crate::rates::ExchangeRates;
+use crate::retention::{RetentionPolicy, enforce_retention};
+use crate::sandbox::{API_KEY_HEADER, Sandbox};
+use crate::types::{
+    ChannelOpenJob, ChannelQuoteRequest, FeeLedgerEntry, HtlcLock, LspEventKind, PaymentReceipt,
+    PeerAccess, QuoteActor, QuoteChannel, QuoteInfo, QuoteMode, QuotePayment, QuoteRefund,
+    QuoteState, QuoteStateTransition, ReceivedProof, RefundLock, RefundMethod, SignedQuoteTerms,
+    SignedReceipt, msats_to_sats, sats_to_msats,
+};
+
+/// Approximate size of a channel funding transaction with one input and change
+const FUNDING_TX_VBYTES: u64 = 154;
+const BOLT12_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const PEER_RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_PEER_RECONNECT_BACKOFF: Duration = Duration::from_secs(600);
+const MINT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Prefix of the history reason recorded when a quote's channel closes
+const CHANNEL_CLOSED_REASON: &str = "Channel closed";
+const CHANNEL_MISSING_REASON: &str = "not found on node at startup";
+/// Event cursor of the task acting on node events
+const NODE_EVENTS_CURSOR: &str = "node_events";
+const NODE_EVENTS_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const CHANNEL_OPEN_JOB_INTERVAL: Duration = Duration::from_secs(30);
+const CHANNEL_OPEN_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_CHANNEL_OPEN_RETRY_BACKOFF: Duration = Duration::from_secs(3600);
+/// Failed opens after which a paid quote is left for the operator to retry
+const MAX_CHANNEL_OPEN_ATTEMPTS: u32 = 10;
+
+/// Timeouts and body size limits applied to the HTTP API
+#[derive(Debug, Clone)]
+pub struct HttpLimits {
+    pub request_timeout: Duration,
+    /// Payments redeem proofs and open a channel so get longer
+    pub payment_timeout: Duration,
+    pub max_body_bytes: usize,
+    /// The proofs array of a payment is attacker controlled
+    pub max_payment_body_bytes: usize,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            payment_timeout: Duration::from_secs(120),
+            max_body_bytes: 16 * 1024,
+            max_payment_body_bytes: 256 * 1024,
+        }
+    }
+}
 
 /// Cashu Lsp State
 #[derive(Clone)]
 pub struct CashuLspState {
-    node: Arc<CashuLspNode>,
-    cashu_lsp_info: CashuLspInfo,
-    payment_url: String,
-    db: Db,
+    pub(crate) node: Arc<CashuLspNode>,
+    cashu_lsp_info: Arc<RwLock<Arc<CashuLspInfo>>>,
+    pub(crate) payment_url: String,
+    pub(crate) db: Arc<dyn LspStore>,
+    pub(crate) nostr: Option<NostrTransport>,
+    pub(crate) htlc: Option<HtlcLocks>,
+    pub(crate) refunds: Option<RefundLocks>,
+    pub(crate) rates: Option<ExchangeRates>,
+    pub(crate) retention: Option<RetentionPolicy>,
+    pub(crate) events: EventJournal,
+    pub(crate) sandbox: Option<Sandbox>,
+    /// Sandbox API key the current request was made with
+    pub(crate) sandbox_key: Option<String>,
+    pub(crate) peer_locks: KeyedLock<PublicKey>,
+    pub(crate) spent_stamps: SpentStamps,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) ids: Arc<dyn IdGenerator>,
+    pub(crate) limits: HttpLimits,
+    pub(crate) mint_health: MintHealth,
+    /// Balance in sats per mint at which quotes using it are refused
+    pub(crate) exposure_limits: HashMap<MintUrl, u64>,
+    /// Peer access lists from the config, kept apart from the runtime lists
+    pub(crate) peer_access: HashMap<PublicKey, PeerAccess>,
 }
 
-pub async fn create_cashu_lsp_router(
-    node: Arc<CashuLspNode>,
-    lsp_info: CashuLspInfo,
-    payment_url: String,
-    db: Db,
-) -> anyhow::Result<Router> {
-    let state = CashuLspState {
-        node,
-        cashu_lsp_info: lsp_info,
-        payment_url,
-        db,
-    };
+impl CashuLspState {
+    pub fn new(
+        node: Arc<CashuLspNode>,
+        cashu_lsp_info: CashuLspInfo,
+        payment_url: String,
+        db: Arc<dyn LspStore>,
+    ) -> Self {
+        Self {
+            node,
+            cashu_lsp_info: Arc::new(RwLock::new(Arc::new(cashu_lsp_info))),
+            payment_url,
+            events: EventJournal::new(db.clone()),
+            db,
+            nostr: None,
+            htlc: None,
+            refunds: None,
+            rates: None,
+            retention: None,
+            sandbox: None,
+            sandbox_key: None,
+            peer_locks: KeyedLock::default(),
+            spent_stamps: SpentStamps::default(),
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+            limits: HttpLimits::default(),
+            mint_health: MintHealth::default(),
+            exposure_limits: HashMap::new(),
+            peer_access: HashMap::new(),
+        }
+    }
+
+    /// Current LSP parameters, new requests should take one snapshot
+    pub fn info(&self) -> Arc<CashuLspInfo> {
+        // Only whole snapshots are swapped in, so a poisoned lock is still consistent
+        let info = self
+            .cashu_lsp_info
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(&info)
+    }
+
+    /// Change the LSP parameters used for new quotes and payments
+    ///
+    /// Existing quotes keep the terms they were created with.
+    pub fn update_info<F>(&self, update: F) -> anyhow::Result<Arc<CashuLspInfo>>
+    where
+        F: FnOnce(&mut CashuLspInfo) -> anyhow::Result<()>,
+    {
+        let mut current = self
+            .cashu_lsp_info
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut info = current.as_ref().clone();
+        update(&mut info)?;
+
+        *current = Arc::new(info);
+
+        Ok(Arc::clone(&current))
+    }
+
+    /// Also accept payments over nostr
+    pub fn with_nostr(mut self, nostr: NostrTransport) -> Self {
+        self.nostr = Some(nostr);
+        self
+    }
+
+    /// Also accept HTLC locked payments released once the channel is ready
+    pub fn with_htlc(mut self, htlc: HtlcLocks) -> Self {
+        self.htlc = Some(htlc);
+        self
+    }
+
+    /// Also accept payments refundable to the payer once the lock expires
+    pub fn with_refunds(mut self, refunds: RefundLocks) -> Self {
+        self.refunds = Some(refunds);
+        self
+    }
+
+    /// Accept units other than sat and msat at these exchange rates
+    pub fn with_rates(mut self, rates: ExchangeRates) -> Self {
+        self.rates = Some(rates);
+        self
+    }
+
+    /// Prune terminal quotes once they are older than `retention` allows
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Use `events` as the event journal instead of one on the LSP database
+    pub fn with_events(mut self, events: EventJournal) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Override the clock, the database keeps its own clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Override how quote ids are generated
+    pub fn with_id_generator(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: HttpLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Refuse quotes using a mint once the ecash held there reaches its limit
+    pub fn with_exposure_limits(mut self, exposure_limits: HashMap<MintUrl, u64>) -> Self {
+        self.exposure_limits = exposure_limits;
+        self
+    }
+
+    /// Allow or deny peers from the config, peers set at runtime take precedence
+    pub fn with_peer_access(mut self, peer_access: HashMap<PublicKey, PeerAccess>) -> Self {
+        self.peer_access = peer_access;
+        self
+    }
+
+    /// Route requests carrying a sandbox API key to the sandbox
+    fn with_api_key(mut self, headers: &HeaderMap) -> Result<Self, LspError> {
+        let Some(key) = headers.get(API_KEY_HEADER) else {
+            return Ok(self);
+        };
+
+        let key = key.to_str().map_err(|_| LspError::InvalidApiKey)?;
+
+        let sandbox = self
+            .sandbox
+            .as_ref()
+            .filter(|s| s.has_key(key))
+            .ok_or(LspError::InvalidApiKey)?;
+
+        self.db = sandbox.db.clone();
+        self.events = sandbox.events.clone();
+        self.sandbox_key = Some(key.to_string());
+
+        Ok(self)
+    }
+
+    /// Record an event in the journal
+    ///
+    /// The state change it describes is already committed, so failures are logged only.
+    pub(crate) fn publish_event(&self, kind: LspEventKind) {
+        if let Err(err) = self.events.publish(kind) {
+            tracing::error!("Failed to record event in journal: {}", err);
+        }
+    }
+}
+
+pub async fn create_cashu_lsp_router(state: CashuLspState) -> anyhow::Result<Router> {
+    if let Some(nostr) = state.nostr.clone() {
+        let listener_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = nostr::listen_for_payments(nostr, listener_state).await {
+                tracing::error!("Nostr payment listener stopped: {}", err);
+            }
+        });
+    }
+
+    reconcile_quotes(&state).await?;
+
+    resume_pending_payments(&state)?;
+
+    if state.info().bolt12 {
+        let watcher_state = state.clone();
+        tokio::spawn(async move {
+            watch_bolt12_payments(watcher_state, BOLT12_POLL_INTERVAL).await;
+        });
+    }
+
+    let node_events_state = state.clone();
+    tokio::spawn(async move {
+        watch_node_events(node_events_state).await;
+    });
+
+    let peers_state = state.clone();
+    tokio::spawn(async move {
+        keep_pending_peers_connected(peers_state, PEER_RECONNECT_INTERVAL).await;
+    });
+
+    let jobs_state = state.clone();
+    tokio::spawn(async move {
+        run_channel_open_jobs(jobs_state, CHANNEL_OPEN_JOB_INTERVAL).await;
+    });
+
+    if let Some(rates) = state.rates.clone() {
+        tokio::spawn(rates.keep_fresh(state.node.events_cancel_token.clone()));
+    }
+
+    let mint_health_state = state.clone();
+    tokio::spawn(async move {
+        monitor_mint_health(mint_health_state, MINT_HEALTH_CHECK_INTERVAL).await;
+    });
+
+    if let Some(retention) = state.retention.clone() {
+        tokio::spawn(enforce_retention(state.clone(), retention));
+    }
+
+    let limits = state.limits.clone();
+
+    let payment_router = Router::new()
+        .route("/payment", post(post_receive_payment))
+        .layer(TimeoutLayer::new(limits.payment_timeout))
+        .layer(DefaultBodyLimit::max(limits.max_payment_body_bytes));
 
     let router = Router::new()
+        .route("/openapi.json", get(get_openapi))
         .route("/info", get(get_lsp_info))
         .route("/channel-quote", post(post_channel_quote))
-        .route("/payment", post(post_receive_payment))
         .route("/quote/{id}", get(get_quote_state))
+        .route("/quote/{id}/renew", post(post_renew_quote))
+        .route("/quote/{id}/proof", get(get_quote_proof))
+        .route("/quote/{id}/preimage", post(post_htlc_preimage))
+        .route("/quote/{id}/receipt", get(get_quote_receipt))
+        .route("/health", get(get_health))
+        .route("/ready", get(get_ready))
+        .layer(TimeoutLayer::new(limits.request_timeout))
+        .layer(DefaultBodyLimit::max(limits.max_body_bytes))
+        .merge(payment_router)
+        .route_layer(middleware::from_fn(track_http_metrics))
         .with_state(state);
 
+    #[cfg(feature = "swagger-ui")]
+    let router = router.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+            .config(utoipa_swagger_ui::Config::from("/openapi.json")),
+    );
+
     Ok(router)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CashuLspInfo {
     pub min_channel_size_sat: u64,
     pub max_channel_size_sat: u64,
+    #[schema(value_type = Vec<String>)]
     pub accepted_mints: Vec<MintUrl>,
+    #[schema(value_type = Vec<String>)]
+    pub accepted_units: Vec<CurrencyUnit>,
+    pub min_fee: u64,
+    pub fee_ppk: u64,
+    pub mint_fee_policies: Vec<MintFeePolicy>,
+    /// Set when payments are also accepted over nostr
+    pub nostr: Option<NostrInfo>,
+    /// Set when quotes may be paid with HTLC locked proofs
+    pub htlc: Option<HtlcInfo>,
+    /// Set when quotes may be paid with proofs refundable to the payer
+    pub refund: Option<RefundInfo>,
+    /// Set when clients may choose the forwarding fees of their channel
+    pub forwarding_fee_bounds: Option<ForwardingFeeBounds>,
+    /// Fee rate used to estimate the onchain cost shown in quotes
+    pub funding_fee_rate_sat_per_vb: u64,
+    /// Quotes can also be paid with a BOLT12 offer
+    pub bolt12: bool,
+    /// Max channels a single node may buy
+    pub max_channels_per_peer: Option<u32>,
+    /// Max total capacity a single node may buy
+    pub max_capacity_per_peer_sat: Option<u64>,
+    /// Quotes where the client only pays the service fee are offered
+    pub inbound_only: bool,
+    /// Required alongside quote requests when set
+    pub anti_spam: Option<AntiSpam>,
+}
+
+/// Forwarding fees clients may request on the LSP's side of a new channel
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ForwardingFeeBounds {
+    pub min_base_msat: u32,
+    pub max_base_msat: u32,
+    pub min_ppm: u32,
+    pub max_ppm: u32,
+}
+
+impl ForwardingFeeBounds {
+    fn contains(&self, base_msat: Option<u32>, ppm: Option<u32>) -> bool {
+        base_msat.is_none_or(|b| (self.min_base_msat..=self.max_base_msat).contains(&b))
+            && ppm.is_none_or(|p| (self.min_ppm..=self.max_ppm).contains(&p))
+    }
+}
+
+/// Fee policy charged for payments from a specific mint
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MintFeePolicy {
+    #[schema(value_type = String)]
+    pub mint_url: MintUrl,
     pub min_fee: u64,
     pub fee_ppk: u64,
 }
 
+impl CashuLspInfo {
+    /// Fee policy for `mint`, falling back to the default fees
+    pub fn fee_policy(&self, mint: &MintUrl) -> MintFeePolicy {
+        self.mint_fee_policies
+            .iter()
+            .find(|p| &p.mint_url == mint)
+            .cloned()
+            .unwrap_or_else(|| MintFeePolicy {
+                mint_url: mint.clone(),
+                min_fee: self.min_fee,
+                fee_ppk: self.fee_ppk,
+            })
+    }
+
+    /// Fee for a channel of `channel_size_sats` payable from any of `mints`
+    ///
+    /// The client may pay from any of the mints, so charge the highest fee across them
+    pub fn quote_fee(&self, channel_size_sats: u64, mints: &[MintUrl]) -> Result<u64, LspError> {
+        let fee = if mints.is_empty() {
+            calculate_fee(channel_size_sats, self.min_fee, self.fee_ppk)
+        } else {
+            mints.iter().try_fold(0, |highest: u64, m| {
+                let policy = self.fee_policy(m);
+                calculate_fee(channel_size_sats, policy.min_fee, policy.fee_ppk)
+                    .map(|fee| highest.max(fee))
+            })
+        };
+
+        fee.ok_or_else(|| LspError::InternalError("Channel fee overflows".to_string()))
+    }
+}
+
+/// Calculate the fee for a channel of `channel_size_sats`, `None` on overflow
+fn calculate_fee(channel_size_sats: u64, min_fee: u64, fee_ppk: u64) -> Option<u64> {
+    let fee = (channel_size_sats / 1_000).checked_mul(fee_ppk)?;
+
+    Some(fee.max(min_fee))
+}
+
+/// Input fee a mint charging `input_fee_ppk` takes to swap proofs worth `amount`
+///
+/// Assumes the proofs are split into powers of two. The client pays the fee,
+/// so it is part of the amount being split.
+fn estimate_swap_fee(amount: u64, input_fee_ppk: u64) -> u64 {
+    let mut fee = 0;
+
+    loop {
+        let proofs = u64::from(amount.saturating_add(fee).count_ones());
+        let next = proofs.saturating_mul(input_fee_ppk).div_ceil(1_000);
+
+        if next <= fee {
+            return fee;
+        }
+
+        fee = next;
+    }
+}
+
+/// Expected swap fee in sats for a payment of `payment_sats` in `unit`
+///
+/// The client may pay from any of `mints`, so charge the highest fee across them.
+async fn quote_mint_fee(
+    state: &CashuLspState,
+    mints: &[MintUrl],
+    unit: &CurrencyUnit,
+    sats_per_unit: Option<f64>,
+    payment_sats: u64,
+) -> Result<u64, LspError> {
+    if payment_sats == 0 {
+        return Ok(0);
+    }
+
+    let amount: u64 = sats_to_amount(payment_sats, unit, sats_per_unit)?.into();
+
+    let mut mint_fee = 0;
+
+    for mint_url in mints {
+        let input_fee_ppk = mint_input_fee_ppk(state, mint_url, unit).await?;
+        let fee = estimate_swap_fee(amount, input_fee_ppk);
+
+        let fee_sats = match (unit, sats_per_unit) {
+            (CurrencyUnit::Msat, _) => fee.div_ceil(1_000),
+            (CurrencyUnit::Sat, _) | (_, None) => fee,
+            (_, Some(rate)) => (fee as f64 * rate).ceil() as u64,
+        };
+
+        mint_fee = mint_fee.max(fee_sats);
+    }
+
+    Ok(mint_fee)
+}
+
+/// Input fee of the active keyset of `mint_url` for `unit`
+///
+/// Fees are refreshed by the mint health monitor and fetched here when not yet known.
+async fn mint_input_fee_ppk(
+    state: &CashuLspState,
+    mint_url: &MintUrl,
+    unit: &CurrencyUnit,
+) -> Result<u64, LspError> {
+    if let Some(fee) = state.mint_health.input_fee_ppk(mint_url, unit) {
+        return Ok(fee);
+    }
+
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(mint_url.clone(), unit.clone()))
+        .await
+        .ok_or_else(|| LspError::UnsupportedMint(mint_url.clone()))?;
+
+    let keysets = tokio::time::timeout(MINT_CHECK_TIMEOUT, wallet.get_mint_keysets())
+        .await
+        .map_err(|_| LspError::MintUnavailable(format!("{} timed out", mint_url)))?
+        .map_err(|e| LspError::MintUnavailable(e.to_string()))?;
+
+    state.mint_health.record_fees(mint_url, &keysets);
+
+    Ok(state
+        .mint_health
+        .input_fee_ppk(mint_url, unit)
+        .unwrap_or_default())
+}
+
+/// Current sats per minor unit for units other than sat and msat
+fn current_rate(state: &CashuLspState, unit: &CurrencyUnit) -> Result<Option<f64>, LspError> {
+    match unit {
+        CurrencyUnit::Sat | CurrencyUnit::Msat => Ok(None),
+        unit => state
+            .rates
+            .as_ref()
+            .and_then(|rates| rates.sats_per_unit(unit))
+            .map(Some)
+            .ok_or_else(|| LspError::RateUnavailable(unit.clone())),
+    }
+}
+
+/// Convert an amount denominated in `unit` to sats, rounding down
+///
+/// Units other than sat and msat are converted at `sats_per_unit`.
+fn amount_to_sats(
+    amount: Amount,
+    unit: &CurrencyUnit,
+    sats_per_unit: Option<f64>,
+) -> Result<u64, LspError> {
+    match (unit, sats_per_unit) {
+        (CurrencyUnit::Sat, _) => Ok(amount.into()),
+        (CurrencyUnit::Msat, _) => Ok(msats_to_sats(amount.into())),
+        (_, Some(rate)) => Ok((u64::from(amount) as f64 * rate).floor() as u64),
+        (unit, None) => Err(LspError::UnsupportedUnit(unit.clone())),
+    }
+}
+
+/// Convert an amount in sats to `unit`, rounding up
+fn sats_to_amount(
+    sats: u64,
+    unit: &CurrencyUnit,
+    sats_per_unit: Option<f64>,
+) -> Result<Amount, LspError> {
+    match (unit, sats_per_unit) {
+        (CurrencyUnit::Sat, _) => Ok(Amount::from(sats)),
+        (CurrencyUnit::Msat, _) => sats_to_msats(sats)
+            .map(Amount::from)
+            .ok_or_else(|| LspError::InternalError("Amount overflows msats".to_string())),
+        (_, Some(rate)) => Ok(Amount::from((sats as f64 / rate).ceil() as u64)),
+        (unit, None) => Err(LspError::UnsupportedUnit(unit.clone())),
+    }
+}
+
 #[derive(Debug)]
 pub enum LspError {
     InvalidUuid(String),
     QuoteNotFound(Uuid),
-    InvalidChannelSize { size: u64, min: u64, max: u64 },
+    InvalidChannelSize {
+        size: u64,
+        min: u64,
+        max: u64,
+    },
+    InvalidForwardingFee {
+        base_msat: Option<u32>,
+        ppm: Option<u32>,
+    },
     UnsupportedMint(MintUrl),
-    InvalidQuoteState { id: Uuid, state: QuoteState },
-    InsufficientPayment { expected: u64, received: u64 },
+    UnsupportedUnit(CurrencyUnit),
+    UnsupportedQuoteMode(QuoteMode),
+    AntiSpamRejected(String),
+    PeerUnreachable(PublicKey),
+    PeerNotAllowed(PublicKey),
+    PeerLimitExceeded {
+        node_pubkey: PublicKey,
+        channels: u64,
+        capacity_sat: u64,
+    },
+    InvalidApiKey,
+    SandboxBalanceExceeded {
+        balance: u64,
+        required: u64,
+    },
+    InvalidQuoteState {
+        id: Uuid,
+        state: QuoteState,
+    },
+    InsufficientPayment {
+        expected: u64,
+        received: u64,
+    },
+    PaymentInProgress(Uuid),
+    ProofsAlreadySpent,
+    MintUnavailable(String),
+    MintExposureExceeded {
+        mint_url: MintUrl,
+        balance: u64,
+        limit: u64,
+    },
+    InvalidHtlc(String),
+    InvalidRefundLock(String),
+    InvalidRefund(String),
+    AlreadyRefunded(Uuid),
+    InvalidToken(String),
+    RateUnavailable(CurrencyUnit),
+    InsufficientLspLiquidity {
+        available: u64,
+        required: u64,
+    },
     DatabaseError(String),
     ChannelOpenError(String),
     WalletError(String),
@@ -86,7 +667,42 @@ impl fmt::Display for LspError {
                     size, min, max
                 )
             }
+            Self::InvalidForwardingFee { base_msat, ppm } => {
+                write!(
+                    f,
+                    "Forwarding fee not allowed: base {:?} msat, {:?} ppm",
+                    base_msat, ppm
+                )
+            }
             Self::UnsupportedMint(mint) => write!(f, "Unsupported mint: {}", mint),
+            Self::UnsupportedUnit(unit) => write!(f, "Unsupported unit: {}", unit),
+            Self::UnsupportedQuoteMode(mode) => write!(f, "Unsupported quote mode: {:?}", mode),
+            Self::PeerUnreachable(node_pubkey) => {
+                write!(f, "Could not connect to peer: {}", node_pubkey)
+            }
+            Self::PeerLimitExceeded {
+                node_pubkey,
+                channels,
+                capacity_sat,
+            } => {
+                write!(
+                    f,
+                    "Peer limit exceeded for {}: {} channels, {} sats capacity",
+                    node_pubkey, channels, capacity_sat
+                )
+            }
+            Self::PeerNotAllowed(node_pubkey) => {
+                write!(f, "Peer not allowed: {}", node_pubkey)
+            }
+            Self::AntiSpamRejected(msg) => write!(f, "Anti-spam check failed: {}", msg),
+            Self::InvalidApiKey => write!(f, "Invalid API key"),
+            Self::SandboxBalanceExceeded { balance, required } => {
+                write!(
+                    f,
+                    "Sandbox balance exceeded: balance {}, required {}",
+                    balance, required
+                )
+            }
             Self::InvalidQuoteState { id, state } => {
                 write!(f, "Quote {} has invalid state: {:?}", id, state)
             }
@@ -97,6 +713,38 @@ impl fmt::Display for LspError {
                     expected, received
                 )
             }
+            Self::PaymentInProgress(id) => {
+                write!(f, "Payment for quote {} is already being processed", id)
+            }
+            Self::ProofsAlreadySpent => write!(f, "Proofs have already been spent"),
+            Self::MintUnavailable(msg) => write!(f, "Mint unavailable: {}", msg),
+            Self::InvalidHtlc(msg) => write!(f, "Invalid HTLC: {}", msg),
+            Self::InvalidRefundLock(msg) => write!(f, "Invalid refund lock: {}", msg),
+            Self::InvalidRefund(msg) => write!(f, "Invalid refund: {}", msg),
+            Self::AlreadyRefunded(id) => write!(f, "Quote {} has already been refunded", id),
+            Self::InvalidToken(msg) => write!(f, "Invalid token: {}", msg),
+            Self::RateUnavailable(unit) => write!(f, "No exchange rate for {}", unit),
+            Self::MintExposureExceeded {
+                mint_url,
+                balance,
+                limit,
+            } => {
+                write!(
+                    f,
+                    "Mint {} is at its exposure limit: holding {} of {} sats",
+                    mint_url, balance, limit
+                )
+            }
+            Self::InsufficientLspLiquidity {
+                available,
+                required,
+            } => {
+                write!(
+                    f,
+                    "Insufficient LSP liquidity: available {}, required {}",
+                    available, required
+                )
+            }
             Self::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             Self::ChannelOpenError(msg) => write!(f, "Failed to open channel: {}", msg),
             Self::WalletError(msg) => write!(f, "Wallet error: {}", msg),
@@ -106,17 +754,42 @@ impl fmt::Display for LspError {
     }
 }
 
+impl std::error::Error for LspError {}
+
 impl IntoResponse for LspError {
     fn into_response(self) -> Response {
         let status = match &self {
             Self::InvalidUuid(_)
             | Self::InvalidChannelSize { .. }
+            | Self::InvalidForwardingFee { .. }
             | Self::UnsupportedMint(_)
+            | Self::UnsupportedUnit(_)
+            | Self::UnsupportedQuoteMode(_)
+            | Self::AntiSpamRejected(_)
+            | Self::PeerUnreachable(_)
+            | Self::PeerLimitExceeded { .. }
+            | Self::SandboxBalanceExceeded { .. }
             | Self::InvalidQuoteState { .. }
-            | Self::InsufficientPayment { .. } => StatusCode::BAD_REQUEST,
+            | Self::InsufficientPayment { .. }
+            | Self::ProofsAlreadySpent
+            | Self::InvalidHtlc(_)
+            | Self::InvalidRefundLock(_)
+            | Self::InvalidRefund(_)
+            | Self::InvalidToken(_) => StatusCode::BAD_REQUEST,
 
             Self::QuoteNotFound(_) => StatusCode::NOT_FOUND,
 
+            Self::InvalidApiKey => StatusCode::UNAUTHORIZED,
+
+            Self::PeerNotAllowed(_) => StatusCode::FORBIDDEN,
+
+            Self::PaymentInProgress(_) | Self::AlreadyRefunded(_) => StatusCode::CONFLICT,
+
+            Self::InsufficientLspLiquidity { .. }
+            | Self::MintUnavailable(_)
+            | Self::MintExposureExceeded { .. }
+            | Self::RateUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+
             Self::DatabaseError(_)
             | Self::ChannelOpenError(_)
             | Self::WalletError(_)
@@ -129,285 +802,3506 @@ impl IntoResponse for LspError {
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/info",
+    responses((status = 200, description = "LSP terms", body = CashuLspInfo))
+)]
 pub async fn get_lsp_info(
     State(state): State<CashuLspState>,
 ) -> Result<Json<CashuLspInfo>, Response> {
     tracing::debug!("Handling LSP info request");
-    Ok(Json(state.cashu_lsp_info))
+    Ok(Json(state.info().as_ref().clone()))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ChannelQuoteResponse {
+    quote_id: Uuid,
     payment_request: String,
+    channel_size_sats: u64,
+    /// LSP fee for the channel, covers the funding transaction
+    service_fee_sats: u64,
+    /// Estimated cost of the funding transaction, included in the service fee
+    estimated_onchain_fee_sats: u64,
+    /// Expected mint fee for swapping the payment
+    mint_fee_sats: u64,
+    push_amount_sats: u64,
+    total_sats: u64,
+    /// BOLT12 offer the quote can be paid with instead of ecash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bolt12_offer: Option<String>,
+    /// Lock the proofs paying the quote must carry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    htlc: Option<HtlcLock>,
+    /// Lock and refund path the proofs paying the quote must carry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refund_lock: Option<RefundLock>,
+    /// Terms of the quote signed by the LSP node
+    proof: QuoteProof,
+}
+
+impl ChannelQuoteResponse {
+    fn new(state: &CashuLspState, quote: &QuoteInfo, payment_request: &PaymentRequest) -> Self {
+        let push_amount_sats = quote.push_amount_sats.unwrap_or_default();
+
+        Self {
+            quote_id: quote.id,
+            payment_request: payment_request.to_string(),
+            channel_size_sats: quote.channel_size_sats,
+            service_fee_sats: quote.service_fee_sats(),
+            estimated_onchain_fee_sats: quote_onchain_fee_sats(state, quote),
+            mint_fee_sats: quote.mint_fee_sats,
+            push_amount_sats,
+            total_sats: quote.expected_payment_sats,
+            bolt12_offer: quote.bolt12_offer.clone(),
+            htlc: quote.htlc.clone(),
+            refund_lock: quote.refund_lock.clone(),
+            proof: QuoteProof::new(state, quote),
+        }
+    }
+}
+
+/// Quote terms signed with the LSP node key
+///
+/// `terms` is the exact signed message, a JSON encoded [`SignedQuoteTerms`].
+/// The signature can be checked against `lsp_pubkey` with any lightning
+/// `verifymessage` implementation.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteProof {
+    pub terms: String,
+    pub signature: String,
+    pub lsp_pubkey: String,
+}
+
+impl QuoteProof {
+    fn new(state: &CashuLspState, quote: &QuoteInfo) -> Self {
+        let terms =
+            serde_json::to_string(&SignedQuoteTerms::from(quote)).expect("quote terms serialize");
+
+        Self {
+            signature: state.node.inner.sign_message(terms.as_bytes()),
+            lsp_pubkey: state.node.inner.node_id().to_string(),
+            terms,
+        }
+    }
+}
+
+/// Sign a receipt with the LSP node key
+fn sign_receipt(state: &CashuLspState, receipt: &PaymentReceipt) -> SignedReceipt {
+    let receipt = serde_json::to_string(receipt).expect("receipt serializes");
+
+    SignedReceipt {
+        signature: state.node.inner.sign_message(receipt.as_bytes()),
+        lsp_pubkey: state.node.inner.node_id().to_string(),
+        receipt,
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/channel-quote",
+    request_body = ChannelQuoteRequest,
+    params(("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")),
+    responses(
+        (status = 200, description = "Quote created", body = ChannelQuoteResponse),
+        (status = 400, description = "Invalid quote request", body = String),
+        (status = 503, description = "Not enough onchain funds for the channel or no mint available", body = String)
+    )
+)]
 pub async fn post_channel_quote(
     State(state): State<CashuLspState>,
+    headers: HeaderMap,
     Json(payload): Json<ChannelQuoteRequest>,
 ) -> Result<Json<ChannelQuoteResponse>, LspError> {
     tracing::debug!("Received channel quote request: {:?}", payload);
 
+    let state = state.with_api_key(&headers)?;
+
+    let info = state.info();
+
     // Validate channel size
-    if payload.channel_size_sats > state.cashu_lsp_info.max_channel_size_sat {
+    if payload.channel_size_sats > info.max_channel_size_sat {
         return Err(LspError::InvalidChannelSize {
             size: payload.channel_size_sats,
-            min: state.cashu_lsp_info.min_channel_size_sat,
-            max: state.cashu_lsp_info.max_channel_size_sat,
+            min: info.min_channel_size_sat,
+            max: info.max_channel_size_sat,
         });
     }
 
-    if payload.channel_size_sats < state.cashu_lsp_info.min_channel_size_sat {
+    if payload.channel_size_sats < info.min_channel_size_sat {
         return Err(LspError::InvalidChannelSize {
             size: payload.channel_size_sats,
-            min: state.cashu_lsp_info.min_channel_size_sat,
-            max: state.cashu_lsp_info.max_channel_size_sat,
+            min: info.min_channel_size_sat,
+            max: info.max_channel_size_sat,
         });
     }
 
-    let fee = payload
-        .channel_size_sats
-        .checked_div(1_000)
-        .expect("Amount overflow")
-        .checked_mul(state.cashu_lsp_info.fee_ppk)
-        .expect("Amount overflow");
+    let mints = match payload.mints {
+        Some(mints) if !mints.is_empty() => {
+            if let Some(mint) = mints.iter().find(|m| !info.accepted_mints.contains(m)) {
+                return Err(LspError::UnsupportedMint(mint.clone()));
+            }
 
-    let fee = if fee < state.cashu_lsp_info.min_fee {
-        state.cashu_lsp_info.min_fee
-    } else {
-        fee
+            for mint in mints.iter() {
+                check_mint_exposure(&state, mint).await?;
+            }
+
+            mints
+        }
+        _ => {
+            let mut mints = vec![];
+
+            for mint in info.accepted_mints.iter() {
+                match check_mint_exposure(&state, mint).await {
+                    Ok(()) => mints.push(mint.clone()),
+                    Err(err) => tracing::debug!("Leaving {} out of quote: {}", mint, err),
+                }
+            }
+
+            mints
+        }
     };
 
-    let payment_id = Uuid::new_v4();
+    if mints.is_empty() {
+        return Err(LspError::MintUnavailable(
+            "No accepted mint is available".to_string(),
+        ));
+    }
 
-    let transport = Transport::builder()
-        .transport_type(TransportType::HttpPost)
-        .target(state.payment_url)
-        .build()
-        .map_err(|e| {
-            tracing::error!("Failed to build transport: {}", e);
-            LspError::InternalError(format!("Failed to build transport: {}", e))
-        })?;
+    let fee = info.quote_fee(payload.channel_size_sats, &mints)?;
 
-    let payment_required = payload
-        .channel_size_sats
-        .checked_add(fee)
-        .expect("amount overflow")
-        .checked_add(payload.push_amount.unwrap_or_default())
-        .expect("amount overflow");
-
-    let payment_request = PaymentRequest::builder()
-        .payment_id(payment_id)
-        .amount(payment_required)
-        .unit(CurrencyUnit::Sat)
-        .single_use(true)
-        .mints(state.cashu_lsp_info.accepted_mints)
-        .add_transport(transport)
-        .build();
+    let unit = payload.unit.unwrap_or(CurrencyUnit::Sat);
+
+    if !info.accepted_units.contains(&unit) {
+        return Err(LspError::UnsupportedUnit(unit));
+    }
+
+    if payload.forwarding_fee_base_msat.is_some() || payload.forwarding_fee_ppm.is_some() {
+        let in_bounds = info.forwarding_fee_bounds.as_ref().is_some_and(|b| {
+            b.contains(payload.forwarding_fee_base_msat, payload.forwarding_fee_ppm)
+        });
+
+        if !in_bounds {
+            return Err(LspError::InvalidForwardingFee {
+                base_msat: payload.forwarding_fee_base_msat,
+                ppm: payload.forwarding_fee_ppm,
+            });
+        }
+    }
+
+    // Before anything reaches out to the peer or the mint
+    let anti_spam = check_anti_spam(&state, &payload)?;
+
+    check_peer_access(&state, payload.node_pubkey)?;
+
+    check_peer_limits(&state, payload.node_pubkey, payload.channel_size_sats)?;
+
+    check_liquidity(&state, payload.channel_size_sats)?;
+
+    check_peer_reachable(&state, payload.node_pubkey, &payload.addr)?;
+
+    let htlc = match payload.htlc_hash.as_deref() {
+        Some(hash) => Some(new_htlc_lock(&state, hash)?),
+        None => None,
+    };
+
+    let refund_lock = match payload.refund_pubkey.as_deref() {
+        // HTLC locks carry their own refund path
+        Some(_) if htlc.is_some() => {
+            return Err(LspError::InvalidRefundLock(
+                "A quote can't be both HTLC locked and refundable".to_string(),
+            ));
+        }
+        Some(refund_pubkey) => Some(new_refund_lock(&state, refund_pubkey)?),
+        None => None,
+    };
+
+    let payment_id = state.ids.new_id();
+
+    let mode = payload.mode.unwrap_or_default();
+
+    if mode == QuoteMode::InboundOnly && !info.inbound_only {
+        return Err(LspError::UnsupportedQuoteMode(mode));
+    }
+
+    let payment_required = mode.payment_required(
+        payload.channel_size_sats,
+        fee,
+        payload.push_amount.unwrap_or_default(),
+    );
+
+    let sats_per_unit = current_rate(&state, &unit)?;
+
+    let mint_fee = quote_mint_fee(&state, &mints, &unit, sats_per_unit, payment_required).await?;
 
-    let quote = QuoteInfo {
+    let mut quote = QuoteInfo {
         id: payment_id,
         channel_size_sats: payload.channel_size_sats,
         push_amount_sats: payload.push_amount,
-        expected_payment_sats: payment_required,
+        expected_payment_sats: payment_required.saturating_add(mint_fee),
         node_pubkey: payload.node_pubkey,
         addr: payload.addr,
         state: QuoteState::Unpaid,
         channel_id: None,
+        mints,
+        unit,
+        created_at: state.clock.now(),
+        payment_error: None,
+        forwarding_fee_base_msat: payload.forwarding_fee_base_msat,
+        forwarding_fee_ppm: payload.forwarding_fee_ppm,
+        bolt12_offer: None,
+        bolt12_offer_id: None,
+        mode,
+        mint_fee_sats: mint_fee,
+        htlc,
+        refund_lock,
+        sats_per_unit,
+        channel: None,
+        onchain_fee_sats: Some(FUNDING_TX_VBYTES.saturating_mul(info.funding_fee_rate_sat_per_vb)),
     };
 
-    state.db.add_quote(&quote).map_err(|e| {
-        tracing::error!("Failed to add quote to database: {}", e);
-        LspError::DatabaseError(e.to_string())
-    })?;
+    create_bolt12_offer(&state, &mut quote)?;
 
-    tracing::info!("Created new channel quote: {}", payment_id);
+    let payment_request = build_payment_request(&state, &quote)?;
 
-    Ok(Json(ChannelQuoteResponse {
-        payment_request: payment_request.to_string(),
-    }))
-}
+    // Last, so a quote that can't be created doesn't cost the client its bond or stamp
+    match anti_spam {
+        Some(AntiSpamProof::Stamp(stamp)) => {
+            if !state.spent_stamps.spend(stamp, state.clock.now()) {
+                return Err(LspError::AntiSpamRejected(
+                    "Proof of work stamp already used".to_string(),
+                ));
+            }
+        }
+        Some(AntiSpamProof::Bond(bond)) => redeem_bond(&state, quote.id, bond).await?,
+        None => {}
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QuoteStateResponse {
-    pub id: Uuid,
-    pub state: QuoteState,
-    pub channel_id: Option<String>,
-}
+    state
+        .db
+        .add_quote(&quote, QuoteActor::Client)
+        .map_err(|e| {
+            tracing::error!("Failed to add quote to database: {}", e);
+            LspError::DatabaseError(e.to_string())
+        })?;
 
-pub async fn get_quote_state(
-    State(state): State<CashuLspState>,
-    axum::extract::Path(id): axum::extract::Path<String>,
-) -> Result<Json<QuoteStateResponse>, LspError> {
-    tracing::debug!("Received quote state request for ID: {}", id);
+    state.publish_event(LspEventKind::QuoteCreated {
+        quote_id: payment_id,
+    });
 
-    let id = Uuid::from_str(&id).map_err(|e| {
-        tracing::warn!("Invalid UUID format: {} - {}", id, e);
-        LspError::InvalidUuid(id.clone())
-    })?;
+    METRICS.quotes_created.inc();
 
-    let quote = state.db.get_quote(id).map_err(|e| {
-        tracing::warn!("Quote not found: {} - {}", id, e);
-        LspError::QuoteNotFound(id)
-    })?;
+    tracing::info!("Created new channel quote: {}", payment_id);
 
-    let mut channel_id = None;
+    Ok(Json(ChannelQuoteResponse::new(
+        &state,
+        &quote,
+        &payment_request,
+    )))
+}
 
-    if let Some(user_channel_id) = quote.channel_id {
-        let all_channel = state.node.inner.list_channels();
+/// Refuse new quotes using `mint_url` while its balance is at the exposure limit
+///
+/// Melt policies bring the balance back down, after which the mint is offered again.
+async fn check_mint_exposure(state: &CashuLspState, mint_url: &MintUrl) -> Result<(), LspError> {
+    let Some(limit) = state.exposure_limits.get(mint_url).copied() else {
+        return Ok(());
+    };
 
-        let channel: Vec<&ldk_node::ChannelDetails> = all_channel
-            .iter()
-            .filter(|c| c.user_channel_id == user_channel_id)
-            .collect();
+    let balance = mint_balance_sats(state, mint_url).await?;
 
-        if let Some(channel_info) = channel.get(0) {
-            channel_id = Some(channel_info.channel_id.to_string());
-        } else {
-            tracing::info!("Unkown channel for Channel user id: {}", user_channel_id.0);
+    if balance >= limit {
+        return Err(LspError::MintExposureExceeded {
+            mint_url: mint_url.clone(),
+            balance,
+            limit,
+        });
+    }
+
+    Ok(())
+}
+
+/// Ecash held at `mint_url` across all units, in sats
+async fn mint_balance_sats(state: &CashuLspState, mint_url: &MintUrl) -> Result<u64, LspError> {
+    let mut balance: u64 = 0;
+
+    for wallet in state
+        .node
+        .wallet
+        .get_wallets()
+        .await
+        .iter()
+        .filter(|w| &w.mint_url == mint_url)
+    {
+        let amount = wallet
+            .total_balance()
+            .await
+            .map_err(|e| LspError::WalletError(e.to_string()))?;
+
+        let sats_per_unit = current_rate(state, &wallet.unit)?;
+
+        balance = balance.saturating_add(amount_to_sats(amount, &wallet.unit, sats_per_unit)?);
+    }
+
+    Ok(balance)
+}
+
+fn htlc_locks(state: &CashuLspState) -> Result<&HtlcLocks, LspError> {
+    state
+        .htlc
+        .as_ref()
+        .ok_or_else(|| LspError::InvalidHtlc("HTLC payments are not accepted".to_string()))
+}
+
+/// Lock for a new quote paid with proofs locked to `hash`
+///
+/// Hashes can't be reused, the same locked proofs would pay for both quotes.
+fn new_htlc_lock(state: &CashuLspState, hash: &str) -> Result<HtlcLock, LspError> {
+    let locks = htlc_locks(state)?;
+
+    if !is_valid_hash(hash) {
+        return Err(LspError::InvalidHtlc(format!("Invalid hash: {}", hash)));
+    }
+
+    let hash = hash.to_lowercase();
+
+    let reused = state
+        .db
+        .list_quotes()
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?
+        .iter()
+        .any(|q| q.htlc.as_ref().is_some_and(|h| h.hash == hash));
+
+    if reused {
+        return Err(LspError::InvalidHtlc(format!(
+            "Hash already used: {}",
+            hash
+        )));
+    }
+
+    Ok(HtlcLock {
+        hash,
+        locktime: state.clock.now().saturating_add(locks.locktime_secs),
+    })
+}
+
+fn refund_locks(state: &CashuLspState) -> Result<&RefundLocks, LspError> {
+    state.refunds.as_ref().ok_or_else(|| {
+        LspError::InvalidRefundLock("Refundable payments are not accepted".to_string())
+    })
+}
+
+/// Lock for a new quote paid with proofs refundable to `refund_pubkey`
+fn new_refund_lock(state: &CashuLspState, refund_pubkey: &str) -> Result<RefundLock, LspError> {
+    let locks = refund_locks(state)?;
+
+    let refund_pubkey = cdk::nuts::PublicKey::from_hex(refund_pubkey)
+        .map_err(|e| LspError::InvalidRefundLock(format!("Invalid refund pubkey: {}", e)))?;
+
+    Ok(RefundLock {
+        refund_pubkey: refund_pubkey.to_hex(),
+        locktime: state.clock.now().saturating_add(locks.locktime_secs),
+    })
+}
+
+/// Anti-spam cost of a quote request, checked but not yet spent
+enum AntiSpamProof {
+    Stamp(PowStamp),
+    Bond(AntiSpamBond),
+}
+
+/// Bond token of a quote request, checked but not yet redeemed
+struct AntiSpamBond {
+    mint: MintUrl,
+    unit: CurrencyUnit,
+    token: String,
+    value_sats: u64,
+}
+
+/// Enforce the configured anti-spam requirement for a new quote
+///
+/// Checks the proof of work or the bond token without side effects. Stamps
+/// are marked spent and bonds passed to [`redeem_bond`] once the quote is
+/// ready to be stored, a bond is kept whether or not the quote is ever paid.
+fn check_anti_spam(
+    state: &CashuLspState,
+    payload: &ChannelQuoteRequest,
+) -> Result<Option<AntiSpamProof>, LspError> {
+    if state.sandbox_key.is_some() {
+        return Ok(None);
+    }
+
+    let info = state.info();
+
+    match &info.anti_spam {
+        None => Ok(None),
+        Some(AntiSpam::Pow { difficulty_bits }) => {
+            let stamp = payload.pow.as_deref().ok_or_else(|| {
+                LspError::AntiSpamRejected("Proof of work stamp required".to_string())
+            })?;
+
+            let stamp = verify_pow(
+                stamp,
+                &payload.node_pubkey,
+                payload.channel_size_sats,
+                *difficulty_bits,
+                state.clock.now(),
+            )
+            .ok_or_else(|| LspError::AntiSpamRejected("Invalid proof of work stamp".to_string()))?;
+
+            if state.spent_stamps.contains(&stamp) {
+                return Err(LspError::AntiSpamRejected(
+                    "Proof of work stamp already used".to_string(),
+                ));
+            }
+
+            Ok(Some(AntiSpamProof::Stamp(stamp)))
+        }
+        Some(AntiSpam::Bond { amount_sats }) => {
+            let encoded_token = payload
+                .bond_token
+                .as_deref()
+                .ok_or_else(|| LspError::AntiSpamRejected("Bond token required".to_string()))?;
+
+            let token = Token::from_str(encoded_token)
+                .map_err(|e| LspError::AntiSpamRejected(format!("Invalid bond token: {}", e)))?;
+
+            let mint = token
+                .mint_url()
+                .map_err(|e| LspError::AntiSpamRejected(format!("Invalid bond token: {}", e)))?;
+
+            if !info.accepted_mints.contains(&mint) {
+                return Err(LspError::UnsupportedMint(mint));
+            }
+
+            let unit = token.unit().unwrap_or(CurrencyUnit::Sat);
+
+            if !info.accepted_units.contains(&unit) {
+                return Err(LspError::UnsupportedUnit(unit));
+            }
+
+            let value = token
+                .value()
+                .map_err(|e| LspError::AntiSpamRejected(format!("Invalid bond token: {}", e)))?;
+
+            let value_sats = amount_to_sats(value, &unit, current_rate(state, &unit)?)?;
+
+            if value_sats < *amount_sats {
+                return Err(LspError::InsufficientPayment {
+                    expected: *amount_sats,
+                    received: value_sats,
+                });
+            }
+
+            Ok(Some(AntiSpamProof::Bond(AntiSpamBond {
+                mint,
+                unit,
+                token: encoded_token.to_string(),
+                value_sats,
+            })))
         }
     }
+}
 
-    let response = QuoteStateResponse {
-        id: quote.id,
-        state: quote.state,
-        channel_id,
+/// Swap the proofs of a checked bond into the LSP's wallet
+async fn redeem_bond(
+    state: &CashuLspState,
+    quote_id: Uuid,
+    bond: AntiSpamBond,
+) -> Result<(), LspError> {
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(bond.mint.clone(), bond.unit))
+        .await
+        .ok_or_else(|| LspError::WalletError(format!("Wallet not created for {}", bond.mint)))?;
+
+    wallet
+        .receive(&bond.token, SplitTarget::default(), &[], &[])
+        .await
+        .map_err(|e| {
+            tracing::warn!("Could not redeem bond token: {}", e);
+            LspError::ProofVerificationError(e.to_string())
+        })?;
+
+    METRICS.fee_revenue_sats.inc_by(bond.value_sats);
+
+    let entry = FeeLedgerEntry {
+        quote_id,
+        recorded_at: state.clock.now(),
+        service_fee_sats: 0,
+        onchain_fee_sats: 0,
+        mint_fee_sats: 0,
+        bond_sats: bond.value_sats,
+        revenue_sats: sats_to_i64(bond.value_sats),
     };
 
-    tracing::debug!("Returning quote state for {}: {:?}", id, response);
-    Ok(Json(response))
+    // The bond is already redeemed, only the report would be off
+    if let Err(err) = state.db.add_fee_entry(&entry) {
+        tracing::error!("Could not record bond for quote {}: {}", quote_id, err);
+    }
+
+    Ok(())
 }
 
-pub async fn post_receive_payment(
-    State(state): State<CashuLspState>,
-    Json(payload): Json<PaymentRequestPayload>,
+/// Refuse peers on the deny list, or missing from the allow list when one is set
+fn check_peer_access(state: &CashuLspState, node_pubkey: PublicKey) -> Result<(), LspError> {
+    let allowed = state
+        .db
+        .is_peer_allowed(node_pubkey, &state.peer_access)
+        .map_err(|e| {
+            tracing::error!("Failed to read peer access lists: {}", e);
+            LspError::DatabaseError(e.to_string())
+        })?;
+
+    if !allowed {
+        tracing::warn!("Refusing service to peer {}", node_pubkey);
+        return Err(LspError::PeerNotAllowed(node_pubkey));
+    }
+
+    Ok(())
+}
+
+/// Refuse quotes for channels the LSP could not fund
+///
+/// Funds for quotes that are paid but not yet opened are treated as reserved,
+/// once a channel is opened its funding is no longer spendable. Unpaid quotes
+/// reserve nothing, anyone can create them and they are only removed when
+/// retention prunes them.
+fn check_liquidity(state: &CashuLspState, channel_size_sats: u64) -> Result<(), LspError> {
+    if state.sandbox_key.is_some() {
+        return Ok(());
+    }
+
+    let quotes = state.db.list_quotes().map_err(|e| {
+        tracing::error!("Failed to list quotes: {}", e);
+        LspError::DatabaseError(e.to_string())
+    })?;
+
+    let reserved_sats: u64 = quotes
+        .iter()
+        .filter(|q| match q.state {
+            QuoteState::Paid => true,
+            QuoteState::ChannelPending => q.channel_id.is_none(),
+            QuoteState::Unpaid | QuoteState::ChannelOpen | QuoteState::ChannelExpired => false,
+        })
+        .map(|q| q.channel_size_sats)
+        .sum();
+
+    let available = state
+        .node
+        .inner
+        .list_balances()
+        .spendable_onchain_balance_sats
+        .saturating_sub(reserved_sats);
+
+    if available < channel_size_sats {
+        return Err(LspError::InsufficientLspLiquidity {
+            available,
+            required: channel_size_sats,
+        });
+    }
+
+    Ok(())
+}
+
+/// Stop a single node from buying more than its share of the LSP's funds
+///
+/// Counts channels already open to the node and paid quotes still waiting on
+/// their channel.
+fn check_peer_limits(
+    state: &CashuLspState,
+    node_pubkey: PublicKey,
+    channel_size_sats: u64,
 ) -> Result<(), LspError> {
-    tracing::debug!("Received payment for mint: {}", payload.mint);
+    let info = state.info();
 
-    // Validate mint
-    if !state.cashu_lsp_info.accepted_mints.contains(&payload.mint) {
-        return Err(LspError::UnsupportedMint(payload.mint.clone()));
+    if state.sandbox_key.is_some()
+        || (info.max_channels_per_peer.is_none() && info.max_capacity_per_peer_sat.is_none())
+    {
+        return Ok(());
     }
 
-    // Validate payment ID
-    let id = payload.id.ok_or_else(|| {
-        tracing::warn!("Missing payment ID in request");
-        LspError::InvalidUuid("missing".to_string())
+    let mut channels = 1u64;
+    let mut capacity_sat = channel_size_sats;
+
+    for channel in state.node.inner.list_channels() {
+        if channel.counterparty_node_id == node_pubkey {
+            channels += 1;
+            capacity_sat = capacity_sat.saturating_add(channel.channel_value_sats);
+        }
+    }
+
+    let quotes = state.db.list_node_quotes(node_pubkey).map_err(|e| {
+        tracing::error!("Failed to list quotes: {}", e);
+        LspError::DatabaseError(e.to_string())
     })?;
 
+    for quote in quotes {
+        // Pending quotes with a channel id are already counted as channels
+        let awaiting_channel = quote.state == QuoteState::Paid
+            || (quote.state == QuoteState::ChannelPending && quote.channel_id.is_none());
+
+        if awaiting_channel {
+            channels += 1;
+            capacity_sat = capacity_sat.saturating_add(quote.channel_size_sats);
+        }
+    }
+
+    let channels_exceeded = info
+        .max_channels_per_peer
+        .is_some_and(|max| channels > u64::from(max));
+    let capacity_exceeded = info
+        .max_capacity_per_peer_sat
+        .is_some_and(|max| capacity_sat > max);
+
+    if channels_exceeded || capacity_exceeded {
+        tracing::warn!(
+            "Peer {} would have {} channels with {} sats capacity",
+            node_pubkey,
+            channels,
+            capacity_sat
+        );
+        return Err(LspError::PeerLimitExceeded {
+            node_pubkey,
+            channels,
+            capacity_sat,
+        });
+    }
+
+    Ok(())
+}
+
+/// Don't take payment for a channel that can never be opened
+fn check_peer_reachable(
+    state: &CashuLspState,
+    node_pubkey: PublicKey,
+    addr: &SocketAddress,
+) -> Result<(), LspError> {
+    if state.sandbox_key.is_some() {
+        return Ok(());
+    }
+
+    state
+        .node
+        .inner
+        .connect(node_pubkey, addr.clone(), false)
+        .map_err(|e| {
+            tracing::warn!("Could not connect to {}: {}", node_pubkey, e);
+            LspError::PeerUnreachable(node_pubkey)
+        })
+}
+
+/// Attach a BOLT12 offer for the amount due to `quote` when enabled
+fn create_bolt12_offer(state: &CashuLspState, quote: &mut QuoteInfo) -> Result<(), LspError> {
+    if !state.info().bolt12 || state.sandbox_key.is_some() {
+        return Ok(());
+    }
+
+    let amount_msat = sats_to_msats(quote.expected_payment_sats)
+        .ok_or_else(|| LspError::InternalError("Quote amount overflows msats".to_string()))?;
+
+    let offer = state
+        .node
+        .inner
+        .bolt12_payment()
+        .receive(
+            amount_msat,
+            &format!("Channel quote {}", quote.id),
+            None,
+            None,
+        )
+        .map_err(|e| {
+            tracing::error!("Failed to create BOLT12 offer for {}: {}", quote.id, e);
+            LspError::InternalError(format!("Failed to create BOLT12 offer: {}", e))
+        })?;
+
+    quote.bolt12_offer_id = Some(offer.id().0);
+    quote.bolt12_offer = Some(offer.to_string());
+
+    Ok(())
+}
+
+/// NUT-18 payment request for the amount due on `quote`
+fn build_payment_request(
+    state: &CashuLspState,
+    quote: &QuoteInfo,
+) -> Result<PaymentRequest, LspError> {
+    let transport = Transport::builder()
+        .transport_type(TransportType::HttpPost)
+        .target(state.payment_url.clone())
+        .build()
+        .map_err(|e| {
+            tracing::error!("Failed to build transport: {}", e);
+            LspError::InternalError(format!("Failed to build transport: {}", e))
+        })?;
+
+    let nostr_transport = state
+        .nostr
+        .as_ref()
+        .map(|n| n.transport())
+        .transpose()
+        .map_err(|e| {
+            tracing::error!("Failed to build nostr transport: {}", e);
+            LspError::InternalError(format!("Failed to build nostr transport: {}", e))
+        })?;
+
+    let mut payment_request = PaymentRequest::builder()
+        .payment_id(quote.id)
+        .amount(sats_to_amount(
+            quote.expected_payment_sats,
+            &quote.unit,
+            quote.sats_per_unit,
+        )?)
+        .unit(quote.unit.clone())
+        .single_use(true)
+        .mints(quote.mints.clone())
+        .add_transport(transport);
+
+    if let Some(nostr_transport) = nostr_transport {
+        payment_request = payment_request.add_transport(nostr_transport);
+    }
+
+    Ok(payment_request.build())
+}
+
+/// Re-price an unpaid or expired quote at the current fee rates
+///
+/// The quote keeps its id, peer and channel parameters, only the
+/// amount due and the payment request are refreshed.
+#[utoipa::path(
+    post,
+    path = "/quote/{id}/renew",
+    params(
+        ("id" = String, Path, description = "Quote id"),
+        ("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")
+    ),
+    responses(
+        (status = 200, description = "Quote renewed", body = ChannelQuoteResponse),
+        (status = 400, description = "Quote cannot be renewed", body = String),
+        (status = 404, description = "Unknown quote", body = String),
+        (status = 503, description = "A mint of the quote is unavailable", body = String)
+    )
+)]
+pub async fn post_renew_quote(
+    State(state): State<CashuLspState>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<Json<ChannelQuoteResponse>, LspError> {
+    tracing::debug!("Received quote renewal request for ID: {}", id);
+
+    let state = state.with_api_key(&headers)?;
+
+    let info = state.info();
+
     let id = Uuid::from_str(&id).map_err(|e| {
         tracing::warn!("Invalid UUID format: {} - {}", id, e);
         LspError::InvalidUuid(id.clone())
     })?;
 
-    // Get quote
     let quote = state.db.get_quote(id).map_err(|e| {
         tracing::warn!("Quote not found: {} - {}", id, e);
         LspError::QuoteNotFound(id)
     })?;
 
-    // Validate quote state
-    if quote.state != QuoteState::Unpaid {
-        tracing::warn!("Quote {} has invalid state: {:?}", id, quote.state);
+    // Serialize with payments for the same peer so a quote isn't
+    // re-priced while its payment is being processed
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    let mut quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    if !matches!(quote.state, QuoteState::Unpaid | QuoteState::ChannelExpired) {
         return Err(LspError::InvalidQuoteState {
             id,
             state: quote.state,
         });
     }
 
-    // Validate payment amount
-    let received_amount =
-        Amount::try_sum(payload.proofs.iter().map(|p| p.amount)).map_err(|e| {
-            tracing::warn!("Failed to sum proof amounts: {}", e);
-            LspError::InternalError("Failed to sum proof amounts".to_string())
-        })?;
+    if let Some(mint) = quote
+        .mints
+        .iter()
+        .find(|m| !info.accepted_mints.contains(m))
+    {
+        return Err(LspError::UnsupportedMint(mint.clone()));
+    }
 
-    if Amount::from(quote.expected_payment_sats) < received_amount {
-        tracing::warn!(
-            "Insufficient payment: expected {}, received {}",
-            quote.expected_payment_sats,
-            received_amount
-        );
-        return Err(LspError::InsufficientPayment {
-            expected: quote.expected_payment_sats,
-            received: received_amount.into(),
-        });
+    for mint in quote.mints.iter() {
+        check_mint_exposure(&state, mint).await?;
     }
 
-    // Get wallet for the mint
-    let wallet = state
-        .node
-        .wallet
-        .get_wallet(&WalletKey::new(payload.mint.clone(), CurrencyUnit::Sat))
-        .await
-        .ok_or_else(|| {
-            let msg = format!("Wallet not created for {}", payload.mint);
-            tracing::warn!("{}", msg);
-            LspError::WalletError(msg)
-        })?;
+    if !info.accepted_units.contains(&quote.unit) {
+        return Err(LspError::UnsupportedUnit(quote.unit));
+    }
 
-    // Receive and verify proofs
-    let amount = wallet
-        .receive_proofs(payload.proofs, SplitTarget::default(), &[], &[])
-        .await
-        .map_err(|e| {
-            tracing::error!("Could not receive proofs for {}: {}", id, e);
-            LspError::ProofVerificationError(e.to_string())
-        })?;
+    check_peer_access(&state, quote.node_pubkey)?;
 
-    tracing::info!(
-        "Successfully received payment of {} sats for quote {}",
-        amount,
-        id
+    check_peer_reachable(&state, quote.node_pubkey, &quote.addr)?;
+
+    let fee = info.quote_fee(quote.channel_size_sats, &quote.mints)?;
+
+    let previous_state = quote.state;
+
+    if quote.mode == QuoteMode::InboundOnly && !info.inbound_only {
+        return Err(LspError::UnsupportedQuoteMode(quote.mode));
+    }
+
+    let payment_required = quote.mode.payment_required(
+        quote.channel_size_sats,
+        fee,
+        quote.push_amount_sats.unwrap_or_default(),
     );
 
-    // Update quote state
-    let mut quote = state
+    quote.sats_per_unit = current_rate(&state, &quote.unit)?;
+    quote.mint_fee_sats = quote_mint_fee(
+        &state,
+        &quote.mints,
+        &quote.unit,
+        quote.sats_per_unit,
+        payment_required,
+    )
+    .await?;
+    quote.expected_payment_sats = payment_required.saturating_add(quote.mint_fee_sats);
+
+    let htlc_locktime_secs = match quote.htlc {
+        Some(_) => htlc_locks(&state)?.locktime_secs,
+        None => 0,
+    };
+
+    let refund_locktime_secs = match quote.refund_lock {
+        Some(_) => refund_locks(&state)?.locktime_secs,
+        None => 0,
+    };
+
+    restart_quote(
+        &mut quote,
+        state.clock.as_ref(),
+        htlc_locktime_secs,
+        refund_locktime_secs,
+    );
+
+    create_bolt12_offer(&state, &mut quote)?;
+
+    let payment_request = build_payment_request(&state, &quote)?;
+
+    state
         .db
-        .update_quote_state(id, QuoteState::ChannelPending)
+        .add_quote(&quote, QuoteActor::Client)
         .map_err(|e| {
-            tracing::error!("Failed to update quote state: {}", e);
+            tracing::error!("Failed to update quote in database: {}", e);
             LspError::DatabaseError(e.to_string())
         })?;
 
-    // Try to open the channel
+    state.publish_event(LspEventKind::QuoteRenewed {
+        quote_id: id,
+        expected_payment_sats: quote.expected_payment_sats,
+    });
+
+    if previous_state != QuoteState::Unpaid {
+        state.publish_event(LspEventKind::QuoteStateChanged {
+            quote_id: id,
+            state: QuoteState::Unpaid,
+        });
+    }
+
     tracing::info!(
-        "Opening channel to {} with {} sats (push: {:?})",
-        quote.node_pubkey,
-        quote.channel_size_sats,
-        quote.push_amount_sats
+        "Renewed quote {} at {} sats",
+        id,
+        quote.expected_payment_sats
     );
 
-    let open_channel = state.node.inner.open_announced_channel(
-        quote.node_pubkey,
-        quote.addr.clone(),
-        quote.channel_size_sats,
-        quote.push_amount_sats.map(|a| a * 1_000),
-        None,
-    );
+    Ok(Json(ChannelQuoteResponse::new(
+        &state,
+        &quote,
+        &payment_request,
+    )))
+}
 
-    match open_channel {
-        Ok(channel_id) => {
-            tracing::info!("Successfully opened channel with ID: {}", channel_id.0);
-            quote.channel_id = Some(channel_id);
-            quote.state = QuoteState::ChannelOpen;
-            state.db.add_quote(&quote).map_err(|e| {
-                tracing::error!("Failed to update quote with channel info: {}", e);
+/// Make a renewed quote unpaid again, as if created now, with its locks restarted
+fn restart_quote(
+    quote: &mut QuoteInfo,
+    clock: &dyn Clock,
+    htlc_locktime_secs: u64,
+    refund_locktime_secs: u64,
+) {
+    let now = clock.now();
+
+    if let Some(htlc) = quote.htlc.as_mut() {
+        htlc.locktime = now.saturating_add(htlc_locktime_secs);
+    }
+
+    if let Some(refund_lock) = quote.refund_lock.as_mut() {
+        refund_lock.locktime = now.saturating_add(refund_locktime_secs);
+    }
+
+    quote.state = QuoteState::Unpaid;
+    quote.created_at = now;
+}
+
+#[utoipa::path(
+    get,
+    path = "/quote/{id}/proof",
+    params(
+        ("id" = String, Path, description = "Quote id"),
+        ("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")
+    ),
+    responses(
+        (status = 200, description = "Signed quote terms", body = QuoteProof),
+        (status = 404, description = "Unknown quote", body = String)
+    )
+)]
+pub async fn get_quote_proof(
+    State(state): State<CashuLspState>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<Json<QuoteProof>, LspError> {
+    let state = state.with_api_key(&headers)?;
+
+    let id = Uuid::from_str(&id).map_err(|e| {
+        tracing::warn!("Invalid UUID format: {} - {}", id, e);
+        LspError::InvalidUuid(id.clone())
+    })?;
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    Ok(Json(QuoteProof::new(&state, &quote)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/quote/{id}/receipt",
+    params(
+        ("id" = String, Path, description = "Quote id"),
+        ("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")
+    ),
+    responses(
+        (status = 200, description = "Signed payment receipt", body = SignedReceipt),
+        (status = 404, description = "Unknown quote or payment not redeemed yet", body = String)
+    )
+)]
+pub async fn get_quote_receipt(
+    State(state): State<CashuLspState>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<Json<SignedReceipt>, LspError> {
+    let state = state.with_api_key(&headers)?;
+
+    let id = Uuid::from_str(&id).map_err(|e| {
+        tracing::warn!("Invalid UUID format: {} - {}", id, e);
+        LspError::InvalidUuid(id.clone())
+    })?;
+
+    state
+        .db
+        .get_receipt(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?
+        .map(Json)
+        .ok_or(LspError::QuoteNotFound(id))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteStateResponse {
+    pub id: Uuid,
+    pub state: QuoteState,
+    pub channel_id: Option<String>,
+    /// State transitions, only included when requested with `?history=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<QuoteStateTransition>>,
+    /// Why the last payment for the quote could not be processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_error: Option<String>,
+    /// Funding details once the channel is pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<QuoteChannel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+pub struct QuoteStateQuery {
+    /// Include the quote's state history
+    #[serde(default)]
+    pub history: bool,
+    /// Only include transitions at or after this unix time
+    pub since: Option<u64>,
+    /// Maximum number of transitions to return
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/quote/{id}",
+    params(
+        ("id" = String, Path, description = "Quote id"),
+        QuoteStateQuery,
+        ("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")
+    ),
+    responses(
+        (status = 200, description = "Quote state", body = QuoteStateResponse),
+        (status = 404, description = "Unknown quote", body = String)
+    )
+)]
+pub async fn get_quote_state(
+    State(state): State<CashuLspState>,
+    Query(query): Query<QuoteStateQuery>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<Json<QuoteStateResponse>, LspError> {
+    tracing::debug!("Received quote state request for ID: {}", id);
+
+    let state = state.with_api_key(&headers)?;
+
+    let id = Uuid::from_str(&id).map_err(|e| {
+        tracing::warn!("Invalid UUID format: {} - {}", id, e);
+        LspError::InvalidUuid(id.clone())
+    })?;
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    let mut channel_id = quote.channel.as_ref().map(|c| c.channel_id.clone());
+
+    // Quotes paid before funding details were recorded
+    if let (None, Some(user_channel_id)) = (&channel_id, quote.channel_id) {
+        let all_channel = state.node.inner.list_channels();
+
+        let channel: Vec<&ldk_node::ChannelDetails> = all_channel
+            .iter()
+            .filter(|c| c.user_channel_id == user_channel_id)
+            .collect();
+
+        if let Some(channel_info) = channel.get(0) {
+            channel_id = Some(channel_info.channel_id.to_string());
+        } else {
+            tracing::info!("Unkown channel for Channel user id: {}", user_channel_id.0);
+        }
+    }
+
+    let history = if query.history {
+        let history = state
+            .db
+            .list_quote_history(id, query.since.unwrap_or_default(), query.limit)
+            .map_err(|e| {
+                tracing::error!("Failed to read quote history: {}", e);
                 LspError::DatabaseError(e.to_string())
             })?;
+        Some(history)
+    } else {
+        None
+    };
+
+    let response = QuoteStateResponse {
+        id: quote.id,
+        state: quote.state,
+        channel_id,
+        history,
+        payment_error: quote.payment_error,
+        channel: quote.channel,
+    };
+
+    tracing::debug!("Returning quote state for {}: {:?}", id, response);
+    Ok(Json(response))
+}
+
+/// Serialized `cashuA`/`cashuB` token paying a quote, for wallets without NUT-18 support
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenPayment {
+    pub quote_id: Uuid,
+    pub token: String,
+}
+
+/// Body of `POST /payment`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PaymentBody {
+    Payload(PaymentRequestPayload),
+    Token(TokenPayment),
+}
+
+impl PaymentBody {
+    fn into_payload(self) -> Result<PaymentRequestPayload, LspError> {
+        let payment = match self {
+            Self::Payload(payload) => return Ok(payload),
+            Self::Token(payment) => payment,
+        };
+
+        let token =
+            Token::from_str(&payment.token).map_err(|e| LspError::InvalidToken(e.to_string()))?;
+
+        let mint = token
+            .mint_url()
+            .map_err(|e| LspError::InvalidToken(e.to_string()))?;
+
+        Ok(PaymentRequestPayload {
+            id: Some(payment.quote_id.to_string()),
+            memo: token.memo().clone(),
+            mint,
+            unit: token.unit().unwrap_or(CurrencyUnit::Sat),
+            proofs: token.proofs(),
+        })
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/payment",
+    request_body(
+        content = Object,
+        description = "NUT-18 payment request payload, or a `TokenPayment` with a serialized token"
+    ),
+    params(("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")),
+    responses(
+        (status = 202, description = "Proofs redeemed, or the same payload was accepted before; poll the quote for the channel", body = PaymentResponse),
+        (status = 400, description = "Invalid payment or proofs already spent", body = String),
+        (status = 409, description = "A payment for the quote is already being processed", body = String),
+        (status = 503, description = "The mint could not be reached, the payment can be retried", body = String)
+    )
+)]
+pub async fn post_receive_payment(
+    State(state): State<CashuLspState>,
+    headers: HeaderMap,
+    Json(body): Json<PaymentBody>,
+) -> Result<(StatusCode, Json<PaymentResponse>), LspError> {
+    let state = state.with_api_key(&headers)?;
+    let quote_id = accept_payment(&state, body.into_payload()?).await?;
+
+    // HTLC payments are only redeemed once the preimage is revealed
+    let receipt = state.db.get_receipt(quote_id).unwrap_or_else(|e| {
+        tracing::error!("Failed to read receipt for {}: {}", quote_id, e);
+        None
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(PaymentResponse { quote_id, receipt }),
+    ))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PaymentResponse {
+    pub quote_id: Uuid,
+    /// Signed receipt for the redeemed proofs, absent for sandbox and HTLC payments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<SignedReceipt>,
+}
+
+/// Validate a payment, redeem its proofs and open the channel in the background
+///
+/// Shared by every transport a [`PaymentRequestPayload`] can arrive on. The
+/// proofs are swapped for fresh ones before returning, so the sender can no
+/// longer spend them once the payment is accepted. The outcome of opening the
+/// channel is surfaced through the quote state.
+pub(crate) async fn accept_payment(
+    state: &CashuLspState,
+    payload: PaymentRequestPayload,
+) -> Result<Uuid, LspError> {
+    tracing::debug!("Received payment for mint: {}", payload.mint);
+
+    if let Some(id) = replayed_payment(state, &payload)? {
+        tracing::info!("Payment for quote {} was already accepted", id);
+        return Ok(id);
+    }
+
+    let (quote, _) = validate_payment(state, &payload)?;
+    let id = quote.id;
+
+    // Sandbox payments are never redeemed so there is nothing to recover
+    if state.sandbox_key.is_none() {
+        let added = state.db.add_pending_payment(id, &payload).map_err(|e| {
+            tracing::error!("Failed to persist payment for {}: {}", id, e);
+            LspError::DatabaseError(e.to_string())
+        })?;
+
+        if !added {
+            return Err(LspError::PaymentInProgress(id));
+        }
+    }
+
+    let result = redeem_payment(state, &payload).await;
+
+    if result.is_ok() {
+        record_payment_hash(state, id, &payload);
+    }
+
+    finish_payment(state, id, &result);
+
+    if result? {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+            let result = open_paid_channel(&state, id).await;
+            finish_payment(&state, id, &result);
+        });
+    }
+
+    Ok(id)
+}
+
+/// Hash identifying a payment payload, independent of how it was encoded
+fn payment_hash(payload: &PaymentRequestPayload) -> Result<String, LspError> {
+    let payload = serde_json::to_vec(payload)
+        .map_err(|e| LspError::InternalError(format!("Could not encode payment payload: {}", e)))?;
+
+    Ok(sha256::Hash::hash(&payload).to_string())
+}
+
+/// Quote `payload` already paid, so a resubmission gets the same answer
+fn replayed_payment(
+    state: &CashuLspState,
+    payload: &PaymentRequestPayload,
+) -> Result<Option<Uuid>, LspError> {
+    let Some(id) = payload.id.as_deref().and_then(|id| Uuid::from_str(id).ok()) else {
+        return Ok(None);
+    };
+
+    let accepted_hash = state
+        .db
+        .get_payment_hash(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    match accepted_hash {
+        Some(accepted_hash) if accepted_hash == payment_hash(payload)? => Ok(Some(id)),
+        _ => Ok(None),
+    }
+}
+
+/// Remember an accepted payload, the payment is already accepted if this fails
+fn record_payment_hash(state: &CashuLspState, id: Uuid, payload: &PaymentRequestPayload) {
+    let result = payment_hash(payload).and_then(|hash| {
+        state
+            .db
+            .set_payment_hash(id, &hash)
+            .map_err(|e| LspError::DatabaseError(e.to_string()))
+    });
+
+    if let Err(err) = result {
+        tracing::error!("Failed to record payment hash for {}: {}", id, err);
+    }
+}
+
+/// Resume payments that were accepted but not processed before a restart
+pub fn resume_pending_payments(state: &CashuLspState) -> anyhow::Result<()> {
+    let pending = state.db.list_pending_payments()?;
+
+    if !pending.is_empty() {
+        tracing::info!("Resuming {} pending payments", pending.len());
+    }
+
+    for (id, payload) in pending {
+        spawn_payment(state.clone(), id, payload);
+    }
+
+    Ok(())
+}
+
+fn spawn_payment(state: CashuLspState, id: Uuid, payload: PaymentRequestPayload) {
+    tokio::spawn(async move {
+        let result = process_payment(&state, payload).await;
+        finish_payment(&state, id, &result);
+    });
+}
+
+/// Record the outcome of processing a payment and drop it from the pending payments
+fn finish_payment<T>(state: &CashuLspState, id: Uuid, result: &Result<T, LspError>) {
+    let payment_error = match result {
+        Ok(_) => None,
+        // Another payment for the quote got there first
+        Err(LspError::InvalidQuoteState { .. }) => {
+            tracing::warn!("Ignoring payment for already paid quote {}", id);
+            None
         }
         Err(err) => {
-            tracing::error!("Could not open channel for quote {}: {}", quote.id, err);
-            quote.state = QuoteState::Paid;
-            state.db.add_quote(&quote).map_err(|e| {
-                tracing::error!(
-                    "Failed to update quote state after channel open failure: {}",
-                    e
-                );
-                LspError::DatabaseError(e.to_string())
-            })?;
+            tracing::warn!("Could not process payment for {}: {}", id, err);
+            Some(err.to_string())
         }
+    };
+
+    if let Err(err) = state.db.set_payment_error(id, payment_error) {
+        tracing::error!("Failed to record payment outcome for {}: {}", id, err);
+    }
+
+    if state.sandbox_key.is_none() {
+        if let Err(err) = state.db.remove_pending_payment(id) {
+            tracing::error!("Failed to remove pending payment {}: {}", id, err);
+        }
+    }
+}
+
+/// Check a payment against its quote, returning the quote and sats received
+fn validate_payment(
+    state: &CashuLspState,
+    payload: &PaymentRequestPayload,
+) -> Result<(QuoteInfo, u64), LspError> {
+    let info = state.info();
+
+    // Validate mint
+    if !info.accepted_mints.contains(&payload.mint) {
+        return Err(LspError::UnsupportedMint(payload.mint.clone()));
+    }
+
+    // Validate unit
+    if !info.accepted_units.contains(&payload.unit) {
+        return Err(LspError::UnsupportedUnit(payload.unit.clone()));
+    }
+
+    // Validate payment ID
+    let id = payload.id.as_ref().ok_or_else(|| {
+        tracing::warn!("Missing payment ID in request");
+        LspError::InvalidUuid("missing".to_string())
+    })?;
+
+    let id = Uuid::from_str(id).map_err(|e| {
+        tracing::warn!("Invalid UUID format: {} - {}", id, e);
+        LspError::InvalidUuid(id.clone())
+    })?;
+
+    // Get quote
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    // The quote was priced for a specific set of mints
+    if !quote.mints.is_empty() && !quote.mints.contains(&payload.mint) {
+        return Err(LspError::UnsupportedMint(payload.mint.clone()));
+    }
+
+    // The quote was priced in a specific unit
+    if quote.unit != payload.unit {
+        return Err(LspError::UnsupportedUnit(payload.unit.clone()));
+    }
+
+    // Validate quote state
+    if quote.state != QuoteState::Unpaid {
+        tracing::warn!("Quote {} has invalid state: {:?}", id, quote.state);
+        return Err(LspError::InvalidQuoteState {
+            id,
+            state: quote.state,
+        });
+    }
+
+    // Validate payment amount
+    let received_amount =
+        Amount::try_sum(payload.proofs.iter().map(|p| p.amount)).map_err(|e| {
+            tracing::warn!("Failed to sum proof amounts: {}", e);
+            LspError::InternalError("Failed to sum proof amounts".to_string())
+        })?;
+
+    let sats_per_unit = current_rate(state, &payload.unit)?;
+
+    let received_sats = amount_to_sats(received_amount, &payload.unit, sats_per_unit)?;
+
+    // The rate may have moved since the quote was priced
+    let required_sats = match (sats_per_unit, state.rates.as_ref()) {
+        (Some(_), Some(rates)) => quote.expected_payment_sats.saturating_sub(
+            quote
+                .expected_payment_sats
+                .saturating_mul(rates.slippage_ppk)
+                / 1_000,
+        ),
+        _ => quote.expected_payment_sats,
+    };
+
+    if received_sats < required_sats {
+        tracing::warn!(
+            "Insufficient payment: expected {}, received {}",
+            quote.expected_payment_sats,
+            received_sats
+        );
+        return Err(LspError::InsufficientPayment {
+            expected: quote.expected_payment_sats,
+            received: received_sats,
+        });
+    }
+
+    Ok((quote, received_sats))
+}
+
+/// Redeem a payment for a quote and open the paid for channel
+async fn process_payment(
+    state: &CashuLspState,
+    payload: PaymentRequestPayload,
+) -> Result<(), LspError> {
+    let (quote, _) = validate_payment(state, &payload)?;
+
+    // Payments for different peers are processed concurrently, but channel
+    // opens to the same peer must not race
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    let open_channel = redeem_payment_locked(state, &payload).await?;
+
+    record_payment_hash(state, quote.id, &payload);
+
+    if open_channel {
+        open_paid_channel(state, quote.id).await?;
     }
 
-    tracing::info!("Payment processing completed for quote {}", id);
     Ok(())
 }
+
+/// Swap the proofs of a payment for fresh ones held by the LSP and mark the quote paid
+///
+/// Returns whether a channel still has to be opened, sandbox payments are
+/// settled without one.
+async fn redeem_payment(
+    state: &CashuLspState,
+    payload: &PaymentRequestPayload,
+) -> Result<bool, LspError> {
+    let (quote, _) = validate_payment(state, payload)?;
+
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    redeem_payment_locked(state, payload).await
+}
+
+/// [`redeem_payment`] for callers holding the peer lock for the quote's node
+async fn redeem_payment_locked(
+    state: &CashuLspState,
+    payload: &PaymentRequestPayload,
+) -> Result<bool, LspError> {
+    // Re-validate now that no other payment for this peer is in flight
+    let (quote, received_sats) = validate_payment(state, payload)?;
+    let id = quote.id;
+
+    if let Some(key) = &state.sandbox_key {
+        settle_sandbox_payment(state, key, quote, received_sats)?;
+        return Ok(false);
+    }
+
+    // Get wallet for the mint
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(payload.mint.clone(), payload.unit.clone()))
+        .await
+        .ok_or_else(|| {
+            let msg = format!("Wallet not created for {}", payload.mint);
+            tracing::warn!("{}", msg);
+            LspError::WalletError(msg)
+        })?;
+
+    METRICS.payments_received.inc();
+
+    if let Some(htlc) = quote.htlc.as_ref() {
+        lock_htlc_payment(state, id, htlc, &wallet, payload).await?;
+        return Ok(true);
+    }
+
+    let proof_count = payload.proofs.len() as u64;
+
+    let signing_keys = match quote.refund_lock.as_ref() {
+        Some(refund_lock) => vec![check_refund_lock(state, refund_lock, payload)?],
+        None => vec![],
+    };
+
+    // Swapping stores the fresh proofs in the wallet database
+    let amount = wallet
+        .receive_proofs(
+            payload.proofs.clone(),
+            SplitTarget::default(),
+            &signing_keys,
+            &[],
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Could not receive proofs for {}: {}", id, e);
+            redeem_error(e)
+        })?;
+
+    METRICS.proofs_redeemed.inc_by(proof_count);
+
+    tracing::info!(
+        "Successfully received payment of {} {} for quote {}",
+        amount,
+        payload.unit,
+        id
+    );
+
+    record_quote_payment(state, id, payload);
+
+    transition_quote(
+        state,
+        id,
+        QuoteState::Unpaid,
+        QuoteState::Paid,
+        QuoteActor::Client,
+    )?;
+
+    schedule_channel_open(state, id);
+
+    Ok(true)
+}
+
+/// Move a quote from `from` to `to` and publish the change
+///
+/// Fails with [`LspError::InvalidQuoteState`] if another request moved the
+/// quote out of `from` first. Returns the quote in its new state.
+fn transition_quote(
+    state: &CashuLspState,
+    id: Uuid,
+    from: QuoteState,
+    to: QuoteState,
+    actor: QuoteActor,
+) -> Result<QuoteInfo, LspError> {
+    let mut quote = state
+        .db
+        .transition_quote_state(id, from, to, actor)
+        .map_err(|e| {
+            tracing::error!("Failed to update quote state: {}", e);
+            LspError::DatabaseError(e.to_string())
+        })?;
+
+    if quote.state != from {
+        tracing::warn!(
+            "Quote {} moved to {:?} before it could move from {:?} to {:?}",
+            id,
+            quote.state,
+            from,
+            to
+        );
+        return Err(LspError::InvalidQuoteState {
+            id,
+            state: quote.state,
+        });
+    }
+
+    quote.state = to;
+
+    state.publish_event(LspEventKind::QuoteStateChanged {
+        quote_id: id,
+        state: to,
+    });
+
+    Ok(quote)
+}
+
+/// Record the proofs redeemed for a quote and issue a signed receipt for them
+///
+/// The proofs are already redeemed, a missing record must not fail the payment.
+fn record_quote_payment(state: &CashuLspState, id: Uuid, payload: &PaymentRequestPayload) {
+    let proofs = payload
+        .proofs
+        .iter()
+        .map(|proof| {
+            Ok(ReceivedProof {
+                amount: proof.amount.into(),
+                keyset_id: proof.keyset_id.to_string(),
+                y: proof.y()?.to_hex(),
+            })
+        })
+        .collect::<Result<Vec<_>, cdk::nuts::nut00::Error>>();
+
+    match proofs {
+        Ok(proofs) => {
+            let payment = QuotePayment {
+                mint_url: payload.mint.clone(),
+                unit: payload.unit.clone(),
+                proofs,
+                received_at: state.clock.now(),
+                sats_per_unit: current_rate(state, &payload.unit).ok().flatten(),
+            };
+
+            if let Err(err) = state.db.add_quote_payment(id, &payment) {
+                tracing::error!("Failed to record proofs for {}: {}", id, err);
+            }
+
+            let receipt = PaymentReceipt {
+                quote_id: id,
+                mint_url: payment.mint_url,
+                unit: payment.unit,
+                amount: payment.proofs.iter().map(|p| p.amount).sum(),
+                received_at: payment.received_at,
+            };
+
+            if let Err(err) = state.db.add_receipt(id, &sign_receipt(state, &receipt)) {
+                tracing::error!("Failed to store receipt for {}: {}", id, err);
+            }
+        }
+        Err(err) => tracing::error!("Failed to identify proofs for {}: {}", id, err),
+    }
+}
+
+/// Check the proofs of a payment carry the quote's refund lock
+///
+/// Returns the key to sign them with when swapping.
+fn check_refund_lock(
+    state: &CashuLspState,
+    refund_lock: &RefundLock,
+    payload: &PaymentRequestPayload,
+) -> Result<cdk::nuts::SecretKey, LspError> {
+    let locks = refund_locks(state)?;
+    let pubkey = locks.pubkey();
+
+    let refund_pubkey = cdk::nuts::PublicKey::from_hex(&refund_lock.refund_pubkey)
+        .map_err(|e| LspError::InternalError(format!("Invalid stored refund pubkey: {}", e)))?;
+
+    for proof in payload.proofs.iter() {
+        check_refund_proof(proof, &pubkey, &refund_pubkey, refund_lock.locktime)
+            .map_err(LspError::InvalidRefundLock)?;
+    }
+
+    Ok(locks.key.clone())
+}
+
+/// Accept HTLC locked proofs for a quote without redeeming them
+///
+/// The proofs must be signed by the mint and unspent. They are kept until the
+/// payer reveals the preimage once the channel is ready, see [`post_htlc_preimage`].
+async fn lock_htlc_payment(
+    state: &CashuLspState,
+    id: Uuid,
+    htlc: &HtlcLock,
+    wallet: &Wallet,
+    payload: &PaymentRequestPayload,
+) -> Result<(), LspError> {
+    let pubkey = htlc_locks(state)?.pubkey();
+
+    for proof in payload.proofs.iter() {
+        check_htlc_proof(proof, &htlc.hash, &pubkey, htlc.locktime)
+            .map_err(LspError::InvalidHtlc)?;
+
+        // Locked proofs are not swapped, so the DLEQ proof is all that shows the mint signed them
+        let keys = wallet
+            .get_keyset_keys(proof.keyset_id)
+            .await
+            .map_err(redeem_error)?;
+
+        let key = keys.amount_key(proof.amount).ok_or_else(|| {
+            LspError::ProofVerificationError(format!(
+                "Unknown amount {} for keyset {}",
+                proof.amount, proof.keyset_id
+            ))
+        })?;
+
+        proof
+            .verify_dleq(key)
+            .map_err(|e| LspError::ProofVerificationError(e.to_string()))?;
+    }
+
+    let states = wallet
+        .check_proofs_spent(payload.proofs.clone())
+        .await
+        .map_err(redeem_error)?;
+
+    if states.iter().any(|s| s.state != ProofState::Unspent) {
+        return Err(LspError::ProofsAlreadySpent);
+    }
+
+    state
+        .db
+        .add_locked_payment(id, payload)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    tracing::info!("Accepted HTLC locked payment for quote {}", id);
+
+    transition_quote(
+        state,
+        id,
+        QuoteState::Unpaid,
+        QuoteState::Paid,
+        QuoteActor::Client,
+    )?;
+
+    schedule_channel_open(state, id);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HtlcPreimageRequest {
+    /// Hex preimage of the quote's HTLC hash
+    pub preimage: String,
+}
+
+/// Reveal the preimage of an HTLC paid quote once its channel is ready
+///
+/// The LSP redeems the locked proofs with the preimage and its HTLC key.
+#[utoipa::path(
+    post,
+    path = "/quote/{id}/preimage",
+    request_body = HtlcPreimageRequest,
+    params(
+        ("id" = String, Path, description = "Quote id"),
+        ("X-Api-Key" = Option<String>, Header, description = "Sandbox API key")
+    ),
+    responses(
+        (status = 200, description = "Locked proofs redeemed"),
+        (status = 400, description = "Wrong preimage or channel not ready", body = String),
+        (status = 404, description = "Unknown quote", body = String),
+        (status = 503, description = "The mint could not be reached, the preimage can be sent again", body = String)
+    )
+)]
+pub async fn post_htlc_preimage(
+    State(state): State<CashuLspState>,
+    headers: HeaderMap,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Json(payload): Json<HtlcPreimageRequest>,
+) -> Result<StatusCode, LspError> {
+    let state = state.with_api_key(&headers)?;
+
+    let id = Uuid::from_str(&id).map_err(|e| {
+        tracing::warn!("Invalid UUID format: {} - {}", id, e);
+        LspError::InvalidUuid(id.clone())
+    })?;
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    let htlc = quote
+        .htlc
+        .as_ref()
+        .ok_or_else(|| LspError::InvalidHtlc(format!("Quote {} is not HTLC locked", id)))?;
+
+    if quote.state != QuoteState::ChannelOpen {
+        return Err(LspError::InvalidQuoteState {
+            id,
+            state: quote.state,
+        });
+    }
+
+    if !verify_preimage(&payload.preimage, &htlc.hash) {
+        return Err(LspError::InvalidHtlc("Preimage does not match".to_string()));
+    }
+
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    let locked = state
+        .db
+        .get_locked_payment(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    // Already redeemed
+    let Some(locked) = locked else {
+        return Ok(StatusCode::OK);
+    };
+
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(locked.mint.clone(), locked.unit.clone()))
+        .await
+        .ok_or_else(|| LspError::WalletError(format!("Wallet not created for {}", locked.mint)))?;
+
+    let amount = wallet
+        .receive_proofs(
+            locked.proofs.clone(),
+            SplitTarget::default(),
+            &[htlc_locks(&state)?.key.clone()],
+            &[payload.preimage.clone()],
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Could not redeem locked proofs for {}: {}", id, e);
+            redeem_error(e)
+        })?;
+
+    METRICS.proofs_redeemed.inc_by(locked.proofs.len() as u64);
+
+    record_quote_payment(&state, id, &locked);
+
+    state
+        .db
+        .remove_locked_payment(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    tracing::info!(
+        "Redeemed HTLC payment of {} {} for quote {}",
+        amount,
+        locked.unit,
+        id
+    );
+
+    Ok(StatusCode::OK)
+}
+
+/// Tell a sender whose proofs were spent apart from a mint that could not be reached
+fn redeem_error(err: cdk::Error) -> LspError {
+    match err {
+        cdk::Error::TokenAlreadySpent => LspError::ProofsAlreadySpent,
+        cdk::Error::HttpError(msg) => LspError::MintUnavailable(msg),
+        err => LspError::ProofVerificationError(err.to_string()),
+    }
+}
+
+/// Open the channel for a quote that has been paid for
+///
+/// Must be called while holding the peer lock for the quote's node. The
+/// quote stays `ChannelPending` until the node reports the channel ready.
+async fn open_paid_channel(state: &CashuLspState, id: Uuid) -> Result<(), LspError> {
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    // The peer may have been denied since the quote was created, keep the
+    // payment but don't open the channel
+    if let Err(err) = check_peer_access(state, quote.node_pubkey) {
+        if let Err(e) = state.db.set_payment_error(id, Some(err.to_string())) {
+            tracing::error!("Failed to record denied peer for {}: {}", id, e);
+        }
+        // Retrying can't help until the operator allows the peer again
+        finish_channel_open_job(state, id);
+        return Err(err);
+    }
+
+    let push_amount_msats = quote
+        .push_amount_sats
+        .map(|a| {
+            sats_to_msats(a)
+                .ok_or_else(|| LspError::InternalError("Push amount overflows msats".to_string()))
+        })
+        .transpose()?;
+
+    // Only one caller gets to open the channel
+    let quote = transition_quote(
+        state,
+        id,
+        QuoteState::Paid,
+        QuoteState::ChannelPending,
+        QuoteActor::Lsp,
+    )?;
+
+    // Try to open the channel
+    tracing::info!(
+        "Opening channel to {} with {} sats (push: {:?})",
+        quote.node_pubkey,
+        quote.channel_size_sats,
+        quote.push_amount_sats
+    );
+
+    let open_channel = state.node.inner.open_announced_channel(
+        quote.node_pubkey,
+        quote.addr.clone(),
+        quote.channel_size_sats,
+        push_amount_msats,
+        channel_config(&quote),
+    );
+
+    match open_channel {
+        Ok(channel_id) => {
+            tracing::info!("Successfully opened channel with ID: {}", channel_id.0);
+            METRICS.channels_opened.inc();
+            METRICS.fee_revenue_sats.inc_by(quote.service_fee_sats());
+            state
+                .db
+                .set_quote_channel_id(id, Some(channel_id))
+                // Details of a previous channel that closed before becoming ready
+                .and_then(|_| state.db.set_quote_channel(id, None))
+                .map_err(|e| {
+                    tracing::error!("Failed to update quote with channel info: {}", e);
+                    LspError::DatabaseError(e.to_string())
+                })?;
+            finish_channel_open_job(state, id);
+        }
+        Err(err) => {
+            tracing::error!("Could not open channel for quote {}: {}", quote.id, err);
+            METRICS.channel_open_failures.inc();
+            reschedule_channel_open(state, id, err.to_string());
+            transition_quote(
+                state,
+                id,
+                QuoteState::ChannelPending,
+                QuoteState::Paid,
+                QuoteActor::Lsp,
+            )?;
+        }
+    }
+
+    tracing::info!("Payment processing completed for quote {}", id);
+    Ok(())
+}
+
+/// Persist that the channel of a just paid quote still has to be opened
+///
+/// The payment is already redeemed, so failures are logged only and leave
+/// the quote for the operator to retry.
+fn schedule_channel_open(state: &CashuLspState, id: Uuid) {
+    // The paying request opens the channel right away, the job is the fallback
+    let job = ChannelOpenJob {
+        quote_id: id,
+        attempts: 0,
+        next_attempt_at: state.clock.now() + CHANNEL_OPEN_RETRY_INTERVAL.as_secs(),
+        last_error: None,
+    };
+
+    if let Err(err) = state.db.put_channel_open_job(&job) {
+        tracing::error!("Failed to persist channel open for quote {}: {}", id, err);
+    }
+}
+
+/// Schedule the next attempt of a failed open, giving up after [`MAX_CHANNEL_OPEN_ATTEMPTS`]
+///
+/// Quotes without a job, e.g. retried by the operator after giving up, are not retried.
+fn reschedule_channel_open(state: &CashuLspState, id: Uuid, error: String) {
+    let job = match state.db.get_channel_open_job(id) {
+        Ok(Some(job)) => job,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::error!("Failed to read channel open job of quote {}: {}", id, err);
+            return;
+        }
+    };
+
+    let job = failed_channel_open_job(job, state.clock.now(), error);
+
+    if job.attempts >= MAX_CHANNEL_OPEN_ATTEMPTS {
+        tracing::error!(
+            "Giving up opening channel for quote {} after {} attempts: {}",
+            id,
+            job.attempts,
+            job.last_error.as_deref().unwrap_or_default()
+        );
+        finish_channel_open_job(state, id);
+        return;
+    }
+
+    if let Err(err) = state.db.put_channel_open_job(&job) {
+        tracing::error!(
+            "Failed to reschedule channel open for quote {}: {}",
+            id,
+            err
+        );
+    }
+}
+
+/// Count a failed open at `now`, doubling the delay up to [`MAX_CHANNEL_OPEN_RETRY_BACKOFF`]
+fn failed_channel_open_job(job: ChannelOpenJob, now: u64, error: String) -> ChannelOpenJob {
+    let delay = CHANNEL_OPEN_RETRY_INTERVAL
+        .saturating_mul(2u32.saturating_pow(job.attempts))
+        .min(MAX_CHANNEL_OPEN_RETRY_BACKOFF);
+
+    ChannelOpenJob {
+        attempts: job.attempts.saturating_add(1),
+        next_attempt_at: now + delay.as_secs(),
+        last_error: Some(error),
+        ..job
+    }
+}
+
+fn finish_channel_open_job(state: &CashuLspState, id: Uuid) {
+    if let Err(err) = state.db.remove_channel_open_job(id) {
+        tracing::error!("Failed to remove channel open job of quote {}: {}", id, err);
+    }
+}
+
+/// Open the channels of paid quotes whose open was interrupted or failed
+///
+/// Jobs left by a previous run are resumed on startup, the rest once due.
+async fn run_channel_open_jobs(state: CashuLspState, interval: Duration) {
+    let cancel_token = state.node.events_cancel_token.clone();
+    let mut due_by = u64::MAX;
+
+    loop {
+        match state.db.list_channel_open_jobs() {
+            Ok(jobs) => {
+                for job in jobs.iter().filter(|job| job.next_attempt_at <= due_by) {
+                    if let Err(err) = run_channel_open_job(&state, job).await {
+                        tracing::warn!(
+                            "Could not resume channel open for quote {}: {}",
+                            job.quote_id,
+                            err
+                        );
+                    }
+                }
+            }
+            Err(err) => tracing::error!("Could not list channel open jobs: {}", err),
+        }
+
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+
+        due_by = state.clock.now();
+    }
+}
+
+async fn run_channel_open_job(state: &CashuLspState, job: &ChannelOpenJob) -> Result<(), LspError> {
+    let id = job.quote_id;
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    // Re-read under the lock, the paying request may have opened the channel
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    // Opened, refunded or expired since
+    if quote.state != QuoteState::Paid {
+        finish_channel_open_job(state, id);
+        return Ok(());
+    }
+
+    // Past the locktime the payer can reclaim the locked proofs
+    if quote
+        .htlc
+        .as_ref()
+        .is_some_and(|h| h.locktime <= state.clock.now())
+    {
+        finish_channel_open_job(state, id);
+        return Err(LspError::InvalidHtlc(format!(
+            "Lock of quote {} has expired",
+            id
+        )));
+    }
+
+    tracing::info!(
+        "Resuming channel open for quote {} after {} failed attempts",
+        id,
+        job.attempts
+    );
+
+    open_paid_channel(state, id).await
+}
+
+/// Retry opening the channel for a quote that was paid but whose open failed
+///
+/// Reconnects to the peer recorded in the quote before retrying and returns
+/// the user channel id of the new channel, which may still be pending.
+pub async fn retry_channel_open(
+    state: &CashuLspState,
+    id: Uuid,
+) -> Result<UserChannelId, LspError> {
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    // Re-read under the lock, a payment task may have opened the channel
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    if quote.state != QuoteState::Paid {
+        return Err(LspError::InvalidQuoteState {
+            id,
+            state: quote.state,
+        });
+    }
+
+    let refund = state
+        .db
+        .get_refund(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    // The payment went back to the client, e.g. after the state was overridden
+    if refund.is_some() {
+        return Err(LspError::AlreadyRefunded(id));
+    }
+
+    // Past the locktime the payer can reclaim the locked proofs
+    if quote
+        .htlc
+        .as_ref()
+        .is_some_and(|h| h.locktime <= state.clock.now())
+    {
+        return Err(LspError::InvalidHtlc(format!(
+            "Lock of quote {} has expired",
+            id
+        )));
+    }
+
+    check_peer_reachable(state, quote.node_pubkey, &quote.addr)?;
+
+    tracing::info!("Retrying channel open for quote {}", id);
+
+    open_paid_channel(state, id).await?;
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::error!("Failed to read quote {}: {}", id, e);
+        LspError::DatabaseError(e.to_string())
+    })?;
+
+    match (quote.state, quote.channel_id) {
+        (QuoteState::ChannelPending | QuoteState::ChannelOpen, Some(channel_id)) => Ok(channel_id),
+        _ => Err(LspError::ChannelOpenError(format!(
+            "Channel open for quote {} failed",
+            id
+        ))),
+    }
+}
+
+/// Return the payment for a quote whose channel will not be opened
+///
+/// Pays `amount_sats` to `invoice`, or as ecash from the mint the quote was
+/// paid at without one. A paid quote is expired first so its channel can't be
+/// opened after the refund, and the refund is recorded before it is paid out
+/// so a quote is never refunded twice.
+pub async fn refund_quote(
+    state: &CashuLspState,
+    id: Uuid,
+    amount_sats: u64,
+    invoice: Option<Bolt11Invoice>,
+) -> Result<QuoteRefund, LspError> {
+    if amount_sats == 0 {
+        return Err(LspError::InvalidRefund(
+            "Amount must be greater than zero".to_string(),
+        ));
+    }
+
+    let amount_msats = sats_to_msats(amount_sats)
+        .ok_or_else(|| LspError::InvalidRefund("Amount overflow".to_string()))?;
+
+    if let Some(invoice_msats) = invoice.as_ref().and_then(|i| i.amount_milli_satoshis()) {
+        if invoice_msats != amount_msats {
+            return Err(LspError::InvalidRefund(format!(
+                "Invoice is for {} msat, not {} sats",
+                invoice_msats, amount_sats
+            )));
+        }
+    }
+
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+    let locked = state
+        .db
+        .get_locked_payment(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    if locked.is_some() {
+        return Err(LspError::InvalidRefund(
+            "Locked proofs return to the payer once the lock expires".to_string(),
+        ));
+    }
+
+    let payment = state
+        .db
+        .get_quote_payment(id)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    let payment = match (&invoice, payment) {
+        (Some(_), _) => None,
+        (None, Some(payment)) => Some(payment),
+        (None, None) => {
+            return Err(LspError::InvalidRefund(format!(
+                "Quote {} was not paid with ecash, refund it to an invoice",
+                id
+            )));
+        }
+    };
+
+    // Re-read under the lock, a payment task may have opened the channel
+    let quote = state.db.get_quote(id).map_err(|e| {
+        tracing::warn!("Quote not found: {} - {}", id, e);
+        LspError::QuoteNotFound(id)
+    })?;
+
+    match quote.state {
+        QuoteState::Paid => {
+            transition_quote(
+                state,
+                id,
+                QuoteState::Paid,
+                QuoteState::ChannelExpired,
+                QuoteActor::Operator,
+            )?;
+        }
+        QuoteState::ChannelExpired => (),
+        _ => {
+            return Err(LspError::InvalidQuoteState {
+                id,
+                state: quote.state,
+            });
+        }
+    }
+
+    let mut refund = QuoteRefund {
+        quote_id: id,
+        amount_sats,
+        method: match invoice {
+            Some(_) => RefundMethod::Lightning,
+            None => RefundMethod::Ecash,
+        },
+        token: None,
+        invoice: invoice.as_ref().map(|i| i.to_string()),
+        created_at: state.clock.now(),
+        claimed_at: None,
+    };
+
+    let added = state
+        .db
+        .add_refund(&refund)
+        .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+    if !added {
+        return Err(LspError::AlreadyRefunded(id));
+    }
+
+    let paid_out = match (invoice, payment) {
+        (Some(invoice), _) => pay_refund_invoice(state, &invoice, amount_msats),
+        (None, Some(payment)) => send_refund_token(state, &payment, id, amount_sats)
+            .await
+            .map(|token| refund.token = Some(token)),
+        (None, None) => Err(LspError::InvalidRefund(
+            "No mint to refund from".to_string(),
+        )),
+    };
+
+    if let Err(err) = paid_out {
+        tracing::error!("Could not refund quote {}: {}", id, err);
+
+        state
+            .db
+            .remove_refund(id)
+            .map_err(|e| LspError::DatabaseError(e.to_string()))?;
+
+        return Err(err);
+    }
+
+    refund.claimed_at = Some(state.clock.now());
+
+    state.db.update_refund(&refund).map_err(|e| {
+        tracing::error!("Failed to record refund of quote {}: {}", id, e);
+        LspError::DatabaseError(e.to_string())
+    })?;
+
+    tracing::info!(
+        "Refunded {} sats for quote {} by {:?}",
+        amount_sats,
+        id,
+        refund.method
+    );
+
+    Ok(refund)
+}
+
+/// Start paying a refund to the client's invoice from the LSP's node
+fn pay_refund_invoice(
+    state: &CashuLspState,
+    invoice: &Bolt11Invoice,
+    amount_msats: u64,
+) -> Result<(), LspError> {
+    let bolt11 = state.node.inner.bolt11_payment();
+
+    match invoice.amount_milli_satoshis() {
+        Some(_) => bolt11.send(invoice, None),
+        None => bolt11.send_using_amount(invoice, amount_msats, None),
+    }
+    .map(|_| ())
+    .map_err(|e| LspError::InternalError(format!("Could not pay refund invoice: {}", e)))
+}
+
+/// Ecash token worth `amount_sats` from the mint and in the unit `payment` was made in
+async fn send_refund_token(
+    state: &CashuLspState,
+    payment: &QuotePayment,
+    id: Uuid,
+    amount_sats: u64,
+) -> Result<String, LspError> {
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(
+            payment.mint_url.clone(),
+            payment.unit.clone(),
+        ))
+        .await
+        .ok_or_else(|| {
+            LspError::WalletError(format!("Wallet not created for {}", payment.mint_url))
+        })?;
+
+    // At the rate the payment was accepted at, so the client gets back what it paid
+    let amount = sats_to_amount(amount_sats, &payment.unit, payment.sats_per_unit)?;
+
+    let token = wallet
+        .send(
+            amount,
+            Some(format!("Refund for quote {}", id)),
+            None,
+            &SplitTarget::default(),
+            &SendKind::default(),
+            true,
+        )
+        .await
+        .map_err(|e| LspError::WalletError(format!("Could not send refund token: {}", e)))?;
+
+    Ok(token.to_string())
+}
+
+/// Advance quotes as the node reports channel and payment events
+///
+/// Node events reach the journal through [`CashuLspNode::journal_node_events`].
+/// They are read back from the journal after the last one handled, so none
+/// are lost across restarts or when this task falls behind.
+async fn watch_node_events(state: CashuLspState) {
+    let cancel_token = state.node.events_cancel_token.clone();
+
+    // Persisted so events journaled but not yet handled before a restart, or
+    // skipped when falling behind the live channel, are read from the journal
+    let mut last_seq = loop {
+        match state.db.get_event_cursor(NODE_EVENTS_CURSOR) {
+            Ok(seq) => break seq,
+            Err(err) => tracing::error!("Could not read node event cursor: {}", err),
+        }
+
+        tokio::select! {
+            _ = cancel_token.cancelled() => return,
+            _ = tokio::time::sleep(NODE_EVENTS_RETRY_INTERVAL) => {}
+        }
+    };
+
+    loop {
+        // Replays the journal after `last_seq`, then follows live events
+        let mut events = match state.events.subscribe(last_seq) {
+            Ok(events) => Box::pin(events),
+            Err(err) => {
+                tracing::error!("Could not subscribe to node events: {}", err);
+
+                tokio::select! {
+                    _ = cancel_token.cancelled() => return,
+                    _ = tokio::time::sleep(NODE_EVENTS_RETRY_INTERVAL) => continue,
+                }
+            }
+        };
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel_token.cancelled() => return,
+                event = events.next() => event,
+            };
+
+            // The stream ends when this task falls behind, resume from the journal
+            let Some(event) = event else {
+                tracing::warn!("Fell behind node events, resuming after {}", last_seq);
+                break;
+            };
+
+            if let Err(err) = handle_node_event(&state, event.kind).await {
+                tracing::error!("Could not handle node event {}: {}", event.seq, err);
+            }
+
+            last_seq = event.seq;
+
+            if let Err(err) = state.db.set_event_cursor(NODE_EVENTS_CURSOR, last_seq) {
+                tracing::error!("Could not record node event cursor: {}", err);
+            }
+        }
+    }
+}
+
+/// Act on a journaled node event
+async fn handle_node_event(state: &CashuLspState, kind: LspEventKind) -> anyhow::Result<()> {
+    match kind {
+        LspEventKind::ChannelPending {
+            channel_id,
+            user_channel_id,
+            counterparty_node_id,
+            funding_txid,
+            funding_output_index,
+        } => {
+            channel_pending(
+                state,
+                &user_channel_id,
+                &counterparty_node_id,
+                QuoteChannel {
+                    channel_id,
+                    funding_txid,
+                    funding_output_index,
+                    short_channel_id: None,
+                    opened_at: state.clock.now(),
+                    ready_at: None,
+                    closed_at: None,
+                    close_reason: None,
+                },
+            )
+            .await
+        }
+        LspEventKind::ChannelReady {
+            user_channel_id,
+            counterparty_node_id,
+            ..
+        } => channel_ready(state, &user_channel_id, counterparty_node_id.as_deref()).await,
+        LspEventKind::ChannelClosed {
+            user_channel_id,
+            counterparty_node_id,
+            reason,
+            ..
+        } => {
+            channel_closed(
+                state,
+                &user_channel_id,
+                counterparty_node_id.as_deref(),
+                reason.as_deref(),
+            )
+            .await
+        }
+        LspEventKind::PaymentReceived { .. } if state.info().bolt12 => {
+            settle_bolt12_payments(&state).await
+        }
+        LspEventKind::PaymentForwarded {
+            prev_user_channel_id,
+            next_user_channel_id,
+            outbound_amount_forwarded_msat,
+            ..
+        } => {
+            payment_forwarded(
+                state,
+                prev_user_channel_id.as_deref(),
+                next_user_channel_id.as_deref(),
+                outbound_amount_forwarded_msat.unwrap_or_default(),
+            )
+            .await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Failed reconnection attempts to a peer
+struct ReconnectBackoff {
+    failures: u32,
+    /// Unix time of the next attempt
+    next_attempt: u64,
+}
+
+impl ReconnectBackoff {
+    fn is_due(&self, now: u64) -> bool {
+        self.next_attempt <= now
+    }
+
+    /// Count a failed attempt at `now`, returning the delay until the next one
+    fn failed(&mut self, now: u64) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+
+        let delay = PEER_RECONNECT_INTERVAL
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_PEER_RECONNECT_BACKOFF);
+        self.next_attempt = now + delay.as_secs();
+
+        delay
+    }
+}
+
+/// Keep peers of `ChannelPending` quotes connected until their channel is ready
+///
+/// Funding can only complete while the peer is connected. Peers that cannot
+/// be reached are retried with exponential backoff.
+async fn keep_pending_peers_connected(state: CashuLspState, interval: Duration) {
+    let cancel_token = state.node.events_cancel_token.clone();
+    let mut backoff = HashMap::new();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {
+                if let Err(err) = reconnect_pending_peers(&state, &mut backoff) {
+                    tracing::warn!("Could not reconnect pending peers: {}", err);
+                }
+            }
+        }
+    }
+}
+
+fn reconnect_pending_peers(
+    state: &CashuLspState,
+    backoff: &mut HashMap<PublicKey, ReconnectBackoff>,
+) -> anyhow::Result<()> {
+    let pending: HashMap<PublicKey, SocketAddress> = state
+        .db
+        .list_quotes()?
+        .into_iter()
+        .filter(|q| q.state == QuoteState::ChannelPending)
+        .map(|q| (q.node_pubkey, q.addr))
+        .collect();
+
+    backoff.retain(|node_pubkey, _| pending.contains_key(node_pubkey));
+
+    let connected: HashSet<PublicKey> = state
+        .node
+        .inner
+        .list_peers()
+        .into_iter()
+        .filter(|p| p.is_connected)
+        .map(|p| p.node_id)
+        .collect();
+
+    let now = state.clock.now();
+
+    for (node_pubkey, addr) in pending {
+        if connected.contains(&node_pubkey) {
+            backoff.remove(&node_pubkey);
+            continue;
+        }
+
+        if backoff.get(&node_pubkey).is_some_and(|b| !b.is_due(now)) {
+            continue;
+        }
+
+        match state.node.inner.connect(node_pubkey, addr, false) {
+            Ok(()) => {
+                tracing::info!("Reconnected to pending peer {}", node_pubkey);
+                backoff.remove(&node_pubkey);
+            }
+            Err(err) => {
+                let delay = backoff
+                    .entry(node_pubkey)
+                    .or_insert(ReconnectBackoff {
+                        failures: 0,
+                        next_attempt: now,
+                    })
+                    .failed(now);
+
+                tracing::warn!(
+                    "Could not reconnect to pending peer {}, retrying in {:?}: {}",
+                    node_pubkey,
+                    delay,
+                    err
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Bring quotes in line with the node's channels after a restart
+///
+/// Channel events missed while the LSP was down leave quotes `ChannelPending`
+/// or `ChannelOpen` for channels that became ready or closed since. Fixed
+/// quotes and discrepancies the operator has to look at are logged.
+async fn reconcile_quotes(state: &CashuLspState) -> anyhow::Result<()> {
+    let quotes = state.db.list_quotes()?;
+    let channels = state.node.inner.list_channels();
+
+    let mut claimed: HashSet<u128> = quotes
+        .iter()
+        .filter_map(|q| q.channel_id.map(|id| id.0))
+        .collect();
+
+    let mut reconciled = 0;
+
+    for quote in quotes.iter().filter(|q| {
+        matches!(
+            q.state,
+            QuoteState::ChannelPending | QuoteState::ChannelOpen
+        )
+    }) {
+        match reconcile_quote(state, quote, &channels, &mut claimed).await {
+            Ok(true) => reconciled += 1,
+            Ok(false) => {}
+            Err(err) => tracing::error!("Could not reconcile quote {}: {}", quote.id, err),
+        }
+    }
+
+    if reconciled > 0 {
+        tracing::info!("Reconciled {} quotes with the node's channels", reconciled);
+    }
+
+    Ok(())
+}
+
+/// Returns whether the quote was changed
+async fn reconcile_quote(
+    state: &CashuLspState,
+    quote: &QuoteInfo,
+    channels: &[ChannelDetails],
+    claimed: &mut HashSet<u128>,
+) -> anyhow::Result<bool> {
+    let node_pubkey = quote.node_pubkey.to_string();
+
+    let Some(channel_id) = quote.channel_id else {
+        if quote.state == QuoteState::ChannelOpen {
+            tracing::warn!("Quote {} is open but has no channel", quote.id);
+            return Ok(false);
+        }
+
+        return adopt_quote_channel(state, quote, channels, claimed).await;
+    };
+
+    let user_channel_id = channel_id.0.to_string();
+
+    match channels.iter().find(|c| c.user_channel_id == channel_id) {
+        Some(channel) if channel.is_channel_ready && quote.state == QuoteState::ChannelPending => {
+            tracing::warn!(
+                "Channel for quote {} became ready while the LSP was down",
+                quote.id
+            );
+            channel_ready(state, &user_channel_id, Some(&node_pubkey)).await?;
+            Ok(true)
+        }
+        Some(channel) if !channel.is_channel_ready && quote.state == QuoteState::ChannelOpen => {
+            tracing::warn!(
+                "Quote {} is open but its channel {} is not ready",
+                quote.id,
+                user_channel_id
+            );
+            Ok(false)
+        }
+        Some(_) => Ok(false),
+        None if close_recorded(state, quote)? => Ok(false),
+        // A close event the node reported but the LSP didn't handle is replayed
+        // from the journal later, and fills in the reason
+        None => {
+            tracing::warn!(
+                "Channel for quote {} closed while the LSP was down",
+                quote.id
+            );
+            channel_closed(
+                state,
+                &user_channel_id,
+                Some(&node_pubkey),
+                Some(CHANNEL_MISSING_REASON),
+            )
+            .await?;
+            Ok(true)
+        }
+    }
+}
+
+/// Whether the close of the quote's current channel is already recorded
+///
+/// Quotes from before channel details were stored only have the close in
+/// their history.
+fn close_recorded(state: &CashuLspState, quote: &QuoteInfo) -> anyhow::Result<bool> {
+    if quote
+        .channel
+        .as_ref()
+        .is_some_and(|c| c.closed_at.is_some())
+    {
+        return Ok(true);
+    }
+
+    let history = state.db.list_quote_history(quote.id, 0, None)?;
+
+    // A channel opened again goes through `ChannelPending`
+    Ok(history
+        .iter()
+        .rev()
+        .take_while(|t| t.state != QuoteState::ChannelPending)
+        .any(|t| {
+            t.reason
+                .as_deref()
+                .is_some_and(|r| r.starts_with(CHANNEL_CLOSED_REASON))
+        }))
+}
+
+/// Find the channel of a quote whose open was interrupted before its id was stored
+///
+/// An unclaimed channel to the quote's node of the quote's size is taken as
+/// its channel, without one the quote goes back to `Paid` so its channel open
+/// job opens it again.
+async fn adopt_quote_channel(
+    state: &CashuLspState,
+    quote: &QuoteInfo,
+    channels: &[ChannelDetails],
+    claimed: &mut HashSet<u128>,
+) -> anyhow::Result<bool> {
+    let channel = channels.iter().find(|c| {
+        c.counterparty_node_id == quote.node_pubkey
+            && c.channel_value_sats == quote.channel_size_sats
+            && !claimed.contains(&c.user_channel_id.0)
+    });
+
+    let Some(channel) = channel else {
+        tracing::warn!(
+            "Channel open for quote {} was interrupted, marking it paid again",
+            quote.id
+        );
+
+        let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+        transition_quote(
+            state,
+            quote.id,
+            QuoteState::ChannelPending,
+            QuoteState::Paid,
+            QuoteActor::Node,
+        )?;
+
+        return Ok(true);
+    };
+
+    tracing::warn!(
+        "Adopting unclaimed channel {} to {} for quote {}",
+        channel.user_channel_id.0,
+        quote.node_pubkey,
+        quote.id
+    );
+
+    claimed.insert(channel.user_channel_id.0);
+
+    {
+        let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+        state
+            .db
+            .set_quote_channel_id(quote.id, Some(channel.user_channel_id))?;
+    }
+
+    if channel.is_channel_ready {
+        channel_ready(
+            state,
+            &channel.user_channel_id.0.to_string(),
+            Some(&quote.node_pubkey.to_string()),
+        )
+        .await?;
+    }
+
+    Ok(true)
+}
+
+/// Quote that bought the channel with `user_channel_id`
+///
+/// Takes the counterparty's peer lock first so a channel open still being
+/// recorded is seen.
+async fn quote_for_channel(
+    state: &CashuLspState,
+    user_channel_id: &str,
+    counterparty_node_id: Option<&str>,
+) -> anyhow::Result<(Option<QuoteInfo>, Option<OwnedMutexGuard<()>>)> {
+    let user_channel_id = UserChannelId(user_channel_id.parse()?);
+
+    let node_pubkey = counterparty_node_id.map(PublicKey::from_str).transpose()?;
+
+    let guard = match node_pubkey {
+        Some(node_pubkey) => Some(state.peer_locks.lock(node_pubkey).await),
+        None => None,
+    };
+
+    let quotes = match node_pubkey {
+        Some(node_pubkey) => state.db.list_node_quotes(node_pubkey)?,
+        None => state.db.list_quotes()?,
+    };
+
+    let quote = quotes
+        .into_iter()
+        .find(|q| q.channel_id == Some(user_channel_id));
+
+    Ok((quote, guard))
+}
+
+/// Record the funding transaction of a quote's channel
+async fn channel_pending(
+    state: &CashuLspState,
+    user_channel_id: &str,
+    counterparty_node_id: &str,
+    channel: QuoteChannel,
+) -> anyhow::Result<()> {
+    let (quote, _peer_guard) =
+        quote_for_channel(state, user_channel_id, Some(counterparty_node_id)).await?;
+
+    let Some(quote) = quote else {
+        return Ok(());
+    };
+
+    tracing::info!(
+        "Channel for quote {} is funded by {}:{}",
+        quote.id,
+        channel.funding_txid,
+        channel.funding_output_index
+    );
+
+    state.db.set_quote_channel(quote.id, Some(channel))?;
+
+    Ok(())
+}
+
+async fn channel_ready(
+    state: &CashuLspState,
+    user_channel_id: &str,
+    counterparty_node_id: Option<&str>,
+) -> anyhow::Result<()> {
+    let (quote, _peer_guard) =
+        quote_for_channel(state, user_channel_id, counterparty_node_id).await?;
+
+    let Some(quote) = quote else {
+        return Ok(());
+    };
+
+    if let Some(mut channel) = quote.channel.clone() {
+        channel.ready_at = Some(state.clock.now());
+        channel.short_channel_id = state
+            .node
+            .inner
+            .list_channels()
+            .into_iter()
+            .find(|c| Some(c.user_channel_id) == quote.channel_id)
+            .and_then(|c| c.short_channel_id);
+
+        state.db.set_quote_channel(quote.id, Some(channel))?;
+    }
+
+    if quote.state != QuoteState::ChannelPending {
+        return Ok(());
+    }
+
+    transition_quote(
+        state,
+        quote.id,
+        QuoteState::ChannelPending,
+        QuoteState::ChannelOpen,
+        QuoteActor::Node,
+    )?;
+
+    tracing::info!("Channel for quote {} is ready", quote.id);
+
+    // The quote is already open, a missing ledger entry only skews reports
+    if let Err(err) = state.db.add_fee_entry(&fee_ledger_entry(state, &quote)) {
+        tracing::error!("Failed to record fee of quote {}: {}", quote.id, err);
+    }
+
+    Ok(())
+}
+
+/// Funding cost estimated for `quote`
+///
+/// Quotes created before the estimate was stored use the current fee rate.
+fn quote_onchain_fee_sats(state: &CashuLspState, quote: &QuoteInfo) -> u64 {
+    quote.onchain_fee_sats.unwrap_or_else(|| {
+        FUNDING_TX_VBYTES.saturating_mul(state.info().funding_fee_rate_sat_per_vb)
+    })
+}
+
+/// Fee earned on `quote`, with the funding cost estimated in its quote
+fn fee_ledger_entry(state: &CashuLspState, quote: &QuoteInfo) -> FeeLedgerEntry {
+    let service_fee_sats = quote.service_fee_sats();
+    let onchain_fee_sats = quote_onchain_fee_sats(state, quote);
+
+    FeeLedgerEntry {
+        quote_id: quote.id,
+        recorded_at: state.clock.now(),
+        service_fee_sats,
+        onchain_fee_sats,
+        mint_fee_sats: quote.mint_fee_sats,
+        bond_sats: 0,
+        revenue_sats: sats_to_i64(service_fee_sats)
+            .saturating_sub(sats_to_i64(onchain_fee_sats))
+            .saturating_sub(sats_to_i64(quote.mint_fee_sats)),
+    }
+}
+
+fn sats_to_i64(sats: u64) -> i64 {
+    i64::try_from(sats).unwrap_or(i64::MAX)
+}
+
+/// Record a closed channel in the history of the quote that bought it
+///
+/// A channel that closes before becoming ready never delivered what was paid
+/// for, so its quote goes back to `Paid` and the open can be retried.
+async fn channel_closed(
+    state: &CashuLspState,
+    user_channel_id: &str,
+    counterparty_node_id: Option<&str>,
+    reason: Option<&str>,
+) -> anyhow::Result<()> {
+    let (quote, _peer_guard) =
+        quote_for_channel(state, user_channel_id, counterparty_node_id).await?;
+
+    let Some(quote) = quote else {
+        return Ok(());
+    };
+
+    if let Some(mut channel) = quote.channel.clone() {
+        // Recorded at startup before the node's close event was handled, only
+        // the node knows why it closed
+        if channel.closed_at.is_some() {
+            if reason.is_some() && channel.close_reason.as_deref() == Some(CHANNEL_MISSING_REASON) {
+                channel.close_reason = reason.map(str::to_string);
+                state.db.set_quote_channel(quote.id, Some(channel))?;
+            }
+
+            return Ok(());
+        }
+
+        state.db.record_sla_close(quote.node_pubkey)?;
+
+        channel.closed_at = Some(state.clock.now());
+        channel.close_reason = reason.map(str::to_string);
+
+        state.db.set_quote_channel(quote.id, Some(channel))?;
+    }
+
+    let reason = format!(
+        "{}: {}",
+        CHANNEL_CLOSED_REASON,
+        reason.unwrap_or("unknown reason")
+    );
+
+    let new_state = match quote.state {
+        QuoteState::ChannelPending => QuoteState::Paid,
+        other => other,
+    };
+
+    state
+        .db
+        .override_quote_state(quote.id, new_state, &reason, QuoteActor::Node)?;
+
+    if new_state != quote.state {
+        state.publish_event(LspEventKind::QuoteStateChanged {
+            quote_id: quote.id,
+            state: new_state,
+        });
+    }
+
+    tracing::info!("Channel for quote {} closed", quote.id);
+
+    Ok(())
+}
+
+/// Count a forward through sold channels towards their peers' SLA
+async fn payment_forwarded(
+    state: &CashuLspState,
+    prev_user_channel_id: Option<&str>,
+    next_user_channel_id: Option<&str>,
+    amount_msat: u64,
+) -> anyhow::Result<()> {
+    let channels = state.node.inner.list_channels();
+
+    for user_channel_id in [prev_user_channel_id, next_user_channel_id]
+        .into_iter()
+        .flatten()
+    {
+        // Resolve the peer so only its quotes are read, not every quote
+        let Some(channel) = channels
+            .iter()
+            .find(|c| c.user_channel_id.0.to_string() == user_channel_id)
+        else {
+            continue;
+        };
+
+        let counterparty_node_id = channel.counterparty_node_id.to_string();
+
+        let (quote, _peer_guard) =
+            quote_for_channel(state, user_channel_id, Some(&counterparty_node_id)).await?;
+
+        if let Some(quote) = quote {
+            state
+                .db
+                .record_sla_forward(quote.node_pubkey, amount_msat)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Poll the node for BOLT12 payments to quote offers and open the paid for channels
+async fn watch_bolt12_payments(state: CashuLspState, interval: Duration) {
+    let cancel_token = state.node.events_cancel_token.clone();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {
+                if let Err(err) = settle_bolt12_payments(&state).await {
+                    tracing::warn!("Could not check BOLT12 payments: {}", err);
+                }
+            }
+        }
+    }
+}
+
+async fn settle_bolt12_payments(state: &CashuLspState) -> anyhow::Result<()> {
+    let unpaid: Vec<QuoteInfo> = state
+        .db
+        .list_quotes()?
+        .into_iter()
+        .filter(|q| q.state == QuoteState::Unpaid && q.bolt12_offer_id.is_some())
+        .collect();
+
+    if unpaid.is_empty() {
+        return Ok(());
+    }
+
+    let payments = state.node.inner.list_payments_with_filter(|p| {
+        p.direction == PaymentDirection::Inbound
+            && p.status == PaymentStatus::Succeeded
+            && matches!(p.kind, PaymentKind::Bolt12Offer { .. })
+    });
+
+    for quote in unpaid {
+        let received_msat: u64 = payments
+            .iter()
+            .filter(|p| match &p.kind {
+                PaymentKind::Bolt12Offer { offer_id, .. } => {
+                    Some(offer_id.0) == quote.bolt12_offer_id
+                }
+                _ => false,
+            })
+            .filter_map(|p| p.amount_msat)
+            .sum();
+
+        if msats_to_sats(received_msat) < quote.expected_payment_sats {
+            continue;
+        }
+
+        let _peer_guard = state.peer_locks.lock(quote.node_pubkey).await;
+
+        // An ecash payment may have settled the quote while we waited
+        if transition_quote(
+            state,
+            quote.id,
+            QuoteState::Unpaid,
+            QuoteState::Paid,
+            QuoteActor::Client,
+        )
+        .is_err()
+        {
+            continue;
+        }
+
+        schedule_channel_open(state, quote.id);
+
+        tracing::info!(
+            "Received BOLT12 payment of {} msat for quote {}",
+            received_msat,
+            quote.id
+        );
+
+        METRICS.payments_received.inc();
+
+        if let Err(err) = open_paid_channel(state, quote.id).await {
+            tracing::error!("Could not settle BOLT12 quote {}: {}", quote.id, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Channel config carrying the forwarding fees requested in the quote
+fn channel_config(quote: &QuoteInfo) -> Option<ChannelConfig> {
+    if quote.forwarding_fee_base_msat.is_none() && quote.forwarding_fee_ppm.is_none() {
+        return None;
+    }
+
+    let mut config = ChannelConfig::default();
+
+    if let Some(base_msat) = quote.forwarding_fee_base_msat {
+        config.forwarding_fee_base_msat = base_msat;
+    }
+
+    if let Some(ppm) = quote.forwarding_fee_ppm {
+        config.forwarding_fee_proportional_millionths = ppm;
+    }
+
+    Some(config)
+}
+
+/// Settle a sandbox quote against the fake balance of `key`
+///
+/// No proofs are redeemed and no channel is opened, the quote moves straight
+/// to `ChannelOpen` with a channel id derived from the quote id.
+fn settle_sandbox_payment(
+    state: &CashuLspState,
+    key: &str,
+    quote: QuoteInfo,
+    received_sats: u64,
+) -> Result<(), LspError> {
+    let sandbox = state.sandbox.as_ref().ok_or(LspError::InvalidApiKey)?;
+
+    sandbox.charge(key, received_sats)?;
+
+    let transitioned = transition_quote(
+        state,
+        quote.id,
+        QuoteState::Unpaid,
+        QuoteState::ChannelOpen,
+        QuoteActor::Lsp,
+    );
+
+    let quote = match transitioned {
+        Ok(quote) => quote,
+        Err(err) => {
+            // Another payment settled the quote first
+            sandbox.credit(key, received_sats);
+            return Err(err);
+        }
+    };
+
+    state
+        .db
+        .set_quote_channel_id(quote.id, Some(UserChannelId(quote.id.as_u128())))
+        .map_err(|e| {
+            tracing::error!("Failed to update sandbox quote: {}", e);
+            LspError::DatabaseError(e.to_string())
+        })?;
+
+    tracing::info!("Settled sandbox payment for quote {}", quote.id);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    pub node_running: bool,
+}
+
+/// Liveness probe, healthy while the LDK node is running
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "Node running", body = HealthResponse),
+        (status = 503, description = "Node stopped", body = HealthResponse)
+    )
+)]
+pub async fn get_health(State(state): State<CashuLspState>) -> Response {
+    let node_running = state.node.inner.status().is_running;
+
+    let status = if node_running {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(HealthResponse { node_running })).into_response()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MintReadiness {
+    #[schema(value_type = String)]
+    pub mint_url: MintUrl,
+    pub reachable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub node_running: bool,
+    pub chain_synced: bool,
+    pub database_ok: bool,
+    pub mints: Vec<MintReadiness>,
+}
+
+/// Readiness probe, ready once the node is synced and the database and mints are reachable
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Ready to serve", body = ReadinessResponse),
+        (status = 503, description = "Not ready", body = ReadinessResponse)
+    )
+)]
+pub async fn get_ready(State(state): State<CashuLspState>) -> Response {
+    let node_status = state.node.inner.status();
+
+    let chain_synced = node_status.latest_onchain_wallet_sync_timestamp.is_some()
+        && node_status.latest_lightning_wallet_sync_timestamp.is_some();
+
+    let database_ok = match state.db.check() {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::warn!("Database readiness check failed: {}", err);
+            false
+        }
+    };
+
+    let info = state.info();
+
+    let mints = futures::future::join_all(
+        info.accepted_mints
+            .iter()
+            .map(|mint_url| check_mint(&state, mint_url)),
+    )
+    .await;
+
+    let ready =
+        node_status.is_running && chain_synced && database_ok && mints.iter().all(|m| m.reachable);
+
+    let response = ReadinessResponse {
+        ready,
+        node_running: node_status.is_running,
+        chain_synced,
+        database_ok,
+        mints,
+    };
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(response)).into_response()
+}
+
+async fn check_mint(state: &CashuLspState, mint_url: &MintUrl) -> MintReadiness {
+    let unit = state
+        .info()
+        .accepted_units
+        .first()
+        .cloned()
+        .unwrap_or(CurrencyUnit::Sat);
+
+    let reachable = match state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(mint_url.clone(), unit))
+        .await
+    {
+        Some(wallet) => {
+            match tokio::time::timeout(MINT_CHECK_TIMEOUT, wallet.get_mint_info()).await {
+                Ok(Ok(_)) => true,
+                Ok(Err(err)) => {
+                    tracing::warn!("Mint {} readiness check failed: {}", mint_url, err);
+                    false
+                }
+                Err(_) => {
+                    tracing::warn!("Mint {} readiness check timed out", mint_url);
+                    false
+                }
+            }
+        }
+        None => false,
+    };
+
+    MintReadiness {
+        mint_url: mint_url.clone(),
+        reachable,
+    }
+}
+
+/// Router serving `/metrics`
+///
+/// Kept off the client facing router as the metrics include revenue and
+/// liquidity, serve it on a listener only operators can reach.
+pub fn create_metrics_router() -> Router {
+    Router::new().route("/metrics", get(get_metrics))
+}
+
+/// Prometheus metrics in the text exposition format
+pub async fn get_metrics() -> Result<String, LspError> {
+    METRICS.encode().map_err(|e| {
+        tracing::error!("Failed to encode metrics: {}", e);
+        LspError::InternalError(e.to_string())
+    })
+}
+
+/// OpenAPI document for the LSP HTTP API
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Cashu LSP"),
+    components(schemas(TokenPayment)),
+    paths(
+        get_lsp_info,
+        post_channel_quote,
+        get_quote_state,
+        post_renew_quote,
+        get_quote_proof,
+        post_htlc_preimage,
+        get_quote_receipt,
+        post_receive_payment,
+        get_health,
+        get_ready
+    )
+)]
+pub struct ApiDoc;
+
+pub async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Record the latency of every routed HTTP request
+async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    let response = next.run(request).await;
+
+    METRICS
+        .http_request_duration
+        .with_label_values(&[&method, &path, response.status().as_str()])
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn renewal_restarts_quote_at_current_time() {
+        let clock = ManualClock::new(1_000);
+
+        let mut quote = QuoteInfo::for_test(Uuid::from_u128(1), clock.now());
+        quote.state = QuoteState::ChannelExpired;
+        quote.htlc = Some(HtlcLock {
+            hash: "00".repeat(32),
+            locktime: 1_000 + 3_600,
+        });
+
+        clock.advance(86_400);
+
+        restart_quote(&mut quote, &clock, 3_600, 0);
+
+        assert_eq!(quote.state, QuoteState::Unpaid);
+        assert_eq!(quote.created_at, 87_400);
+        assert_eq!(quote.htlc.unwrap().locktime, 87_400 + 3_600);
+        assert!(quote.refund_lock.is_none());
+    }
+
+    #[test]
+    fn renewal_restarts_refund_lock() {
+        let clock = ManualClock::new(5_000);
+
+        let mut quote = QuoteInfo::for_test(Uuid::from_u128(1), 0);
+        quote.refund_lock = Some(RefundLock {
+            refund_pubkey: String::new(),
+            locktime: 100,
+        });
+
+        restart_quote(&mut quote, &clock, 0, 600);
+
+        assert_eq!(quote.created_at, 5_000);
+        assert_eq!(quote.refund_lock.unwrap().locktime, 5_600);
+    }
+
+    #[test]
+    fn channel_open_retries_back_off_to_the_cap() {
+        let clock = ManualClock::new(1_000);
+
+        let mut job = ChannelOpenJob {
+            quote_id: Uuid::from_u128(1),
+            attempts: 0,
+            next_attempt_at: clock.now(),
+            last_error: None,
+        };
+
+        for (attempts, delay) in [60, 120, 240, 480, 960, 1_920, 3_600, 3_600]
+            .into_iter()
+            .enumerate()
+        {
+            job = failed_channel_open_job(job, clock.now(), "timed out".to_string());
+
+            assert_eq!(job.attempts as usize, attempts + 1);
+            assert_eq!(job.next_attempt_at, clock.now() + delay);
+            assert_eq!(job.last_error.as_deref(), Some("timed out"));
+
+            clock.set(job.next_attempt_at);
+        }
+    }
+
+    #[test]
+    fn peer_reconnects_back_off_to_the_cap() {
+        let clock = ManualClock::new(1_000);
+
+        let mut backoff = ReconnectBackoff {
+            failures: 0,
+            next_attempt: clock.now(),
+        };
+        assert!(backoff.is_due(clock.now()));
+
+        for delay in [20, 40, 80, 160, 320, 600, 600] {
+            assert_eq!(backoff.failed(clock.now()), Duration::from_secs(delay));
+
+            clock.advance(delay - 1);
+            assert!(!backoff.is_due(clock.now()));
+
+            clock.advance(1);
+            assert!(backoff.is_due(clock.now()));
+        }
+    }
+
+    #[test]
+    fn amounts_round_trip_through_units() {
+        for sats in [0, 1, 1_000, 21_000_000 * 100_000_000] {
+            for unit in [CurrencyUnit::Sat, CurrencyUnit::Msat] {
+                let amount = sats_to_amount(sats, &unit, None).unwrap();
+                assert_eq!(amount_to_sats(amount, &unit, None).unwrap(), sats);
+            }
+        }
+
+        assert_eq!(
+            sats_to_amount(1_000, &CurrencyUnit::Msat, None).unwrap(),
+            Amount::from(1_000_000)
+        );
+    }
+
+    #[test]
+    fn sats_to_msat_amount_overflows() {
+        assert!(sats_to_amount(u64::MAX, &CurrencyUnit::Msat, None).is_err());
+        assert!(sats_to_amount(u64::MAX / 1_000, &CurrencyUnit::Msat, None).is_ok());
+    }
+
+    #[test]
+    fn fiat_amounts_round_against_the_lsp() {
+        // 2.5 sats per cent
+        let rate = Some(2.5);
+
+        // The client pays at least what is due
+        assert_eq!(
+            sats_to_amount(11, &CurrencyUnit::Usd, rate).unwrap(),
+            Amount::from(5)
+        );
+        // The LSP credits no more than it received
+        assert_eq!(
+            amount_to_sats(Amount::from(5), &CurrencyUnit::Usd, rate).unwrap(),
+            12
+        );
+        assert_eq!(
+            amount_to_sats(Amount::from(3), &CurrencyUnit::Usd, rate).unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn units_without_rate_are_unsupported() {
+        assert!(matches!(
+            sats_to_amount(1, &CurrencyUnit::Usd, None),
+            Err(LspError::UnsupportedUnit(CurrencyUnit::Usd))
+        ));
+        assert!(matches!(
+            amount_to_sats(Amount::from(1), &CurrencyUnit::Usd, None),
+            Err(LspError::UnsupportedUnit(CurrencyUnit::Usd))
+        ));
+    }
+
+    #[test]
+    fn channel_fee_overflow_is_an_error() {
+        assert_eq!(calculate_fee(100_000, 1_000, 20), Some(2_000));
+        assert_eq!(calculate_fee(10_000, 1_000, 20), Some(1_000));
+        assert_eq!(calculate_fee(u64::MAX, 0, 2_000), None);
+
+        let info = CashuLspInfo {
+            min_channel_size_sat: 0,
+            max_channel_size_sat: u64::MAX,
+            accepted_mints: vec![],
+            accepted_units: vec![CurrencyUnit::Sat],
+            min_fee: 0,
+            fee_ppk: u64::MAX,
+            mint_fee_policies: vec![],
+            nostr: None,
+            htlc: None,
+            refund: None,
+            forwarding_fee_bounds: None,
+            funding_fee_rate_sat_per_vb: 1,
+            bolt12: false,
+            max_channels_per_peer: None,
+            max_capacity_per_peer_sat: None,
+            inbound_only: false,
+            anti_spam: None,
+        };
+
+        assert!(matches!(
+            info.quote_fee(1_000_000, &[]),
+            Err(LspError::InternalError(_))
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use cdk::mint_url::MintUrl;
+use cdk::nuts::CurrencyUnit;
+use cdk::types::Melted;
+use cdk::wallet::Wallet;
+use cdk::wallet::types::{MeltQuote, WalletKey};
+use ldk_node::lightning_invoice::Bolt11Invoice;
+
+use crate::CashuLspNode;
+use crate::db::LspStore;
+use crate::types::{QuoteState, sats_to_msats};
+
+const MELT_INVOICE_EXPIRY_SECS: u32 = 600;
+const MELT_QUOTE_ATTEMPTS: usize = 2;
+
+/// Limit on the ecash held at a single mint
+///
+/// Whenever the sat balance at `mint_url` exceeds `threshold_sats` the excess
+/// over `target_sats` is melted to the LSP's own node. Ecash backing quotes
+/// that may still be refunded is left out of the balance, and the mint's fee
+/// reserve is paid out of the excess.
+#[derive(Debug, Clone)]
+pub struct MeltPolicy {
+    pub mint_url: MintUrl,
+    pub threshold_sats: u64,
+    pub target_sats: u64,
+    /// How often the balance is checked
+    pub interval: Duration,
+}
+
+/// Melt `amount_sats` of ecash from `wallet` by paying an invoice of the LSP's node
+pub async fn melt_to_node(
+    node: &CashuLspNode,
+    wallet: &Wallet,
+    amount_sats: u64,
+) -> anyhow::Result<(Bolt11Invoice, Melted)> {
+    let (invoice, quote) = node_melt_quote(node, wallet, amount_sats).await?;
+
+    let melted = melt_quote(wallet, &quote).await?;
+
+    Ok((invoice, melted))
+}
+
+/// Invoice of the LSP's node for `amount_sats` and the mint's quote to pay it
+async fn node_melt_quote(
+    node: &CashuLspNode,
+    wallet: &Wallet,
+    amount_sats: u64,
+) -> anyhow::Result<(Bolt11Invoice, MeltQuote)> {
+    let amount_msats = sats_to_msats(amount_sats).ok_or(anyhow!("Amount overflow"))?;
+
+    let invoice = node.inner.bolt11_payment().receive(
+        amount_msats,
+        &format!("Melt from {}", wallet.mint_url),
+        MELT_INVOICE_EXPIRY_SECS,
+    )?;
+
+    let quote = wallet
+        .melt_quote(invoice.to_string(), None)
+        .await
+        .map_err(|e| anyhow!("Could not get melt quote: {}", e))?;
+
+    Ok((invoice, quote))
+}
+
+async fn melt_quote(wallet: &Wallet, quote: &MeltQuote) -> anyhow::Result<Melted> {
+    tracing::info!(
+        "Melting {} sats from {} with {} sats fee reserve",
+        quote.amount,
+        wallet.mint_url,
+        quote.fee_reserve
+    );
+
+    wallet
+        .melt(&quote.id)
+        .await
+        .map_err(|e| anyhow!("Melt failed: {}", e))
+}
+
+/// Enforce `policy` until the node is stopped
+pub async fn enforce_melt_policy(
+    node: Arc<CashuLspNode>,
+    db: Arc<dyn LspStore>,
+    policy: MeltPolicy,
+) {
+    // Fee reserve of the mint's last melt quote, set aside before quoting the next
+    let mut fee_reserve_sats = 0;
+
+    loop {
+        tokio::select! {
+            _ = node.events_cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(policy.interval) => {
+                let applied =
+                    apply_melt_policy(&node, db.as_ref(), &policy, &mut fee_reserve_sats).await;
+
+                if let Err(err) = applied {
+                    tracing::warn!("Could not apply melt policy for {}: {}", policy.mint_url, err);
+                }
+            }
+        }
+    }
+}
+
+async fn apply_melt_policy(
+    node: &CashuLspNode,
+    db: &dyn LspStore,
+    policy: &MeltPolicy,
+    fee_reserve_sats: &mut u64,
+) -> anyhow::Result<()> {
+    let wallet = node
+        .wallet
+        .get_wallet(&WalletKey::new(policy.mint_url.clone(), CurrencyUnit::Sat))
+        .await
+        .ok_or(anyhow!("No sat wallet for {}", policy.mint_url))?;
+
+    let held = held_sats(db, &policy.mint_url)?;
+    let balance = u64::from(wallet.total_balance().await?).saturating_sub(held);
+
+    if balance <= policy.threshold_sats {
+        return Ok(());
+    }
+
+    let excess_sats = balance.saturating_sub(policy.target_sats);
+
+    // A lower amount has no higher fee reserve, so a second quote fits
+    for _ in 0..MELT_QUOTE_ATTEMPTS {
+        let amount_sats = excess_sats.saturating_sub(*fee_reserve_sats);
+
+        // Checked before creating an invoice so none is left unpaid
+        if amount_sats == 0 {
+            tracing::info!(
+                "Not melting from {}, {} sats over the target don't cover the {} sats fee reserve",
+                policy.mint_url,
+                excess_sats,
+                fee_reserve_sats
+            );
+            return Ok(());
+        }
+
+        let (_, quote) = node_melt_quote(node, &wallet, amount_sats).await?;
+
+        let fits = amount_sats.saturating_add(u64::from(quote.fee_reserve)) <= excess_sats;
+        *fee_reserve_sats = u64::from(quote.fee_reserve);
+
+        if !fits {
+            // The invoice for this quote expires unpaid
+            tracing::debug!(
+                "Fee reserve at {} rose to {} sats, quoting a lower amount",
+                policy.mint_url,
+                fee_reserve_sats
+            );
+            continue;
+        }
+
+        tracing::info!(
+            "Balance of {} sats at {} exceeds {} sats with {} sats held, melting {} sats",
+            balance,
+            policy.mint_url,
+            policy.threshold_sats,
+            held,
+            amount_sats
+        );
+
+        let melted = melt_quote(&wallet, &quote).await?;
+
+        tracing::info!(
+            "Melted {} sats from {} ({:?}), paid {} sats in fees",
+            melted.amount,
+            policy.mint_url,
+            melted.state,
+            melted.fee_paid
+        );
+
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Fee reserve at {} exceeds the last quote's",
+        policy.mint_url
+    ))
+}
+
+/// Sat ecash at `mint_url` that must stay put
+///
+/// Payments for quotes whose channel isn't open yet may still be refunded,
+/// and refunds not yet paid out are owed to clients.
+fn held_sats(db: &dyn LspStore, mint_url: &MintUrl) -> anyhow::Result<u64> {
+    let at_mint = |quote_id| -> anyhow::Result<u64> {
+        Ok(db
+            .get_quote_payment(quote_id)?
+            .filter(|p| &p.mint_url == mint_url && p.unit == CurrencyUnit::Sat)
+            .map(|p| p.proofs.iter().map(|proof| proof.amount).sum())
+            .unwrap_or_default())
+    };
+
+    let mut held: u64 = 0;
+
+    for quote in db
+        .list_quotes()?
+        .into_iter()
+        .filter(|q| matches!(q.state, QuoteState::Paid | QuoteState::ChannelPending))
+    {
+        held = held.saturating_add(at_mint(quote.id)?);
+    }
+
+    for refund in db
+        .list_refunds()?
+        .into_iter()
+        .filter(|r| r.claimed_at.is_none())
+    {
+        if at_mint(refund.quote_id)? > 0 {
+            held = held.saturating_add(refund.amount_sats);
+        }
+    }
+
+    Ok(held)
+}
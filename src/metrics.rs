@@ -0,0 +1,69 @@
+use std::sync::LazyLock;
+
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, Registry, TextEncoder};
+
+/// Metrics exposed on `/metrics`
+pub static METRICS: LazyLock<LspMetrics> =
+    LazyLock::new(|| LspMetrics::new().expect("Valid metric definitions"));
+
+pub struct LspMetrics {
+    registry: Registry,
+    pub quotes_created: IntCounter,
+    pub payments_received: IntCounter,
+    pub proofs_redeemed: IntCounter,
+    pub channels_opened: IntCounter,
+    pub channel_open_failures: IntCounter,
+    pub fee_revenue_sats: IntCounter,
+    pub http_request_duration: HistogramVec,
+}
+
+impl LspMetrics {
+    fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("cashu_lsp".to_string()), None)?;
+
+        let quotes_created = IntCounter::new("quotes_created_total", "Channel quotes created")?;
+        let payments_received =
+            IntCounter::new("payments_received_total", "Payments received for quotes")?;
+        let proofs_redeemed =
+            IntCounter::new("proofs_redeemed_total", "Cashu proofs redeemed with mints")?;
+        let channels_opened = IntCounter::new("channels_opened_total", "Channels opened")?;
+        let channel_open_failures =
+            IntCounter::new("channel_open_failures_total", "Failed channel opens")?;
+        let fee_revenue_sats =
+            IntCounter::new("fee_revenue_sats_total", "Service fees earned in sats")?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latencies in seconds",
+            ),
+            &["method", "path", "status"],
+        )?;
+
+        registry.register(Box::new(quotes_created.clone()))?;
+        registry.register(Box::new(payments_received.clone()))?;
+        registry.register(Box::new(proofs_redeemed.clone()))?;
+        registry.register(Box::new(channels_opened.clone()))?;
+        registry.register(Box::new(channel_open_failures.clone()))?;
+        registry.register(Box::new(fee_revenue_sats.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            quotes_created,
+            payments_received,
+            proofs_redeemed,
+            channels_opened,
+            channel_open_failures,
+            fee_revenue_sats,
+            http_request_duration,
+        })
+    }
+
+    /// Encode all metrics in the prometheus text format
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
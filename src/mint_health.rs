@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+use cdk::mint_url::MintUrl;
+use cdk::nuts::{CurrencyUnit, Id, KeySetInfo};
+use cdk::wallet::types::WalletKey;
+
+use crate::lsp_server::CashuLspState;
+
+/// How often accepted mints are checked
+pub const MINT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const MINT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+struct MintHealthInner {
+    /// Mints taken out of `accepted_mints` and why
+    disabled: HashMap<MintUrl, String>,
+    /// Every keyset seen from each mint
+    keysets: HashMap<MintUrl, HashSet<Id>>,
+    /// Input fee of the active keyset of each mint per unit
+    input_fee_ppk: HashMap<MintUrl, HashMap<CurrencyUnit, u64>>,
+}
+
+/// Mints disabled because they failed a health check
+///
+/// A disabled mint is removed from the advertised `accepted_mints` so quotes
+/// and payments using it are rejected, and added back once it recovers.
+#[derive(Debug, Clone, Default)]
+pub struct MintHealth {
+    inner: Arc<RwLock<MintHealthInner>>,
+}
+
+impl MintHealth {
+    /// Disabled mints with the reason they failed
+    pub fn disabled(&self) -> HashMap<MintUrl, String> {
+        self.read().disabled.clone()
+    }
+
+    pub fn is_disabled(&self, mint_url: &MintUrl) -> bool {
+        self.read().disabled.contains_key(mint_url)
+    }
+
+    /// Stop tracking `mint_url`, returns whether it was disabled
+    ///
+    /// Called when the operator changes whether the mint is accepted.
+    pub fn forget(&self, mint_url: &MintUrl) -> bool {
+        let mut inner = self.write();
+        inner.keysets.remove(mint_url);
+        inner.input_fee_ppk.remove(mint_url);
+        inner.disabled.remove(mint_url).is_some()
+    }
+
+    /// Input fee in parts per thousand per proof swapped at `mint_url`
+    ///
+    /// `None` until the keysets of the mint have been fetched.
+    pub fn input_fee_ppk(&self, mint_url: &MintUrl, unit: &CurrencyUnit) -> Option<u64> {
+        let inner = self.read();
+        let fees = inner.input_fee_ppk.get(mint_url)?;
+
+        // A mint without an active keyset for the unit cannot be paid in it
+        Some(fees.get(unit).copied().unwrap_or_default())
+    }
+
+    /// Remember the input fees of the active `keysets` of `mint_url`
+    pub(crate) fn record_fees(&self, mint_url: &MintUrl, keysets: &[KeySetInfo]) {
+        let mut fees: HashMap<CurrencyUnit, u64> = HashMap::new();
+
+        for keyset in keysets.iter().filter(|k| k.active) {
+            let fee = fees.entry(keyset.unit.clone()).or_default();
+            *fee = (*fee).max(keyset.input_fee_ppk);
+        }
+
+        self.write().input_fee_ppk.insert(mint_url.clone(), fees);
+    }
+
+    /// Returns whether the mint was enabled before
+    fn disable(&self, mint_url: &MintUrl, reason: String) -> bool {
+        self.write()
+            .disabled
+            .insert(mint_url.clone(), reason)
+            .is_none()
+    }
+
+    /// Returns whether the mint was disabled before
+    fn enable(&self, mint_url: &MintUrl) -> bool {
+        self.write().disabled.remove(mint_url).is_some()
+    }
+
+    /// Check `keysets` against the ones seen before and remember new ones
+    ///
+    /// Mints keep rotated out keysets listed as inactive, a keyset that
+    /// disappears means proofs we hold from it may no longer be redeemable.
+    /// The missing keyset stays known, so the mint stays disabled until the
+    /// operator has checked it and adds it again with AddMint.
+    fn check_keysets(&self, mint_url: &MintUrl, keysets: HashSet<Id>) -> Result<(), String> {
+        let mut inner = self.write();
+        let known = inner.keysets.entry(mint_url.clone()).or_default();
+
+        if let Some(missing) = known.iter().find(|id| !keysets.contains(id)) {
+            return Err(format!(
+                "Keyset {} is no longer offered, re-add the mint once checked",
+                missing
+            ));
+        }
+
+        known.extend(keysets);
+
+        Ok(())
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, MintHealthInner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, MintHealthInner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Periodically check the info and keysets of every accepted mint
+pub(crate) async fn monitor_mint_health(state: CashuLspState, interval: Duration) {
+    let cancel_token = state.node.events_cancel_token.clone();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(interval) => check_mints(&state).await,
+        }
+    }
+}
+
+async fn check_mints(state: &CashuLspState) {
+    let info = state.info();
+
+    let unit = info
+        .accepted_units
+        .first()
+        .cloned()
+        .unwrap_or(CurrencyUnit::Sat);
+
+    let mut mints = info.accepted_mints.clone();
+    mints.extend(state.mint_health.disabled().into_keys());
+
+    for mint_url in mints {
+        match check_mint(state, &mint_url, &unit).await {
+            Ok(()) => {
+                if state.mint_health.enable(&mint_url) {
+                    tracing::info!("Mint {} recovered, accepting it again", mint_url);
+
+                    set_advertised(state, &mint_url, true);
+                }
+            }
+            Err(reason) => {
+                if state.mint_health.disable(&mint_url, reason.clone()) {
+                    tracing::warn!("Disabling mint {}: {}", mint_url, reason);
+
+                    set_advertised(state, &mint_url, false);
+                }
+            }
+        }
+    }
+}
+
+async fn check_mint(
+    state: &CashuLspState,
+    mint_url: &MintUrl,
+    unit: &CurrencyUnit,
+) -> Result<(), String> {
+    let wallet = state
+        .node
+        .wallet
+        .get_wallet(&WalletKey::new(mint_url.clone(), unit.clone()))
+        .await
+        .ok_or_else(|| "No wallet for mint".to_string())?;
+
+    tokio::time::timeout(MINT_HEALTH_CHECK_TIMEOUT, wallet.get_mint_info())
+        .await
+        .map_err(|_| "Mint info request timed out".to_string())?
+        .map_err(|e| format!("Could not get mint info: {}", e))?;
+
+    let keysets = tokio::time::timeout(MINT_HEALTH_CHECK_TIMEOUT, wallet.get_mint_keysets())
+        .await
+        .map_err(|_| "Keysets request timed out".to_string())?
+        .map_err(|e| format!("Could not get keysets: {}", e))?;
+
+    state.mint_health.record_fees(mint_url, &keysets);
+
+    state
+        .mint_health
+        .check_keysets(mint_url, keysets.into_iter().map(|k| k.id).collect())
+}
+
+fn set_advertised(state: &CashuLspState, mint_url: &MintUrl, advertised: bool) {
+    let result = state.update_info(|info| {
+        if advertised {
+            if !info.accepted_mints.contains(mint_url) {
+                info.accepted_mints.push(mint_url.clone());
+            }
+        } else {
+            info.accepted_mints.retain(|m| m != mint_url);
+        }
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        tracing::error!("Could not update accepted mints: {}", err);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use cdk::nuts::{PaymentRequestPayload, Transport, TransportType};
+use nostr_sdk::nips::nip19::{Nip19Profile, ToBech32};
+use nostr_sdk::nips::nip59::UnwrappedGift;
+use nostr_sdk::{Client, Filter, Keys, Kind, RelayPoolNotification};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::ToSchema;
+
+use crate::lsp_server::{CashuLspState, accept_payment};
+
+/// Nostr identity and relays payments can be delivered on
+#[derive(Debug, Clone)]
+pub struct NostrTransport {
+    pub keys: Keys,
+    pub relays: Vec<String>,
+}
+
+/// Nostr transport details advertised in `/info`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NostrInfo {
+    pub pubkey: String,
+    pub relays: Vec<String>,
+}
+
+impl NostrTransport {
+    pub fn new(secret_key: &str, relays: Vec<String>) -> Result<Self> {
+        Ok(Self {
+            keys: Keys::parse(secret_key)?,
+            relays,
+        })
+    }
+
+    pub fn info(&self) -> NostrInfo {
+        NostrInfo {
+            pubkey: self.keys.public_key().to_string(),
+            relays: self.relays.clone(),
+        }
+    }
+
+    /// NUT-18 transport asking for payment as a NIP-17 direct message
+    pub fn transport(&self) -> Result<Transport> {
+        let nprofile = Nip19Profile::new(self.keys.public_key(), self.relays.clone())?;
+
+        let transport = Transport::builder()
+            .transport_type(TransportType::Nostr)
+            .target(nprofile.to_bech32()?)
+            .tags(vec![vec!["n".to_string(), "17".to_string()]])
+            .build()?;
+
+        Ok(transport)
+    }
+}
+
+/// Listen for [`PaymentRequestPayload`]s sent as NIP-17 direct messages
+pub async fn listen_for_payments(transport: NostrTransport, state: CashuLspState) -> Result<()> {
+    let client = Client::new(transport.keys.clone());
+
+    for relay in transport.relays.iter() {
+        client.add_relay(relay).await?;
+    }
+
+    client.connect().await;
+
+    let filter = Filter::new()
+        .kind(Kind::GiftWrap)
+        .pubkey(transport.keys.public_key());
+
+    client.subscribe(vec![filter], None).await?;
+
+    tracing::info!(
+        "Listening for nostr payments on {} relays",
+        transport.relays.len()
+    );
+
+    let mut notifications = client.notifications();
+    let cancel_token = state.node.events_cancel_token.clone();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            notification = notifications.recv() => {
+                let event = match notification {
+                    Ok(RelayPoolNotification::Event { event, .. }) => event,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Nostr listener skipped {} notifications", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if event.kind != Kind::GiftWrap {
+                    continue;
+                }
+
+                let UnwrappedGift { rumor, sender } = match client.unwrap_gift_wrap(&event).await {
+                    Ok(gift) => gift,
+                    Err(err) => {
+                        tracing::warn!("Could not unwrap nostr message {}: {}", event.id, err);
+                        continue;
+                    }
+                };
+
+                let payload: PaymentRequestPayload = match serde_json::from_str(&rumor.content) {
+                    Ok(payload) => payload,
+                    Err(err) => {
+                        tracing::warn!("Invalid payment payload from {}: {}", sender, err);
+                        continue;
+                    }
+                };
+
+                tracing::debug!("Received nostr payment from {}", sender);
+
+                // Redeeming waits on the mint, don't hold up other notifications
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = accept_payment(&state, payload).await {
+                        tracing::warn!("Could not process nostr payment from {}: {}", sender, err);
+                    }
+                });
+            }
+        }
+    }
+
+    client.disconnect().await?;
+
+    Ok(())
+}
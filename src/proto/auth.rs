@@ -0,0 +1,95 @@
+use std::sync::{Arc, RwLock};
+
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Checks the bearer token of every management request
+///
+/// Tokens are shared between clones so they can be rotated while the server
+/// runs. Authentication is disabled while no tokens are set.
+#[derive(Clone, Default)]
+pub struct TokenAuth {
+    tokens: Arc<RwLock<Vec<String>>>,
+}
+
+impl TokenAuth {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self {
+            tokens: Arc::new(RwLock::new(tokens)),
+        }
+    }
+
+    /// Replace the accepted tokens, in flight requests are unaffected
+    pub fn set_tokens(&self, tokens: Vec<String>) {
+        match self.tokens.write() {
+            Ok(mut current) => *current = tokens,
+            Err(_) => tracing::error!("gRPC token lock poisoned, tokens not updated"),
+        }
+    }
+}
+
+impl Interceptor for TokenAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let tokens = self
+            .tokens
+            .read()
+            .map_err(|_| Status::internal("Token lock poisoned"))?;
+
+        if tokens.is_empty() {
+            return Ok(request);
+        }
+
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token"))?;
+
+        if !tokens
+            .iter()
+            .any(|token| constant_time_eq(token, presented))
+        {
+            return Err(Status::unauthenticated("Invalid token"));
+        }
+
+        Ok(request)
+    }
+}
+
+/// Adds a bearer token to every request made by [`super::client::CdkLdkClient`]
+#[derive(Clone, Default)]
+pub struct ClientToken {
+    header: Option<MetadataValue<Ascii>>,
+}
+
+impl ClientToken {
+    pub fn new(token: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            header: Some(format!("{}{}", BEARER_PREFIX, token).parse()?),
+        })
+    }
+}
+
+impl Interceptor for ClientToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(header) = &self.header {
+            request
+                .metadata_mut()
+                .insert("authorization", header.clone());
+        }
+
+        Ok(request)
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
@@ -1,22 +1,29 @@
-use tonic::transport::Channel;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Channel, Endpoint};
 
+use super::auth::ClientToken;
 use super::cdk_ldk_management_client::CdkLdkManagementClient;
 use super::*;
 
 pub struct CdkLdkClient {
-    client: CdkLdkManagementClient<Channel>,
+    client: CdkLdkManagementClient<InterceptedService<Channel, ClientToken>>,
 }
 
 impl CdkLdkClient {
     pub fn new(channel: Channel) -> Self {
+        Self::with_token(channel, ClientToken::default())
+    }
+
+    /// Client authenticating every request with `token`
+    pub fn with_token(channel: Channel, token: ClientToken) -> Self {
         Self {
-            client: CdkLdkManagementClient::new(channel),
+            client: CdkLdkManagementClient::with_interceptor(channel, token),
         }
     }
 
     pub async fn connect(addr: String) -> anyhow::Result<Self> {
-        let client = CdkLdkManagementClient::connect(addr).await?;
-        Ok(Self { client })
+        let channel = Endpoint::from_shared(addr)?.connect().await?;
+        Ok(Self::new(channel))
     }
 
     pub async fn get_info(&mut self) -> anyhow::Result<GetInfoResponse> {
@@ -35,16 +42,18 @@ impl CdkLdkClient {
         &mut self,
         node_id: String,
         address: String,
-        port: u32,
-        amount_msats: u64,
+        port: u16,
+        amount_sats: u64,
         push_to_counter_party_msats: Option<u64>,
+        options: ChannelOptions,
     ) -> anyhow::Result<String> {
         let request = OpenChannelRequest {
             node_id,
             address,
-            port,
-            amount_msats,
+            port: port.into(),
+            amount_sats,
             push_to_counter_party_msats,
+            options: Some(options),
         };
         let response = self.client.open_channel(request).await?;
         Ok(response.into_inner().channel_id)
@@ -54,13 +63,36 @@ impl CdkLdkClient {
         &mut self,
         channel_id: String,
         node_pubkey: String,
-    ) -> anyhow::Result<()> {
+        force: bool,
+        reason: Option<String>,
+    ) -> anyhow::Result<CloseChannelResponse> {
         let request = CloseChannelRequest {
             channel_id,
             node_pubkey,
+            force,
+            reason,
         };
-        self.client.close_channel(request).await?;
-        Ok(())
+        let response = self.client.close_channel(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn update_channel_config(
+        &mut self,
+        channel_id: String,
+        node_pubkey: String,
+        forwarding_fee_base_msat: Option<u32>,
+        forwarding_fee_ppm: Option<u32>,
+        cltv_expiry_delta: Option<u32>,
+    ) -> anyhow::Result<UpdateChannelConfigResponse> {
+        let request = UpdateChannelConfigRequest {
+            channel_id,
+            node_pubkey,
+            forwarding_fee_base_msat,
+            forwarding_fee_ppm,
+            cltv_expiry_delta,
+        };
+        let response = self.client.update_channel_config(request).await?;
+        Ok(response.into_inner())
     }
 
     pub async fn list_balance(&mut self) -> anyhow::Result<ListBalanceResponse> {
@@ -69,16 +101,438 @@ impl CdkLdkClient {
         Ok(response.into_inner())
     }
 
+    pub async fn list_cashu_balances(&mut self) -> anyhow::Result<Vec<CashuBalance>> {
+        let request = ListCashuBalancesRequest {};
+        let response = self.client.list_cashu_balances(request).await?;
+        Ok(response.into_inner().balances)
+    }
+
+    pub async fn restore_wallets(&mut self) -> anyhow::Result<Vec<RestoredBalance>> {
+        let request = RestoreWalletsRequest {};
+        let response = self.client.restore_wallets(request).await?;
+        Ok(response.into_inner().balances)
+    }
+
+    pub async fn export_proofs(
+        &mut self,
+        mint_url: String,
+        unit: Option<String>,
+        amount: u64,
+        p2pk_pubkey: Option<String>,
+        memo: Option<String>,
+    ) -> anyhow::Result<String> {
+        let request = ExportProofsRequest {
+            mint_url,
+            unit,
+            amount,
+            p2pk_pubkey,
+            memo,
+        };
+        let response = self.client.export_proofs(request).await?;
+        Ok(response.into_inner().token)
+    }
+
+    pub async fn melt_to_lightning(
+        &mut self,
+        mint_url: String,
+        amount_sats: u64,
+    ) -> anyhow::Result<MeltToLightningResponse> {
+        let request = MeltToLightningRequest {
+            mint_url,
+            amount_sats,
+        };
+        let response = self.client.melt_to_lightning(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_channels(&mut self) -> anyhow::Result<Vec<ChannelInfo>> {
+        let request = ListChannelsRequest {};
+        let response = self.client.list_channels(request).await?;
+        Ok(response.into_inner().channels)
+    }
+
+    pub async fn list_payments(
+        &mut self,
+        offset: u32,
+        limit: Option<u32>,
+    ) -> anyhow::Result<ListPaymentsResponse> {
+        let request = ListPaymentsRequest { offset, limit };
+        let response = self.client.list_payments(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_onchain_transactions(&mut self) -> anyhow::Result<Vec<OnchainTransaction>> {
+        let request = ListOnchainTransactionsRequest {};
+        let response = self.client.list_onchain_transactions(request).await?;
+        Ok(response.into_inner().transactions)
+    }
+
+    pub async fn sweep_onchain(
+        &mut self,
+        address: String,
+        retain_reserves: bool,
+    ) -> anyhow::Result<SweepOnchainResponse> {
+        let request = SweepOnchainRequest {
+            address,
+            retain_reserves,
+        };
+        let response = self.client.sweep_onchain(request).await?;
+        Ok(response.into_inner())
+    }
+
     pub async fn send_onchain(
         &mut self,
-        amount_sat: u64,
+        amount_sats: u64,
         address: String,
     ) -> anyhow::Result<String> {
         let request = SendOnchainRequest {
-            amount_sat,
+            amount_sats,
             address,
         };
         let response = self.client.send_onchain(request).await?;
         Ok(response.into_inner().txid)
     }
+
+    pub async fn connect_peer(
+        &mut self,
+        node_id: String,
+        address: String,
+        port: u16,
+        persist: bool,
+    ) -> anyhow::Result<()> {
+        let request = ConnectPeerRequest {
+            node_id,
+            address,
+            port: port.into(),
+            persist,
+        };
+        self.client.connect_peer(request).await?;
+        Ok(())
+    }
+
+    pub async fn disconnect_peer(&mut self, node_id: String) -> anyhow::Result<()> {
+        let request = DisconnectPeerRequest { node_id };
+        self.client.disconnect_peer(request).await?;
+        Ok(())
+    }
+
+    pub async fn list_peers(&mut self) -> anyhow::Result<Vec<PeerInfo>> {
+        let request = ListPeersRequest {};
+        let response = self.client.list_peers(request).await?;
+        Ok(response.into_inner().peers)
+    }
+
+    pub async fn pay_bolt11(
+        &mut self,
+        invoice: String,
+        amount_msats: Option<u64>,
+        timeout_secs: Option<u32>,
+    ) -> anyhow::Result<PayBolt11Response> {
+        let request = PayBolt11Request {
+            invoice,
+            amount_msats,
+            timeout_secs,
+        };
+        let response = self.client.pay_bolt11(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn probe_payment(
+        &mut self,
+        node_id: Option<String>,
+        invoice: Option<String>,
+        amount_msats: Option<u64>,
+    ) -> anyhow::Result<ProbePaymentResponse> {
+        let request = ProbePaymentRequest {
+            node_id,
+            invoice,
+            amount_msats,
+        };
+        let response = self.client.probe_payment(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn decode_payment_request(
+        &mut self,
+        request: String,
+    ) -> anyhow::Result<Option<decode_payment_request_response::Decoded>> {
+        let request = DecodePaymentRequestRequest { request };
+        let response = self.client.decode_payment_request(request).await?;
+        Ok(response.into_inner().decoded)
+    }
+
+    pub async fn create_invoice(
+        &mut self,
+        amount_msats: Option<u64>,
+        description: String,
+        expiry_secs: Option<u32>,
+    ) -> anyhow::Result<CreateInvoiceResponse> {
+        let request = CreateInvoiceRequest {
+            amount_msats,
+            description,
+            expiry_secs,
+        };
+        let response = self.client.create_invoice(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn create_offer(
+        &mut self,
+        amount_msats: Option<u64>,
+        description: String,
+        expiry_secs: Option<u32>,
+        quantity: Option<u64>,
+    ) -> anyhow::Result<CreateOfferResponse> {
+        let request = CreateOfferRequest {
+            amount_msats,
+            description,
+            expiry_secs,
+            quantity,
+        };
+        let response = self.client.create_offer(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn pay_offer(
+        &mut self,
+        offer: String,
+        amount_msats: Option<u64>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+        timeout_secs: Option<u32>,
+    ) -> anyhow::Result<PayOfferResponse> {
+        let request = PayOfferRequest {
+            offer,
+            amount_msats,
+            quantity,
+            payer_note,
+            timeout_secs,
+        };
+        let response = self.client.pay_offer(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_offers(&mut self) -> anyhow::Result<Vec<OfferInfo>> {
+        let request = ListOffersRequest {};
+        let response = self.client.list_offers(request).await?;
+        Ok(response.into_inner().offers)
+    }
+
+    pub async fn subscribe_events(
+        &mut self,
+        cursor: u64,
+    ) -> anyhow::Result<tonic::Streaming<JournalEvent>> {
+        let request = SubscribeEventsRequest { cursor };
+        let response = self.client.subscribe_events(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_quotes(
+        &mut self,
+        state: QuoteStatus,
+        node_pubkey: Option<String>,
+        created_after: Option<u64>,
+        created_before: Option<u64>,
+        limit: Option<u32>,
+        page_token: Option<String>,
+    ) -> anyhow::Result<ListQuotesResponse> {
+        let request = ListQuotesRequest {
+            state: state as i32,
+            node_pubkey,
+            created_after,
+            created_before,
+            limit,
+            page_token,
+        };
+        let response = self.client.list_quotes(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn get_quote(&mut self, quote_id: String) -> anyhow::Result<GetQuoteResponse> {
+        let request = GetQuoteRequest { quote_id };
+        let response = self.client.get_quote(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_quote_audit(
+        &mut self,
+        quote_id: Option<String>,
+        cursor: u64,
+        limit: Option<u32>,
+    ) -> anyhow::Result<Vec<QuoteAuditRecord>> {
+        let request = ListQuoteAuditRequest {
+            quote_id,
+            cursor,
+            limit,
+        };
+        let response = self.client.list_quote_audit(request).await?;
+        Ok(response.into_inner().entries)
+    }
+
+    pub async fn backup_database(
+        &mut self,
+        path: String,
+    ) -> anyhow::Result<BackupDatabaseResponse> {
+        let request = BackupDatabaseRequest { path };
+        let response = self.client.backup_database(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn export_quotes(
+        &mut self,
+        format: Option<String>,
+    ) -> anyhow::Result<tonic::Streaming<ExportQuotesChunk>> {
+        let request = ExportQuotesRequest { format };
+        let response = self.client.export_quotes(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn admin_set_quote_state(
+        &mut self,
+        quote_id: String,
+        state: QuoteStatus,
+        reason: String,
+    ) -> anyhow::Result<Option<QuoteRecord>> {
+        let request = AdminSetQuoteStateRequest {
+            quote_id,
+            state: state as i32,
+            reason,
+        };
+        let response = self.client.admin_set_quote_state(request).await?;
+        Ok(response.into_inner().quote)
+    }
+
+    pub async fn refund_quote(
+        &mut self,
+        quote_id: String,
+        amount_sats: u64,
+        invoice: Option<String>,
+    ) -> anyhow::Result<Option<RefundRecord>> {
+        let request = RefundQuoteRequest {
+            quote_id,
+            amount_sats,
+            invoice,
+        };
+        let response = self.client.refund_quote(request).await?;
+        Ok(response.into_inner().refund)
+    }
+
+    pub async fn list_refunds(
+        &mut self,
+        quote_id: Option<String>,
+    ) -> anyhow::Result<Vec<RefundRecord>> {
+        let request = ListRefundsRequest { quote_id };
+        let response = self.client.list_refunds(request).await?;
+        Ok(response.into_inner().refunds)
+    }
+
+    pub async fn retry_channel_open(&mut self, quote_id: String) -> anyhow::Result<String> {
+        let request = RetryChannelOpenRequest { quote_id };
+        let response = self.client.retry_channel_open(request).await?;
+        Ok(response.into_inner().user_channel_id)
+    }
+
+    pub async fn sign_message(&mut self, message: Vec<u8>) -> anyhow::Result<String> {
+        let request = SignMessageRequest { message };
+        let response = self.client.sign_message(request).await?;
+        Ok(response.into_inner().signature)
+    }
+
+    pub async fn verify_message(
+        &mut self,
+        message: Vec<u8>,
+        signature: String,
+        pubkey: String,
+    ) -> anyhow::Result<bool> {
+        let request = VerifyMessageRequest {
+            message,
+            signature,
+            pubkey,
+        };
+        let response = self.client.verify_message(request).await?;
+        Ok(response.into_inner().valid)
+    }
+
+    pub async fn update_lsp_info(
+        &mut self,
+        min_channel_size_sats: Option<u64>,
+        max_channel_size_sats: Option<u64>,
+        min_fee: Option<u64>,
+        fee_ppk: Option<u64>,
+        accepted_mints: Option<Vec<String>>,
+    ) -> anyhow::Result<UpdateLspInfoResponse> {
+        let request = UpdateLspInfoRequest {
+            min_channel_size_sats,
+            max_channel_size_sats,
+            min_fee,
+            fee_ppk,
+            accepted_mints: accepted_mints.map(|mint_urls| AcceptedMints { mint_urls }),
+        };
+        let response = self.client.update_lsp_info(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn add_mint(&mut self, mint_url: String) -> anyhow::Result<Vec<String>> {
+        let request = AddMintRequest { mint_url };
+        let response = self.client.add_mint(request).await?;
+        Ok(response.into_inner().accepted_mints)
+    }
+
+    pub async fn remove_mint(&mut self, mint_url: String) -> anyhow::Result<Vec<String>> {
+        let request = RemoveMintRequest { mint_url };
+        let response = self.client.remove_mint(request).await?;
+        Ok(response.into_inner().accepted_mints)
+    }
+
+    pub async fn stop_node(&mut self) -> anyhow::Result<()> {
+        let request = StopNodeRequest {};
+        self.client.stop_node(request).await?;
+        Ok(())
+    }
+
+    pub async fn restart_node(&mut self) -> anyhow::Result<()> {
+        let request = RestartNodeRequest {};
+        self.client.restart_node(request).await?;
+        Ok(())
+    }
+
+    pub async fn get_fee_revenue(
+        &mut self,
+        since: Option<u64>,
+        until: Option<u64>,
+        window_secs: Option<u64>,
+    ) -> anyhow::Result<GetFeeRevenueResponse> {
+        let request = GetFeeRevenueRequest {
+            since,
+            until,
+            window_secs,
+        };
+        let response = self.client.get_fee_revenue(request).await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_peer_sla(&mut self) -> anyhow::Result<Vec<PeerSla>> {
+        let request = ListPeerSlaRequest {};
+        let response = self.client.list_peer_sla(request).await?;
+        Ok(response.into_inner().peers)
+    }
+
+    pub async fn set_peer_access(
+        &mut self,
+        node_pubkey: String,
+        access: PeerAccessLevel,
+    ) -> anyhow::Result<()> {
+        let request = SetPeerAccessRequest {
+            node_pubkey,
+            access: access as i32,
+        };
+        self.client.set_peer_access(request).await?;
+        Ok(())
+    }
+
+    pub async fn list_peer_access(&mut self) -> anyhow::Result<Vec<PeerAccessEntry>> {
+        let request = ListPeerAccessRequest {};
+        let response = self.client.list_peer_access(request).await?;
+        Ok(response.into_inner().peers)
+    }
 }
@@ -1,4 +1,9 @@
 tonic::include_proto!("cdk_ldk_management");
 
+/// Encoded descriptors of the management API, served by gRPC reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("cdk_ldk_management_descriptor");
+
+pub mod auth;
 pub mod client;
 pub mod server;
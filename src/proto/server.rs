@@ -1,33 +1,191 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use cdk::Amount;
+use cdk::amount::SplitTarget;
+use cdk::mint_url::MintUrl;
+use cdk::nuts::{CurrencyUnit, MeltQuoteState, PaymentRequest, SpendingConditions};
+use cdk::wallet::SendKind;
+use cdk::wallet::types::WalletKey;
 use ldk_node::UserChannelId;
 use ldk_node::bitcoin::Address;
+use ldk_node::bitcoin::hex::DisplayHex;
 use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::config::ChannelConfig;
+use ldk_node::lightning::ln::channelmanager::PaymentId;
 use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::offers::offer::{Amount as OfferAmount, Offer};
+use ldk_node::lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
+use ldk_node::payment::{PaymentDetails, PaymentDirection, PaymentKind, PaymentStatus};
+use tokio_stream::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
 use tonic::{Request, Response, Status};
+use uuid::Uuid;
 
 use super::cdk_ldk_management_server::CdkLdkManagement;
 use super::*;
-use crate::CashuLspNode;
+use crate::db::LspStore;
+use crate::events::EventJournal;
+use crate::lsp_server::{CashuLspState, LspError, refund_quote, retry_channel_open};
+use crate::melt_policy::melt_to_node;
+use crate::types::{
+    FeeLedgerEntry, LspEventKind, PeerAccess, QuoteActor, QuoteAuditEntry, QuoteInfo, QuoteMode,
+    QuoteRefund, QuoteState, QuoteStateTransition, RefundMethod, msats_to_sats, sats_to_msats,
+};
+use crate::{CashuLspNode, WalletFactory, mint_network};
+
+const DEFAULT_PAYMENTS_LIMIT: u32 = 100;
+const MAX_MONEY_SATS: u64 = 21_000_000 * 100_000_000;
+const DEFAULT_QUOTES_LIMIT: u32 = 100;
+const DEFAULT_AUDIT_LIMIT: u32 = 100;
+/// Quotes read from the store per chunk of an export
+const EXPORT_PAGE_SIZE: usize = 500;
+const DEFAULT_PAYMENT_TIMEOUT_SECS: u32 = 60;
+const DEFAULT_INVOICE_EXPIRY_SECS: u32 = 3600;
+const PAYMENT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Process shutdown requested over the management API or by a signal
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    restart: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.token.cancel();
+    }
+
+    /// Stop, asking the process to start again once shut down
+    pub fn restart(&self) {
+        self.restart.store(true, Ordering::SeqCst);
+        self.token.cancel();
+    }
+
+    /// Resolves once a stop or restart has been requested
+    pub async fn requested(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn restart_requested(&self) -> bool {
+        self.restart.load(Ordering::SeqCst)
+    }
+}
 
 pub struct CdkLdkServer {
     node: Arc<CashuLspNode>,
+    events: EventJournal,
+    lsp: Option<CashuLspState>,
+    shutdown: Option<Shutdown>,
+    wallet_factory: Option<WalletFactory>,
+    db: Arc<dyn LspStore>,
+    started_at: Instant,
+}
+
+impl CdkLdkServer {
+    pub fn new(node: Arc<CashuLspNode>, db: Arc<dyn LspStore>) -> Self {
+        Self {
+            node,
+            events: EventJournal::new(db.clone()),
+            lsp: None,
+            shutdown: None,
+            wallet_factory: None,
+            db,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Share the event journal with the LSP so live events reach subscribers
+    pub fn with_events(mut self, events: EventJournal) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Enable RPCs that act on quotes through the LSP
+    pub fn with_lsp(mut self, lsp: CashuLspState) -> Self {
+        self.lsp = Some(lsp);
+        self
+    }
+
+    /// Enable the stop and restart RPCs
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Enable adding mints at runtime
+    pub fn with_wallet_factory(mut self, wallet_factory: WalletFactory) -> Self {
+        self.wallet_factory = Some(wallet_factory);
+        self
+    }
 }
 
 impl CdkLdkServer {
-    pub fn new(node: Arc<CashuLspNode>) -> Self {
-        Self { node }
+    /// Poll a payment until it resolves or `timeout` elapses
+    async fn wait_for_payment(
+        &self,
+        payment_id: PaymentId,
+        timeout: Duration,
+    ) -> Option<PaymentDetails> {
+        let started = Instant::now();
+
+        loop {
+            let payment = self.node.inner.payment(&payment_id);
+
+            let resolved = payment
+                .as_ref()
+                .is_some_and(|p| p.status != PaymentStatus::Pending);
+
+            if resolved || started.elapsed() >= timeout {
+                return payment;
+            }
+
+            tokio::time::sleep(PAYMENT_POLL_INTERVAL).await;
+        }
     }
 }
 
 #[tonic::async_trait]
 impl CdkLdkManagement for CdkLdkServer {
+    type SubscribeEventsStream =
+        Pin<Box<dyn Stream<Item = Result<JournalEvent, Status>> + Send + 'static>>;
+    type ExportQuotesStream =
+        Pin<Box<dyn Stream<Item = Result<ExportQuotesChunk, Status>> + Send + 'static>>;
+
     async fn get_info(
         &self,
         _request: Request<GetInfoRequest>,
     ) -> Result<Response<GetInfoResponse>, Status> {
-        Ok(Response::new(GetInfoResponse {}))
+        let node = &self.node.inner;
+        let status = node.status();
+        let peers = node.list_peers();
+        let channels = node.list_channels();
+
+        Ok(Response::new(GetInfoResponse {
+            node_id: node.node_id().to_string(),
+            alias: node.node_alias().map(|alias| alias.to_string()),
+            network: node.config().network.to_string(),
+            block_height: status.current_best_block.height,
+            block_hash: status.current_best_block.block_hash.to_string(),
+            is_running: status.is_running,
+            latest_onchain_wallet_sync_timestamp: status.latest_onchain_wallet_sync_timestamp,
+            latest_lightning_wallet_sync_timestamp: status.latest_lightning_wallet_sync_timestamp,
+            num_peers: peers.len() as u32,
+            num_connected_peers: peers.iter().filter(|p| p.is_connected).count() as u32,
+            num_channels: channels.len() as u32,
+            num_usable_channels: channels.iter().filter(|c| c.is_usable).count() as u32,
+            num_pending_channels: channels.iter().filter(|c| !c.is_channel_ready).count() as u32,
+            uptime_secs: self.started_at.elapsed().as_secs(),
+        }))
     }
 
     async fn get_new_address(
@@ -52,20 +210,45 @@ impl CdkLdkManagement for CdkLdkServer {
     ) -> Result<Response<OpenChannelResponse>, Status> {
         let req = request.into_inner();
 
-        let socket_addr = SocketAddress::from_str(&format!("{}:{}", req.address, req.port))
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let port = u16::try_from(req.port)
+            .map_err(|_| Status::invalid_argument(format!("Invalid port: {}", req.port)))?;
 
-        let channel = self
-            .node
-            .inner
-            .open_announced_channel(
-                PublicKey::from_str(&req.node_id).map_err(|e| Status::internal(e.to_string()))?,
+        let socket_addr = SocketAddress::from_str(&format!("{}:{}", req.address, port))
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let node_id = PublicKey::from_str(&req.node_id)
+            .map_err(|e| Status::invalid_argument(format!("Invalid node id: {}", e)))?;
+
+        let amount_msats = sats_to_msats(req.amount_sats)
+            .ok_or_else(|| Status::invalid_argument("Channel amount overflow"))?;
+
+        if req.push_to_counter_party_msats.unwrap_or_default() > amount_msats {
+            return Err(Status::invalid_argument(
+                "Push amount exceeds channel amount",
+            ));
+        }
+
+        let options = req.options.unwrap_or_default();
+        let channel_config = options_channel_config(&options)?;
+
+        let channel = if options.announce.unwrap_or(true) {
+            self.node.inner.open_announced_channel(
+                node_id,
                 socket_addr,
-                req.amount_msats,
+                req.amount_sats,
                 req.push_to_counter_party_msats,
-                None,
+                channel_config,
             )
-            .map_err(|e| Status::internal(e.to_string()))?;
+        } else {
+            self.node.inner.open_channel(
+                node_id,
+                socket_addr,
+                req.amount_sats,
+                req.push_to_counter_party_msats,
+                channel_config,
+            )
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
 
         Ok(Response::new(OpenChannelResponse {
             channel_id: channel.0.to_string(),
@@ -90,12 +273,94 @@ impl CdkLdkManagement for CdkLdkServer {
 
         let channel_id = UserChannelId(channel_id);
 
+        let channel = self
+            .node
+            .inner
+            .list_channels()
+            .into_iter()
+            .find(|c| c.user_channel_id == channel_id && c.counterparty_node_id == node_pubkey)
+            .ok_or_else(|| Status::not_found("Unknown channel"))?;
+
+        let status = if req.force {
+            tracing::warn!(
+                "Force closing channel {} with {}",
+                channel.channel_id,
+                node_pubkey
+            );
+
+            self.node
+                .inner
+                .force_close_channel(&channel_id, node_pubkey, req.reason)
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            "force_closed"
+        } else {
+            self.node
+                .inner
+                .close_channel(&channel_id, node_pubkey)
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            "closing"
+        };
+
+        Ok(Response::new(CloseChannelResponse {
+            status: status.to_string(),
+            funding_txo: channel.funding_txo.map(|txo| txo.to_string()),
+        }))
+    }
+
+    async fn update_channel_config(
+        &self,
+        request: Request<UpdateChannelConfigRequest>,
+    ) -> Result<Response<UpdateChannelConfigResponse>, Status> {
+        let req = request.into_inner();
+
+        let node_pubkey = req
+            .node_pubkey
+            .parse()
+            .map_err(|e| Status::invalid_argument(format!("Invalid node pubkey: {}", e)))?;
+
+        let channel_id: u128 = req
+            .channel_id
+            .parse()
+            .map_err(|e| Status::invalid_argument(format!("Invalid channel id: {}", e)))?;
+
+        let channel_id = UserChannelId(channel_id);
+
+        let channel = self
+            .node
+            .inner
+            .list_channels()
+            .into_iter()
+            .find(|c| c.user_channel_id == channel_id && c.counterparty_node_id == node_pubkey)
+            .ok_or_else(|| Status::not_found("Unknown channel"))?;
+
+        let mut config = channel.config;
+
+        if let Some(base_msat) = req.forwarding_fee_base_msat {
+            config.forwarding_fee_base_msat = base_msat;
+        }
+
+        if let Some(ppm) = req.forwarding_fee_ppm {
+            config.forwarding_fee_proportional_millionths = ppm;
+        }
+
+        if let Some(delta) = req.cltv_expiry_delta {
+            config.cltv_expiry_delta = u16::try_from(delta).map_err(|_| {
+                Status::invalid_argument(format!("Invalid cltv expiry delta: {}", delta))
+            })?;
+        }
+
         self.node
             .inner
-            .close_channel(&channel_id, node_pubkey)
+            .update_channel_config(&channel_id, node_pubkey, config.clone())
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        Ok(Response::new(CloseChannelResponse {}))
+        Ok(Response::new(UpdateChannelConfigResponse {
+            forwarding_fee_base_msat: config.forwarding_fee_base_msat,
+            forwarding_fee_ppm: config.forwarding_fee_proportional_millionths,
+            cltv_expiry_delta: u32::from(config.cltv_expiry_delta),
+        }))
     }
 
     async fn list_balance(
@@ -111,24 +376,1815 @@ impl CdkLdkManagement for CdkLdkServer {
         }))
     }
 
+    async fn list_cashu_balances(
+        &self,
+        _request: Request<ListCashuBalancesRequest>,
+    ) -> Result<Response<ListCashuBalancesResponse>, Status> {
+        let mut balances = vec![];
+
+        for wallet in self.node.wallet.get_wallets().await {
+            let balance = wallet
+                .total_balance()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let pending = wallet
+                .total_pending_balance()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let reserved = wallet
+                .total_reserved_balance()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            balances.push(CashuBalance {
+                mint_url: wallet.mint_url.to_string(),
+                unit: wallet.unit.to_string(),
+                balance: balance.into(),
+                pending: pending.into(),
+                reserved: reserved.into(),
+            });
+        }
+
+        Ok(Response::new(ListCashuBalancesResponse { balances }))
+    }
+
+    async fn restore_wallets(
+        &self,
+        _request: Request<RestoreWalletsRequest>,
+    ) -> Result<Response<RestoreWalletsResponse>, Status> {
+        let mut balances = vec![];
+
+        for wallet in self.node.wallet.get_wallets().await {
+            let (restored, error) = match wallet.restore().await {
+                Ok(restored) => {
+                    tracing::info!(
+                        "Restored {} {} from {}",
+                        restored,
+                        wallet.unit,
+                        wallet.mint_url
+                    );
+                    (restored.into(), None)
+                }
+                Err(err) => {
+                    tracing::warn!("Could not restore from {}: {}", wallet.mint_url, err);
+                    (0, Some(err.to_string()))
+                }
+            };
+
+            balances.push(RestoredBalance {
+                mint_url: wallet.mint_url.to_string(),
+                unit: wallet.unit.to_string(),
+                restored,
+                error,
+            });
+        }
+
+        Ok(Response::new(RestoreWalletsResponse { balances }))
+    }
+
+    async fn export_proofs(
+        &self,
+        request: Request<ExportProofsRequest>,
+    ) -> Result<Response<ExportProofsResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.amount == 0 {
+            return Err(Status::invalid_argument("Amount must be greater than zero"));
+        }
+
+        let mint_url = MintUrl::from_str(&req.mint_url)
+            .map_err(|e| Status::invalid_argument(format!("Invalid mint url: {}", e)))?;
+
+        let unit = match req.unit {
+            Some(unit) => CurrencyUnit::from_str(&unit)
+                .map_err(|e| Status::invalid_argument(format!("Invalid unit: {}", e)))?,
+            None => CurrencyUnit::Sat,
+        };
+
+        let conditions = req
+            .p2pk_pubkey
+            .map(|pubkey| {
+                cdk::nuts::PublicKey::from_hex(&pubkey)
+                    .map(|pubkey| SpendingConditions::new_p2pk(pubkey, None))
+                    .map_err(|e| Status::invalid_argument(format!("Invalid pubkey: {}", e)))
+            })
+            .transpose()?;
+
+        let wallet = self
+            .node
+            .wallet
+            .get_wallet(&WalletKey::new(mint_url.clone(), unit.clone()))
+            .await
+            .ok_or_else(|| Status::not_found(format!("No {} wallet for {}", unit, mint_url)))?;
+
+        let token = wallet
+            .send(
+                Amount::from(req.amount),
+                req.memo,
+                conditions,
+                &SplitTarget::default(),
+                &SendKind::default(),
+                true,
+            )
+            .await
+            .map_err(|e| Status::failed_precondition(format!("Could not export proofs: {}", e)))?;
+
+        tracing::info!("Exported {} {} from {}", req.amount, unit, mint_url);
+
+        Ok(Response::new(ExportProofsResponse {
+            token: token.to_string(),
+        }))
+    }
+
+    async fn melt_to_lightning(
+        &self,
+        request: Request<MeltToLightningRequest>,
+    ) -> Result<Response<MeltToLightningResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.amount_sats == 0 {
+            return Err(Status::invalid_argument("Amount must be greater than zero"));
+        }
+
+        let mint_url = MintUrl::from_str(&req.mint_url)
+            .map_err(|e| Status::invalid_argument(format!("Invalid mint url: {}", e)))?;
+
+        let wallet = self
+            .node
+            .wallet
+            .get_wallet(&WalletKey::new(mint_url.clone(), CurrencyUnit::Sat))
+            .await
+            .ok_or_else(|| Status::not_found(format!("No sat wallet for {}", mint_url)))?;
+
+        let (invoice, melted) = melt_to_node(&self.node, &wallet, req.amount_sats)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let state = match melted.state {
+            MeltQuoteState::Paid => "paid",
+            MeltQuoteState::Pending => "pending",
+            _ => "unpaid",
+        };
+
+        Ok(Response::new(MeltToLightningResponse {
+            payment_hash: invoice.payment_hash().to_string(),
+            state: state.to_string(),
+            amount_sats: melted.amount.into(),
+            fee_paid_sats: melted.fee_paid.into(),
+        }))
+    }
+
+    async fn list_channels(
+        &self,
+        _request: Request<ListChannelsRequest>,
+    ) -> Result<Response<ListChannelsResponse>, Status> {
+        let channels = self
+            .node
+            .inner
+            .list_channels()
+            .into_iter()
+            .map(|c| ChannelInfo {
+                channel_id: c.channel_id.to_string(),
+                user_channel_id: c.user_channel_id.0.to_string(),
+                counterparty_node_id: c.counterparty_node_id.to_string(),
+                funding_txo: c.funding_txo.map(|txo| txo.to_string()),
+                channel_value_sats: c.channel_value_sats,
+                outbound_capacity_msats: c.outbound_capacity_msat,
+                inbound_capacity_msats: c.inbound_capacity_msat,
+                confirmations: c.confirmations,
+                confirmations_required: c.confirmations_required,
+                is_outbound: c.is_outbound,
+                is_channel_ready: c.is_channel_ready,
+                is_usable: c.is_usable,
+                is_announced: c.is_announced,
+                forwarding_fee_base_msat: c.config.forwarding_fee_base_msat,
+                forwarding_fee_ppm: c.config.forwarding_fee_proportional_millionths,
+                cltv_expiry_delta: u32::from(c.config.cltv_expiry_delta),
+            })
+            .collect();
+
+        Ok(Response::new(ListChannelsResponse { channels }))
+    }
+
+    async fn list_payments(
+        &self,
+        request: Request<ListPaymentsRequest>,
+    ) -> Result<Response<ListPaymentsResponse>, Status> {
+        let req = request.into_inner();
+
+        let mut payments = self.node.inner.list_payments();
+        payments.sort_by(|a, b| b.latest_update_timestamp.cmp(&a.latest_update_timestamp));
+
+        let total = payments.len() as u64;
+
+        let payments = payments
+            .into_iter()
+            .skip(req.offset as usize)
+            .take(req.limit.unwrap_or(DEFAULT_PAYMENTS_LIMIT) as usize)
+            .map(payment_info)
+            .collect();
+
+        Ok(Response::new(ListPaymentsResponse { payments, total }))
+    }
+
     async fn send_onchain(
         &self,
         request: Request<SendOnchainRequest>,
     ) -> Result<Response<SendOnchainResponse>, Status> {
         let req = request.into_inner();
 
-        let address =
-            Address::from_str(&req.address).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let address = Address::from_str(&req.address)
+            .and_then(|address| address.require_network(self.node.inner.config().network))
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let txid = self
             .node
             .inner
             .onchain_payment()
-            .send_to_address(address.assume_checked_ref(), req.amount_sat)
+            .send_to_address(&address, req.amount_sats)
             .map_err(|e| Status::internal(e.to_string()))?;
 
         Ok(Response::new(SendOnchainResponse {
             txid: txid.to_string(),
         }))
     }
+
+    async fn list_onchain_transactions(
+        &self,
+        _request: Request<ListOnchainTransactionsRequest>,
+    ) -> Result<Response<ListOnchainTransactionsResponse>, Status> {
+        let funding = self.node.inner.list_channels().into_iter().filter_map(|c| {
+            let txo = c.funding_txo?;
+            Some(OnchainTransaction {
+                txid: Some(txo.txid.to_string()),
+                kind: "funding".to_string(),
+                direction: if c.is_outbound { "outbound" } else { "inbound" }.to_string(),
+                amount_sats: Some(c.channel_value_sats),
+                confirmations: c.confirmations,
+                status: None,
+                user_channel_id: Some(c.user_channel_id.0.to_string()),
+            })
+        });
+
+        let payments = self
+            .node
+            .inner
+            .list_payments_with_filter(|p| matches!(p.kind, PaymentKind::Onchain { .. }))
+            .into_iter()
+            .map(|p| OnchainTransaction {
+                txid: None,
+                kind: "payment".to_string(),
+                direction: match p.direction {
+                    PaymentDirection::Inbound => "inbound",
+                    PaymentDirection::Outbound => "outbound",
+                }
+                .to_string(),
+                amount_sats: p.amount_msat.map(msats_to_sats),
+                confirmations: None,
+                status: Some(payment_status(p.status)),
+                user_channel_id: None,
+            });
+
+        Ok(Response::new(ListOnchainTransactionsResponse {
+            transactions: funding.chain(payments).collect(),
+        }))
+    }
+
+    async fn sweep_onchain(
+        &self,
+        request: Request<SweepOnchainRequest>,
+    ) -> Result<Response<SweepOnchainResponse>, Status> {
+        let req = request.into_inner();
+
+        let address = Address::from_str(&req.address)
+            .and_then(|address| address.require_network(self.node.inner.config().network))
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let spendable_before_sats = self
+            .node
+            .inner
+            .list_balances()
+            .spendable_onchain_balance_sats;
+
+        let txid = self
+            .node
+            .inner
+            .onchain_payment()
+            .send_all_to_address(&address, req.retain_reserves)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        tracing::info!(
+            "Swept onchain balance of {} sats, less fee and reserves, in {}",
+            spendable_before_sats,
+            txid
+        );
+
+        Ok(Response::new(SweepOnchainResponse {
+            txid: txid.to_string(),
+            spendable_before_sats,
+        }))
+    }
+
+    async fn connect_peer(
+        &self,
+        request: Request<ConnectPeerRequest>,
+    ) -> Result<Response<ConnectPeerResponse>, Status> {
+        let req = request.into_inner();
+
+        let port = u16::try_from(req.port)
+            .map_err(|_| Status::invalid_argument(format!("Invalid port: {}", req.port)))?;
+
+        let socket_addr = SocketAddress::from_str(&format!("{}:{}", req.address, port))
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let node_id = PublicKey::from_str(&req.node_id)
+            .map_err(|e| Status::invalid_argument(format!("Invalid node id: {}", e)))?;
+
+        self.node
+            .inner
+            .connect(node_id, socket_addr, req.persist)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ConnectPeerResponse {}))
+    }
+
+    async fn disconnect_peer(
+        &self,
+        request: Request<DisconnectPeerRequest>,
+    ) -> Result<Response<DisconnectPeerResponse>, Status> {
+        let req = request.into_inner();
+
+        let node_id = PublicKey::from_str(&req.node_id)
+            .map_err(|e| Status::invalid_argument(format!("Invalid node id: {}", e)))?;
+
+        self.node
+            .inner
+            .disconnect(node_id)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(DisconnectPeerResponse {}))
+    }
+
+    async fn list_peers(
+        &self,
+        _request: Request<ListPeersRequest>,
+    ) -> Result<Response<ListPeersResponse>, Status> {
+        let peers = self
+            .node
+            .inner
+            .list_peers()
+            .into_iter()
+            .map(|p| PeerInfo {
+                node_id: p.node_id.to_string(),
+                address: p.address.to_string(),
+                is_persisted: p.is_persisted,
+                is_connected: p.is_connected,
+            })
+            .collect();
+
+        Ok(Response::new(ListPeersResponse { peers }))
+    }
+
+    async fn pay_bolt11(
+        &self,
+        request: Request<PayBolt11Request>,
+    ) -> Result<Response<PayBolt11Response>, Status> {
+        let req = request.into_inner();
+
+        let invoice = Bolt11Invoice::from_str(&req.invoice)
+            .map_err(|e| Status::invalid_argument(format!("Invalid invoice: {}", e)))?;
+
+        let bolt11 = self.node.inner.bolt11_payment();
+
+        let payment_id = match (invoice.amount_milli_satoshis(), req.amount_msats) {
+            (Some(_), Some(_)) => {
+                return Err(Status::invalid_argument(
+                    "Amount must not be set for invoices with an amount",
+                ));
+            }
+            (None, None) => {
+                return Err(Status::invalid_argument(
+                    "Amount is required for zero-amount invoices",
+                ));
+            }
+            (Some(_), None) => bolt11.send(&invoice, None),
+            (None, Some(amount_msats)) => bolt11.send_using_amount(&invoice, amount_msats, None),
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let timeout = Duration::from_secs(
+            req.timeout_secs
+                .unwrap_or(DEFAULT_PAYMENT_TIMEOUT_SECS)
+                .into(),
+        );
+        let payment = self.wait_for_payment(payment_id, timeout).await;
+
+        let (status, preimage) = match payment {
+            Some(payment) => {
+                let preimage = match payment.kind {
+                    PaymentKind::Bolt11 { preimage, .. } => preimage,
+                    _ => None,
+                };
+                (payment_status(payment.status), preimage)
+            }
+            None => (payment_status(PaymentStatus::Pending), None),
+        };
+
+        Ok(Response::new(PayBolt11Response {
+            payment_id: payment_id.0.to_lower_hex_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+            status,
+            preimage: preimage.map(|p| p.0.to_lower_hex_string()),
+            fee_paid_msats: None,
+        }))
+    }
+
+    async fn probe_payment(
+        &self,
+        request: Request<ProbePaymentRequest>,
+    ) -> Result<Response<ProbePaymentResponse>, Status> {
+        let req = request.into_inner();
+
+        let result = match (req.node_id, req.invoice) {
+            (Some(node_id), None) => {
+                let node_id = PublicKey::from_str(&node_id)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid node id: {}", e)))?;
+                let amount_msats = req
+                    .amount_msats
+                    .ok_or_else(|| Status::invalid_argument("Amount is required"))?;
+
+                self.node
+                    .inner
+                    .spontaneous_payment()
+                    .send_probes(amount_msats, node_id)
+            }
+            (None, Some(invoice)) => {
+                let invoice = Bolt11Invoice::from_str(&invoice)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid invoice: {}", e)))?;
+                let bolt11 = self.node.inner.bolt11_payment();
+
+                match (invoice.amount_milli_satoshis(), req.amount_msats) {
+                    (Some(_), None) => bolt11.send_probes(&invoice),
+                    (None, Some(amount_msats)) => {
+                        bolt11.send_probes_using_amount(&invoice, amount_msats)
+                    }
+                    (Some(_), Some(_)) => {
+                        return Err(Status::invalid_argument(
+                            "Amount can only be set for zero-amount invoices",
+                        ));
+                    }
+                    (None, None) => {
+                        return Err(Status::invalid_argument(
+                            "Amount is required for zero-amount invoices",
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Err(Status::invalid_argument(
+                    "Exactly one of node id or invoice is required",
+                ));
+            }
+        };
+
+        Ok(Response::new(match result {
+            Ok(()) => ProbePaymentResponse {
+                routable: true,
+                error: None,
+            },
+            Err(err) => ProbePaymentResponse {
+                routable: false,
+                error: Some(err.to_string()),
+            },
+        }))
+    }
+
+    async fn decode_payment_request(
+        &self,
+        request: Request<DecodePaymentRequestRequest>,
+    ) -> Result<Response<DecodePaymentRequestResponse>, Status> {
+        let request = request.into_inner().request;
+        let request = request.trim();
+
+        let decoded = if let Ok(invoice) = Bolt11Invoice::from_str(request) {
+            decode_payment_request_response::Decoded::Bolt11(decode_bolt11(&invoice))
+        } else if let Ok(offer) = Offer::from_str(request) {
+            decode_payment_request_response::Decoded::Bolt12Offer(decode_offer(&offer))
+        } else if let Ok(payment_request) = PaymentRequest::from_str(request) {
+            decode_payment_request_response::Decoded::Cashu(decode_cashu_request(payment_request))
+        } else {
+            return Err(Status::invalid_argument(
+                "Not a BOLT11 invoice, BOLT12 offer or NUT-18 payment request",
+            ));
+        };
+
+        Ok(Response::new(DecodePaymentRequestResponse {
+            decoded: Some(decoded),
+        }))
+    }
+
+    async fn create_invoice(
+        &self,
+        request: Request<CreateInvoiceRequest>,
+    ) -> Result<Response<CreateInvoiceResponse>, Status> {
+        let req = request.into_inner();
+
+        let expiry_secs = req.expiry_secs.unwrap_or(DEFAULT_INVOICE_EXPIRY_SECS);
+        let bolt11 = self.node.inner.bolt11_payment();
+
+        let invoice = match req.amount_msats {
+            Some(amount_msats) => bolt11.receive(amount_msats, &req.description, expiry_secs),
+            None => bolt11.receive_variable_amount(&req.description, expiry_secs),
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(CreateInvoiceResponse {
+            invoice: invoice.to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+        }))
+    }
+
+    async fn create_offer(
+        &self,
+        request: Request<CreateOfferRequest>,
+    ) -> Result<Response<CreateOfferResponse>, Status> {
+        let req = request.into_inner();
+
+        let bolt12 = self.node.inner.bolt12_payment();
+
+        let offer = match req.amount_msats {
+            Some(amount_msats) => bolt12.receive(
+                amount_msats,
+                &req.description,
+                req.expiry_secs,
+                req.quantity,
+            ),
+            None => {
+                if req.quantity.is_some() {
+                    return Err(Status::invalid_argument(
+                        "Quantity requires an offer amount",
+                    ));
+                }
+                bolt12.receive_variable_amount(&req.description, req.expiry_secs)
+            }
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let record = self
+            .db
+            .add_offer(
+                offer.id().0,
+                offer.to_string(),
+                req.description,
+                req.amount_msats,
+            )
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(CreateOfferResponse {
+            offer: record.offer,
+            offer_id: record.offer_id.to_lower_hex_string(),
+        }))
+    }
+
+    async fn pay_offer(
+        &self,
+        request: Request<PayOfferRequest>,
+    ) -> Result<Response<PayOfferResponse>, Status> {
+        let req = request.into_inner();
+
+        let offer = Offer::from_str(&req.offer)
+            .map_err(|e| Status::invalid_argument(format!("Invalid offer: {:?}", e)))?;
+
+        let bolt12 = self.node.inner.bolt12_payment();
+
+        let payment_id = match req.amount_msats {
+            Some(amount_msats) => {
+                bolt12.send_using_amount(&offer, amount_msats, req.quantity, req.payer_note)
+            }
+            None => bolt12.send(&offer, req.quantity, req.payer_note),
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let timeout = Duration::from_secs(
+            req.timeout_secs
+                .unwrap_or(DEFAULT_PAYMENT_TIMEOUT_SECS)
+                .into(),
+        );
+        let payment = self.wait_for_payment(payment_id, timeout).await;
+
+        let (status, hash, preimage) = match payment {
+            Some(payment) => match payment.kind {
+                PaymentKind::Bolt12Offer { hash, preimage, .. } => {
+                    (payment_status(payment.status), hash, preimage)
+                }
+                _ => (payment_status(payment.status), None, None),
+            },
+            None => (payment_status(PaymentStatus::Pending), None, None),
+        };
+
+        Ok(Response::new(PayOfferResponse {
+            payment_id: payment_id.0.to_lower_hex_string(),
+            status,
+            payment_hash: hash.map(|h| h.0.to_lower_hex_string()),
+            preimage: preimage.map(|p| p.0.to_lower_hex_string()),
+        }))
+    }
+
+    async fn list_offers(
+        &self,
+        _request: Request<ListOffersRequest>,
+    ) -> Result<Response<ListOffersResponse>, Status> {
+        let records = self
+            .db
+            .list_offers()
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let payments = self.node.inner.list_payments_with_filter(|p| {
+            p.direction == PaymentDirection::Inbound && p.status == PaymentStatus::Succeeded
+        });
+
+        let offers = records
+            .into_iter()
+            .map(|record| {
+                let offer_payments: Vec<&PaymentDetails> = payments
+                    .iter()
+                    .filter(|p| {
+                        matches!(&p.kind, PaymentKind::Bolt12Offer { offer_id, .. }
+                            if offer_id.0 == record.offer_id)
+                    })
+                    .collect();
+
+                OfferInfo {
+                    offer_id: record.offer_id.to_lower_hex_string(),
+                    offer: record.offer,
+                    description: record.description,
+                    amount_msats: record.amount_msats,
+                    created_at: record.created_at,
+                    num_payments: offer_payments.len() as u64,
+                    received_msats: offer_payments.iter().filter_map(|p| p.amount_msat).sum(),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(ListOffersResponse { offers }))
+    }
+
+    async fn subscribe_events(
+        &self,
+        request: Request<SubscribeEventsRequest>,
+    ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        let cursor = request.into_inner().cursor;
+
+        let events = self
+            .events
+            .subscribe(cursor)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map(|event| {
+                Ok(JournalEvent {
+                    seq: event.seq,
+                    timestamp: event.timestamp,
+                    kind: serde_json::to_string(&event.kind)
+                        .map_err(|e| Status::internal(e.to_string()))?,
+                })
+            });
+
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn list_quotes(
+        &self,
+        request: Request<ListQuotesRequest>,
+    ) -> Result<Response<ListQuotesResponse>, Status> {
+        let req = request.into_inner();
+
+        let state = QuoteStatus::try_from(req.state)
+            .map_err(|_| Status::invalid_argument(format!("Invalid state: {}", req.state)))?;
+
+        let node_pubkey = req
+            .node_pubkey
+            .map(|pk| PublicKey::from_str(&pk))
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid node pubkey"))?;
+
+        let page_token = req
+            .page_token
+            .map(|token| parse_page_token(&token))
+            .transpose()?;
+
+        let quotes = match node_pubkey {
+            Some(node_pubkey) => self.db.list_node_quotes(node_pubkey),
+            None => self.db.list_quotes(),
+        }
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut quotes: Vec<QuoteInfo> = quotes
+            .into_iter()
+            .filter(|quote| state == QuoteStatus::Unspecified || quote_status(quote.state) == state)
+            .filter(|quote| node_pubkey.is_none_or(|pk| quote.node_pubkey == pk))
+            .filter(|quote| req.created_after.is_none_or(|t| quote.created_at >= t))
+            .filter(|quote| req.created_before.is_none_or(|t| quote.created_at < t))
+            .filter(|quote| page_token.is_none_or(|token| (quote.created_at, quote.id) < token))
+            .collect();
+
+        // Newest first, ties broken by id so pages never overlap
+        quotes.sort_by(|a, b| (b.created_at, b.id).cmp(&(a.created_at, a.id)));
+
+        let limit = req.limit.unwrap_or(DEFAULT_QUOTES_LIMIT).max(1) as usize;
+
+        let next_page_token = if quotes.len() > limit {
+            quotes
+                .get(limit - 1)
+                .map(|quote| format!("{}:{}", quote.created_at, quote.id))
+        } else {
+            None
+        };
+
+        let quotes = quotes.iter().take(limit).map(quote_record).collect();
+
+        Ok(Response::new(ListQuotesResponse {
+            quotes,
+            next_page_token,
+        }))
+    }
+
+    async fn get_quote(
+        &self,
+        request: Request<GetQuoteRequest>,
+    ) -> Result<Response<GetQuoteResponse>, Status> {
+        let quote_id = parse_quote_id(&request.into_inner().quote_id)?;
+
+        let quote = self
+            .db
+            .get_quote(quote_id)
+            .map_err(|_| Status::not_found(format!("Unknown quote: {}", quote_id)))?;
+
+        let history = self
+            .db
+            .list_quote_history(quote_id, 0, None)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(quote_transition)
+            .collect();
+
+        let payment = self
+            .db
+            .get_quote_payment(quote_id)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map(|payment| QuotePayment {
+                mint_url: payment.mint_url.to_string(),
+                unit: payment.unit.to_string(),
+                proofs: payment
+                    .proofs
+                    .into_iter()
+                    .map(|proof| ReceivedProof {
+                        amount: proof.amount,
+                        keyset_id: proof.keyset_id,
+                        y: proof.y,
+                    })
+                    .collect(),
+                received_at: payment.received_at,
+            });
+
+        Ok(Response::new(GetQuoteResponse {
+            quote: Some(quote_record(&quote)),
+            history,
+            payment,
+        }))
+    }
+
+    async fn list_quote_audit(
+        &self,
+        request: Request<ListQuoteAuditRequest>,
+    ) -> Result<Response<ListQuoteAuditResponse>, Status> {
+        let req = request.into_inner();
+
+        let quote_id = req
+            .quote_id
+            .map(|quote_id| parse_quote_id(&quote_id))
+            .transpose()?;
+
+        let limit = req.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).max(1) as usize;
+
+        let entries = self
+            .db
+            .list_quote_audit(quote_id, req.cursor, limit)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(quote_audit_record)
+            .collect();
+
+        Ok(Response::new(ListQuoteAuditResponse { entries }))
+    }
+
+    async fn backup_database(
+        &self,
+        request: Request<BackupDatabaseRequest>,
+    ) -> Result<Response<BackupDatabaseResponse>, Status> {
+        let path = PathBuf::from(request.into_inner().path);
+
+        if !path.is_absolute() {
+            return Err(Status::invalid_argument("Backup path must be absolute"));
+        }
+
+        if path.exists() {
+            return Err(Status::already_exists(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+
+        self.db
+            .backup(&path)
+            .map_err(|e| Status::internal(format!("Could not back up database: {}", e)))?;
+
+        let size_bytes = std::fs::metadata(&path)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .len();
+
+        tracing::info!("Backed up database to {}", path.display());
+
+        Ok(Response::new(BackupDatabaseResponse {
+            path: path.display().to_string(),
+            size_bytes,
+        }))
+    }
+
+    async fn export_quotes(
+        &self,
+        request: Request<ExportQuotesRequest>,
+    ) -> Result<Response<Self::ExportQuotesStream>, Status> {
+        let format = request.into_inner().format;
+
+        let csv = match format.as_deref().unwrap_or("json") {
+            "json" => false,
+            "csv" => true,
+            format => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown export format {}",
+                    format
+                )));
+            }
+        };
+
+        let header = csv.then(|| ExportQuotesChunk {
+            data: (QUOTES_CSV_HEADER.to_string() + "\n").into_bytes(),
+        });
+
+        // Read the store a page at a time, the state is the last quote id sent
+        // or `None` once the last page has been sent
+        let db = Arc::clone(&self.db);
+        let pages = futures::stream::unfold(Some(None), move |after| {
+            let db = Arc::clone(&db);
+            async move {
+                let page = match db.list_quotes_after(after?, EXPORT_PAGE_SIZE) {
+                    Ok(page) => page,
+                    Err(e) => return Some((Err(Status::internal(e.to_string())), None)),
+                };
+
+                let next =
+                    (page.len() == EXPORT_PAGE_SIZE).then(|| page.last().map(|quote| quote.id));
+
+                (!page.is_empty()).then(|| (export_chunk(&page, csv), next))
+            }
+        });
+
+        let chunks = tokio_stream::iter(header.map(Ok)).chain(pages);
+
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    async fn admin_set_quote_state(
+        &self,
+        request: Request<AdminSetQuoteStateRequest>,
+    ) -> Result<Response<AdminSetQuoteStateResponse>, Status> {
+        let req = request.into_inner();
+
+        let quote_id = parse_quote_id(&req.quote_id)?;
+
+        let state = match QuoteStatus::try_from(req.state)
+            .map_err(|_| Status::invalid_argument(format!("Invalid state: {}", req.state)))?
+        {
+            QuoteStatus::Unspecified => return Err(Status::invalid_argument("State is required")),
+            QuoteStatus::Unpaid => QuoteState::Unpaid,
+            QuoteStatus::Paid => QuoteState::Paid,
+            QuoteStatus::ChannelPending => QuoteState::ChannelPending,
+            QuoteStatus::ChannelOpen => QuoteState::ChannelOpen,
+            QuoteStatus::ChannelExpired => QuoteState::ChannelExpired,
+        };
+
+        let reason = req.reason.trim();
+        if reason.is_empty() {
+            return Err(Status::invalid_argument("Reason is required"));
+        }
+
+        let current = self
+            .db
+            .get_quote(quote_id)
+            .map_err(|_| Status::not_found(format!("Unknown quote: {}", quote_id)))?;
+
+        // Payments and channel opens for the peer hold its lock
+        let _peer_guard = match self.lsp.as_ref() {
+            Some(lsp) => Some(lsp.peer_locks.lock(current.node_pubkey).await),
+            None => None,
+        };
+
+        // Overriding a quote mid-payment would race the payment task
+        if self
+            .db
+            .is_payment_pending(quote_id)
+            .map_err(|e| Status::internal(e.to_string()))?
+        {
+            return Err(Status::failed_precondition(format!(
+                "Payment for quote {} is being processed",
+                quote_id
+            )));
+        }
+
+        // Re-read under the lock, the quote may have moved on meanwhile
+        let current = self
+            .db
+            .get_quote(quote_id)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        if !override_allowed(current.state, state) {
+            return Err(Status::failed_precondition(format!(
+                "Quote {} can't be moved from {:?} to {:?}",
+                quote_id, current.state, state
+            )));
+        }
+
+        let previous = self
+            .db
+            .override_quote_state(quote_id, state, reason, QuoteActor::Operator)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        tracing::warn!(
+            "Quote {} state overridden from {:?} to {:?}: {}",
+            quote_id,
+            previous.state,
+            state,
+            reason
+        );
+
+        if let Err(err) = self
+            .events
+            .publish(LspEventKind::QuoteStateChanged { quote_id, state })
+        {
+            tracing::error!("Failed to journal event: {}", err);
+        }
+
+        let quote = self
+            .db
+            .get_quote(quote_id)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(AdminSetQuoteStateResponse {
+            quote: Some(quote_record(&quote)),
+        }))
+    }
+
+    async fn retry_channel_open(
+        &self,
+        request: Request<RetryChannelOpenRequest>,
+    ) -> Result<Response<RetryChannelOpenResponse>, Status> {
+        let quote_id = parse_quote_id(&request.into_inner().quote_id)?;
+
+        let lsp = self
+            .lsp
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("LSP is not running"))?;
+
+        let channel_id = retry_channel_open(lsp, quote_id)
+            .await
+            .map_err(|err| match err {
+                LspError::QuoteNotFound(_) => Status::not_found(err.to_string()),
+                LspError::InvalidQuoteState { .. }
+                | LspError::PeerNotAllowed(_)
+                | LspError::AlreadyRefunded(_) => Status::failed_precondition(err.to_string()),
+                LspError::PeerUnreachable(_) => Status::unavailable(err.to_string()),
+                _ => Status::internal(err.to_string()),
+            })?;
+
+        Ok(Response::new(RetryChannelOpenResponse {
+            user_channel_id: channel_id.0.to_string(),
+        }))
+    }
+
+    async fn refund_quote(
+        &self,
+        request: Request<RefundQuoteRequest>,
+    ) -> Result<Response<RefundQuoteResponse>, Status> {
+        let req = request.into_inner();
+
+        let quote_id = parse_quote_id(&req.quote_id)?;
+
+        let invoice = req
+            .invoice
+            .map(|invoice| Bolt11Invoice::from_str(&invoice))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(format!("Invalid invoice: {}", e)))?;
+
+        let lsp = self
+            .lsp
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("LSP is not running"))?;
+
+        let refund = refund_quote(lsp, quote_id, req.amount_sats, invoice)
+            .await
+            .map_err(|err| match err {
+                LspError::QuoteNotFound(_) => Status::not_found(err.to_string()),
+                LspError::InvalidQuoteState { .. } | LspError::InvalidRefund(_) => {
+                    Status::failed_precondition(err.to_string())
+                }
+                LspError::AlreadyRefunded(_) => Status::already_exists(err.to_string()),
+                _ => Status::internal(err.to_string()),
+            })?;
+
+        Ok(Response::new(RefundQuoteResponse {
+            refund: Some(refund_record(refund)),
+        }))
+    }
+
+    async fn list_refunds(
+        &self,
+        request: Request<ListRefundsRequest>,
+    ) -> Result<Response<ListRefundsResponse>, Status> {
+        let refunds = match request.into_inner().quote_id {
+            Some(quote_id) => self
+                .db
+                .get_refund(parse_quote_id(&quote_id)?)
+                .map(|refund| refund.into_iter().collect()),
+            None => self.db.list_refunds(),
+        }
+        .map_err(|e| Status::internal(e.to_string()))?
+        .into_iter()
+        .map(refund_record)
+        .collect();
+
+        Ok(Response::new(ListRefundsResponse { refunds }))
+    }
+
+    async fn sign_message(
+        &self,
+        request: Request<SignMessageRequest>,
+    ) -> Result<Response<SignMessageResponse>, Status> {
+        let signature = self.node.inner.sign_message(&request.into_inner().message);
+
+        Ok(Response::new(SignMessageResponse { signature }))
+    }
+
+    async fn verify_message(
+        &self,
+        request: Request<VerifyMessageRequest>,
+    ) -> Result<Response<VerifyMessageResponse>, Status> {
+        let req = request.into_inner();
+
+        let pubkey = PublicKey::from_str(&req.pubkey)
+            .map_err(|_| Status::invalid_argument("Invalid pubkey"))?;
+
+        let valid = self
+            .node
+            .inner
+            .verify_signature(&req.message, &req.signature, &pubkey);
+
+        Ok(Response::new(VerifyMessageResponse { valid }))
+    }
+
+    async fn add_mint(
+        &self,
+        request: Request<AddMintRequest>,
+    ) -> Result<Response<AddMintResponse>, Status> {
+        let req = request.into_inner();
+
+        let lsp = self
+            .lsp
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("LSP is not running"))?;
+
+        let wallet_factory = self
+            .wallet_factory
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Mints cannot be added at runtime"))?;
+
+        let mint_url = MintUrl::from_str(&req.mint_url)
+            .map_err(|e| Status::invalid_argument(format!("Invalid mint url: {}", e)))?;
+
+        let info = lsp.info();
+
+        if info.accepted_mints.contains(&mint_url) {
+            return Err(Status::already_exists(format!(
+                "Mint {} is already accepted",
+                mint_url
+            )));
+        }
+
+        let mut wallets = vec![];
+
+        for unit in info.accepted_units.iter() {
+            // Wallets of removed mints are kept
+            let key = WalletKey::new(mint_url.clone(), unit.clone());
+
+            if self.node.wallet.get_wallet(&key).await.is_none() {
+                wallets.push(
+                    wallet_factory(&mint_url, unit).map_err(|e| Status::internal(e.to_string()))?,
+                );
+            }
+        }
+
+        if let Some(wallet) = wallets.first() {
+            let network = mint_network(wallet)
+                .await
+                .map_err(|e| Status::unavailable(format!("Could not reach mint: {}", e)))?;
+
+            if network != self.node.inner.config().network {
+                return Err(Status::failed_precondition(format!(
+                    "Mint {} is on {}",
+                    mint_url, network
+                )));
+            }
+        }
+
+        for wallet in wallets {
+            self.node.wallet.add_wallet(wallet).await;
+        }
+
+        self.db
+            .set_mint_accepted(&mint_url, true)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        // Checked again by the mint health monitor
+        lsp.mint_health.forget(&mint_url);
+
+        let info = lsp
+            .update_info(|info| {
+                if !info.accepted_mints.contains(&mint_url) {
+                    info.accepted_mints.push(mint_url.clone());
+                }
+                Ok(())
+            })
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        tracing::info!("Accepting mint {}", mint_url);
+
+        Ok(Response::new(AddMintResponse {
+            accepted_mints: info.accepted_mints.iter().map(|m| m.to_string()).collect(),
+        }))
+    }
+
+    async fn remove_mint(
+        &self,
+        request: Request<RemoveMintRequest>,
+    ) -> Result<Response<RemoveMintResponse>, Status> {
+        let req = request.into_inner();
+
+        let lsp = self
+            .lsp
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("LSP is not running"))?;
+
+        let mint_url = MintUrl::from_str(&req.mint_url)
+            .map_err(|e| Status::invalid_argument(format!("Invalid mint url: {}", e)))?;
+
+        // Disabled mints are not advertised but are still accepted
+        let disabled = lsp.mint_health.is_disabled(&mint_url);
+
+        let info = lsp
+            .update_info(|info| {
+                if info.accepted_mints.contains(&mint_url) {
+                    if info.accepted_mints.len() == 1 {
+                        anyhow::bail!("Cannot remove the last accepted mint");
+                    }
+                } else if !disabled {
+                    anyhow::bail!("Mint {} is not accepted", mint_url);
+                }
+
+                info.accepted_mints.retain(|m| m != &mint_url);
+                Ok(())
+            })
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        lsp.mint_health.forget(&mint_url);
+
+        self.db
+            .set_mint_accepted(&mint_url, false)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        tracing::info!("No longer accepting mint {}", mint_url);
+
+        Ok(Response::new(RemoveMintResponse {
+            accepted_mints: info.accepted_mints.iter().map(|m| m.to_string()).collect(),
+        }))
+    }
+
+    async fn update_lsp_info(
+        &self,
+        request: Request<UpdateLspInfoRequest>,
+    ) -> Result<Response<UpdateLspInfoResponse>, Status> {
+        let req = request.into_inner();
+
+        let lsp = self
+            .lsp
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("LSP is not running"))?;
+
+        let accepted_mints = match req.accepted_mints {
+            Some(accepted) => {
+                let mints = accepted
+                    .mint_urls
+                    .iter()
+                    .map(|m| MintUrl::from_str(m))
+                    .collect::<Result<Vec<MintUrl>, _>>()
+                    .map_err(|e| Status::invalid_argument(format!("Invalid mint url: {}", e)))?;
+
+                let wallets = self.node.wallet.get_wallets().await;
+
+                if let Some(mint) = mints
+                    .iter()
+                    .find(|m| !wallets.iter().any(|w| &w.mint_url == *m))
+                {
+                    return Err(Status::failed_precondition(format!(
+                        "No wallet for {}, add it with AddMint",
+                        mint
+                    )));
+                }
+
+                Some(mints)
+            }
+            None => None,
+        };
+
+        let disabled_mints = lsp.mint_health.disabled();
+
+        // Disabled mints that stay accepted are added back once they recover
+        let dropped_mints: Vec<MintUrl> = match accepted_mints.as_ref() {
+            Some(mints) => disabled_mints
+                .keys()
+                .filter(|m| !mints.contains(m))
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+
+        // Accepted before the update, including mints disabled for now
+        let mut previous_mints: Vec<MintUrl> = disabled_mints.keys().cloned().collect();
+
+        let info = lsp
+            .update_info(|info| {
+                previous_mints.extend(info.accepted_mints.iter().cloned());
+
+                if let Some(min) = req.min_channel_size_sats {
+                    info.min_channel_size_sat = min;
+                }
+
+                if let Some(max) = req.max_channel_size_sats {
+                    info.max_channel_size_sat = max;
+                }
+
+                if info.min_channel_size_sat > info.max_channel_size_sat {
+                    anyhow::bail!("Min channel size above max channel size");
+                }
+
+                if info.max_channel_size_sat > MAX_MONEY_SATS {
+                    anyhow::bail!("Max channel size above the bitcoin supply");
+                }
+
+                if let Some(min_fee) = req.min_fee {
+                    info.min_fee = min_fee;
+                }
+
+                if let Some(fee_ppk) = req.fee_ppk {
+                    info.fee_ppk = fee_ppk;
+                }
+
+                // A fee can't be more than the channel it pays for
+                if info.fee_ppk > 1_000 {
+                    anyhow::bail!("Fee above 1000 ppk");
+                }
+
+                if info.min_fee > info.max_channel_size_sat {
+                    anyhow::bail!("Min fee above max channel size");
+                }
+
+                // Mint fee policies are kept so they apply again if a mint is re-added
+                if let Some(mints) = accepted_mints.clone() {
+                    if mints.is_empty() {
+                        anyhow::bail!("At least one mint must be accepted");
+                    }
+
+                    info.accepted_mints = mints
+                        .into_iter()
+                        .filter(|m| !disabled_mints.contains_key(m))
+                        .collect();
+                }
+
+                Ok(())
+            })
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        // Persisted like AddMint and RemoveMint so a restart keeps the change
+        if let Some(mints) = accepted_mints.as_ref() {
+            for mint in mints.iter().filter(|m| !previous_mints.contains(m)) {
+                self.db
+                    .set_mint_accepted(mint, true)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            }
+
+            for mint in previous_mints.iter().filter(|m| !mints.contains(m)) {
+                self.db
+                    .set_mint_accepted(mint, false)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            }
+        }
+
+        for mint in dropped_mints.iter() {
+            lsp.mint_health.forget(mint);
+        }
+
+        tracing::info!(
+            "LSP info updated: channels {}-{} sats, fee {} + {} ppk, {} mints",
+            info.min_channel_size_sat,
+            info.max_channel_size_sat,
+            info.min_fee,
+            info.fee_ppk,
+            info.accepted_mints.len()
+        );
+
+        Ok(Response::new(UpdateLspInfoResponse {
+            min_channel_size_sats: info.min_channel_size_sat,
+            max_channel_size_sats: info.max_channel_size_sat,
+            min_fee: info.min_fee,
+            fee_ppk: info.fee_ppk,
+            accepted_mints: info.accepted_mints.iter().map(|m| m.to_string()).collect(),
+        }))
+    }
+
+    async fn stop_node(
+        &self,
+        _request: Request<StopNodeRequest>,
+    ) -> Result<Response<StopNodeResponse>, Status> {
+        let shutdown = self
+            .shutdown
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Process control is not enabled"))?;
+
+        tracing::info!("Stop requested over gRPC");
+        shutdown.stop();
+
+        Ok(Response::new(StopNodeResponse {}))
+    }
+
+    async fn restart_node(
+        &self,
+        _request: Request<RestartNodeRequest>,
+    ) -> Result<Response<RestartNodeResponse>, Status> {
+        let shutdown = self
+            .shutdown
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Process control is not enabled"))?;
+
+        tracing::info!("Restart requested over gRPC");
+        shutdown.restart();
+
+        Ok(Response::new(RestartNodeResponse {}))
+    }
+
+    async fn list_peer_sla(
+        &self,
+        _request: Request<ListPeerSlaRequest>,
+    ) -> Result<Response<ListPeerSlaResponse>, Status> {
+        let peers = self
+            .db
+            .list_peer_sla()
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter()
+            .map(|sla| PeerSla {
+                node_pubkey: sla.node_pubkey.to_string(),
+                samples: sla.samples,
+                connected_samples: sla.connected_samples,
+                usable_samples: sla.usable_samples,
+                uptime_percent: sla.uptime_percent(),
+                last_sample: sla.last_sample,
+                forwarded_payments: sla.forwarded_payments,
+                forwarded_msats: sla.forwarded_msats,
+                closed_channels: sla.closed_channels,
+            })
+            .collect();
+
+        Ok(Response::new(ListPeerSlaResponse { peers }))
+    }
+
+    async fn get_fee_revenue(
+        &self,
+        request: Request<GetFeeRevenueRequest>,
+    ) -> Result<Response<GetFeeRevenueResponse>, Status> {
+        let req = request.into_inner();
+
+        let since = req.since.unwrap_or_default();
+        let until = req.until.unwrap_or_else(cdk::util::unix_time);
+
+        if since >= until {
+            return Err(Status::invalid_argument("since must be before until"));
+        }
+
+        if req.window_secs == Some(0) {
+            return Err(Status::invalid_argument("Window must be at least a second"));
+        }
+
+        let entries = self
+            .db
+            .list_fee_entries(since, until)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut total = FeeRevenue {
+            start: since,
+            end: until,
+            ..Default::default()
+        };
+        let mut windows: BTreeMap<u64, FeeRevenue> = BTreeMap::new();
+
+        for entry in entries.iter() {
+            add_fee_revenue(&mut total, entry);
+
+            if let Some(window_secs) = req.window_secs {
+                let start = since + (entry.recorded_at - since) / window_secs * window_secs;
+
+                let window = windows.entry(start).or_insert_with(|| FeeRevenue {
+                    start,
+                    end: start.saturating_add(window_secs).min(until),
+                    ..Default::default()
+                });
+
+                add_fee_revenue(window, entry);
+            }
+        }
+
+        Ok(Response::new(GetFeeRevenueResponse {
+            total: Some(total),
+            windows: windows.into_values().collect(),
+        }))
+    }
+
+    async fn set_peer_access(
+        &self,
+        request: Request<SetPeerAccessRequest>,
+    ) -> Result<Response<SetPeerAccessResponse>, Status> {
+        let req = request.into_inner();
+
+        let node_pubkey = PublicKey::from_str(&req.node_pubkey)
+            .map_err(|_| Status::invalid_argument("Invalid node pubkey"))?;
+
+        let access = match PeerAccessLevel::try_from(req.access)
+            .map_err(|_| Status::invalid_argument(format!("Invalid access: {}", req.access)))?
+        {
+            PeerAccessLevel::None => None,
+            PeerAccessLevel::Allow => Some(PeerAccess::Allow),
+            PeerAccessLevel::Deny => Some(PeerAccess::Deny),
+        };
+
+        self.db
+            .set_peer_access(node_pubkey, access)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(SetPeerAccessResponse {}))
+    }
+
+    async fn list_peer_access(
+        &self,
+        _request: Request<ListPeerAccessRequest>,
+    ) -> Result<Response<ListPeerAccessResponse>, Status> {
+        let runtime = self
+            .db
+            .list_peer_access()
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        // Config entries overridden at runtime are left out
+        let configured = self
+            .lsp
+            .iter()
+            .flat_map(|lsp| lsp.peer_access.iter())
+            .filter(|(pk, _)| !runtime.iter().any(|(runtime_pk, _)| runtime_pk == *pk))
+            .map(|(pk, access)| (*pk, *access, true));
+
+        let peers = runtime
+            .iter()
+            .map(|(pk, access)| (*pk, *access, false))
+            .chain(configured)
+            .map(|(node_pubkey, access, from_config)| PeerAccessEntry {
+                node_pubkey: node_pubkey.to_string(),
+                access: match access {
+                    PeerAccess::Allow => PeerAccessLevel::Allow,
+                    PeerAccess::Deny => PeerAccessLevel::Deny,
+                } as i32,
+                from_config,
+            })
+            .collect();
+
+        Ok(Response::new(ListPeerAccessResponse { peers }))
+    }
+}
+
+/// Node channel config with the overrides in `options`, if any
+fn options_channel_config(options: &ChannelOptions) -> Result<Option<ChannelConfig>, Status> {
+    if options.forwarding_fee_base_msat.is_none()
+        && options.forwarding_fee_ppm.is_none()
+        && options.cltv_expiry_delta.is_none()
+    {
+        return Ok(None);
+    }
+
+    let mut config = ChannelConfig::default();
+
+    if let Some(base_msat) = options.forwarding_fee_base_msat {
+        config.forwarding_fee_base_msat = base_msat;
+    }
+
+    if let Some(ppm) = options.forwarding_fee_ppm {
+        config.forwarding_fee_proportional_millionths = ppm;
+    }
+
+    if let Some(delta) = options.cltv_expiry_delta {
+        config.cltv_expiry_delta = u16::try_from(delta).map_err(|_| {
+            Status::invalid_argument(format!("Invalid cltv expiry delta: {}", delta))
+        })?;
+    }
+
+    Ok(Some(config))
+}
+
+fn quote_status(state: QuoteState) -> QuoteStatus {
+    match state {
+        QuoteState::Unpaid => QuoteStatus::Unpaid,
+        QuoteState::Paid => QuoteStatus::Paid,
+        QuoteState::ChannelPending => QuoteStatus::ChannelPending,
+        QuoteState::ChannelOpen => QuoteStatus::ChannelOpen,
+        QuoteState::ChannelExpired => QuoteStatus::ChannelExpired,
+    }
+}
+
+fn quote_record(quote: &QuoteInfo) -> QuoteRecord {
+    QuoteRecord {
+        id: quote.id.to_string(),
+        state: quote_status(quote.state) as i32,
+        node_pubkey: quote.node_pubkey.to_string(),
+        addr: quote.addr.to_string(),
+        channel_size_sats: quote.channel_size_sats,
+        push_amount_sats: quote.push_amount_sats,
+        expected_payment_sats: quote.expected_payment_sats,
+        service_fee_sats: quote.service_fee_sats(),
+        mode: match quote.mode {
+            QuoteMode::Funded => "funded",
+            QuoteMode::InboundOnly => "inbound_only",
+        }
+        .to_string(),
+        unit: quote.unit.to_string(),
+        mints: quote.mints.iter().map(|mint| mint.to_string()).collect(),
+        created_at: quote.created_at,
+        user_channel_id: quote.channel_id.map(|id| id.0.to_string()),
+        payment_error: quote.payment_error.clone(),
+        channel_id: quote.channel.as_ref().map(|c| c.channel_id.clone()),
+        funding_txid: quote.channel.as_ref().map(|c| c.funding_txid.clone()),
+        funding_output_index: quote.channel.as_ref().map(|c| c.funding_output_index),
+        short_channel_id: quote.channel.as_ref().and_then(|c| c.short_channel_id),
+        opened_at: quote.channel.as_ref().map(|c| c.opened_at),
+        ready_at: quote.channel.as_ref().and_then(|c| c.ready_at),
+        closed_at: quote.channel.as_ref().and_then(|c| c.closed_at),
+        close_reason: quote.channel.as_ref().and_then(|c| c.close_reason.clone()),
+    }
+}
+
+const QUOTES_CSV_HEADER: &str = "id,state,node_pubkey,addr,mode,unit,channel_size_sats,push_amount_sats,expected_payment_sats,service_fee_sats,created_at,funding_txid,funding_output_index,short_channel_id,closed_at";
+
+/// Quote as a line of CSV, none of the exported fields contain separators
+/// One page of a quote export, as JSON lines or CSV rows
+fn export_chunk(quotes: &[QuoteInfo], csv: bool) -> Result<ExportQuotesChunk, Status> {
+    let data = if csv {
+        quotes.iter().map(quote_csv_row).collect()
+    } else {
+        quotes
+            .iter()
+            .map(|quote| serde_json::to_string(quote).map(|line| line + "\n"))
+            .collect::<Result<String, _>>()
+            .map_err(|e| Status::internal(e.to_string()))?
+    };
+
+    Ok(ExportQuotesChunk {
+        data: data.into_bytes(),
+    })
+}
+
+fn quote_csv_row(quote: &QuoteInfo) -> String {
+    let channel = quote.channel.as_ref();
+
+    format!(
+        "{},{:?},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+        quote.id,
+        quote.state,
+        quote.node_pubkey,
+        quote.addr,
+        match quote.mode {
+            QuoteMode::Funded => "funded",
+            QuoteMode::InboundOnly => "inbound_only",
+        },
+        quote.unit,
+        quote.channel_size_sats,
+        quote
+            .push_amount_sats
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+        quote.expected_payment_sats,
+        quote.service_fee_sats(),
+        quote.created_at,
+        channel.map(|c| c.funding_txid.clone()).unwrap_or_default(),
+        channel
+            .map(|c| c.funding_output_index.to_string())
+            .unwrap_or_default(),
+        channel
+            .and_then(|c| c.short_channel_id)
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+        channel
+            .and_then(|c| c.closed_at)
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+fn add_fee_revenue(revenue: &mut FeeRevenue, entry: &FeeLedgerEntry) {
+    // Bond entries are recorded apart from the channel's entry
+    if entry.bond_sats == 0 {
+        revenue.quotes += 1;
+    }
+
+    revenue.service_fee_sats = revenue
+        .service_fee_sats
+        .saturating_add(entry.service_fee_sats);
+    revenue.onchain_fee_sats = revenue
+        .onchain_fee_sats
+        .saturating_add(entry.onchain_fee_sats);
+    revenue.mint_fee_sats = revenue.mint_fee_sats.saturating_add(entry.mint_fee_sats);
+    revenue.bond_sats = revenue.bond_sats.saturating_add(entry.bond_sats);
+    revenue.revenue_sats = revenue.revenue_sats.saturating_add(entry.revenue_sats);
+}
+
+fn refund_record(refund: QuoteRefund) -> RefundRecord {
+    RefundRecord {
+        quote_id: refund.quote_id.to_string(),
+        amount_sats: refund.amount_sats,
+        method: match refund.method {
+            RefundMethod::Ecash => "ecash",
+            RefundMethod::Lightning => "lightning",
+        }
+        .to_string(),
+        token: refund.token,
+        invoice: refund.invoice,
+        created_at: refund.created_at,
+        claimed_at: refund.claimed_at,
+    }
+}
+
+fn quote_transition(transition: QuoteStateTransition) -> QuoteTransition {
+    QuoteTransition {
+        state: quote_status(transition.state) as i32,
+        timestamp: transition.timestamp,
+        reason: transition.reason,
+    }
+}
+
+fn quote_audit_record(entry: QuoteAuditEntry) -> QuoteAuditRecord {
+    let actor = match entry.actor {
+        QuoteActor::Client => QuoteAuditActor::Client,
+        QuoteActor::Lsp => QuoteAuditActor::Lsp,
+        QuoteActor::Node => QuoteAuditActor::Node,
+        QuoteActor::Operator => QuoteAuditActor::Operator,
+    };
+
+    QuoteAuditRecord {
+        seq: entry.seq,
+        quote_id: entry.quote_id.to_string(),
+        from_state: entry.from.map_or(QuoteStatus::Unspecified, quote_status) as i32,
+        to_state: quote_status(entry.to) as i32,
+        timestamp: entry.timestamp,
+        reason: entry.reason,
+        actor: actor as i32,
+    }
+}
+
+/// Manual recoveries operators may make
+///
+/// A stuck `ChannelPending` quote can be marked open or set back to paid, and
+/// any quote that isn't finished can be expired. Anything else could let a
+/// quote be paid or opened twice.
+fn override_allowed(from: QuoteState, to: QuoteState) -> bool {
+    matches!(
+        (from, to),
+        (
+            QuoteState::ChannelPending,
+            QuoteState::ChannelOpen | QuoteState::Paid
+        ) | (
+            QuoteState::Unpaid | QuoteState::Paid | QuoteState::ChannelPending,
+            QuoteState::ChannelExpired
+        )
+    )
+}
+
+fn parse_quote_id(quote_id: &str) -> Result<Uuid, Status> {
+    Uuid::from_str(quote_id).map_err(|_| Status::invalid_argument("Invalid quote id"))
+}
+
+/// Parse a `<created_at>:<quote id>` page token
+fn parse_page_token(token: &str) -> Result<(u64, Uuid), Status> {
+    let invalid = || Status::invalid_argument(format!("Invalid page token: {}", token));
+
+    let (created_at, id) = token.split_once(':').ok_or_else(invalid)?;
+
+    Ok((
+        created_at.parse().map_err(|_| invalid())?,
+        Uuid::from_str(id).map_err(|_| invalid())?,
+    ))
+}
+
+fn decode_bolt11(invoice: &Bolt11Invoice) -> DecodedBolt11 {
+    DecodedBolt11 {
+        payment_hash: invoice.payment_hash().to_string(),
+        amount_msats: invoice.amount_milli_satoshis(),
+        description: match invoice.description() {
+            Bolt11InvoiceDescription::Direct(description) => description.to_string(),
+            Bolt11InvoiceDescription::Hash(hash) => hash.0.to_string(),
+        },
+        payee_pubkey: invoice.get_payee_pub_key().to_string(),
+        network: invoice.network().to_string(),
+        timestamp: invoice.duration_since_epoch().as_secs(),
+        expiry_secs: invoice.expiry_time().as_secs(),
+        is_expired: invoice.is_expired(),
+    }
+}
+
+fn decode_offer(offer: &Offer) -> DecodedBolt12Offer {
+    DecodedBolt12Offer {
+        offer_id: offer.id().0.to_lower_hex_string(),
+        amount_msats: match offer.amount() {
+            Some(OfferAmount::Bitcoin { amount_msats }) => Some(amount_msats),
+            _ => None,
+        },
+        description: offer.description().map(|d| d.to_string()),
+        issuer: offer.issuer().map(|i| i.to_string()),
+        signing_pubkey: offer.signing_pubkey().map(|pk| pk.to_string()),
+        absolute_expiry: offer.absolute_expiry().map(|expiry| expiry.as_secs()),
+    }
+}
+
+fn decode_cashu_request(payment_request: PaymentRequest) -> DecodedCashuPaymentRequest {
+    DecodedCashuPaymentRequest {
+        payment_id: payment_request.payment_id,
+        amount: payment_request.amount.map(u64::from),
+        unit: payment_request.unit.map(|unit| unit.to_string()),
+        single_use: payment_request.single_use,
+        mints: payment_request
+            .mints
+            .unwrap_or_default()
+            .iter()
+            .map(|mint| mint.to_string())
+            .collect(),
+        description: payment_request.description,
+        transport_targets: payment_request
+            .transports
+            .into_iter()
+            .map(|transport| transport.target)
+            .collect(),
+    }
+}
+
+fn payment_info(payment: PaymentDetails) -> PaymentInfo {
+    let (kind, hash, preimage) = match payment.kind {
+        PaymentKind::Onchain { .. } => ("onchain", None, None),
+        PaymentKind::Bolt11 { hash, preimage, .. } => ("bolt11", Some(hash), preimage),
+        PaymentKind::Bolt11Jit { hash, preimage, .. } => ("bolt11_jit", Some(hash), preimage),
+        PaymentKind::Bolt12Offer { hash, preimage, .. } => ("bolt12_offer", hash, preimage),
+        PaymentKind::Bolt12Refund { hash, preimage, .. } => ("bolt12_refund", hash, preimage),
+        PaymentKind::Spontaneous { hash, preimage } => ("spontaneous", Some(hash), preimage),
+    };
+
+    PaymentInfo {
+        id: payment.id.0.to_lower_hex_string(),
+        kind: kind.to_string(),
+        direction: match payment.direction {
+            PaymentDirection::Inbound => "inbound",
+            PaymentDirection::Outbound => "outbound",
+        }
+        .to_string(),
+        status: payment_status(payment.status),
+        amount_msats: payment.amount_msat,
+        payment_hash: hash.map(|h| h.0.to_lower_hex_string()),
+        preimage: preimage.map(|p| p.0.to_lower_hex_string()),
+        latest_update_timestamp: payment.latest_update_timestamp,
+    }
+}
+
+fn payment_status(status: PaymentStatus) -> String {
+    match status {
+        PaymentStatus::Pending => "pending",
+        PaymentStatus::Succeeded => "succeeded",
+        PaymentStatus::Failed => "failed",
+    }
+    .to_string()
 }
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use cdk::nuts::CurrencyUnit;
+use tokio_util::sync::CancellationToken;
+
+const SATS_PER_BTC: f64 = 100_000_000.0;
+const RATE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Source of the bitcoin price in a fiat or stablecoin unit
+#[async_trait]
+pub trait RateSource: Send + Sync {
+    /// Price of one bitcoin in whole units, e.g. dollars
+    async fn btc_price(&self) -> Result<f64>;
+}
+
+/// Price that never changes, for testing and pegged units
+#[derive(Debug, Clone, Copy)]
+pub struct FixedRate {
+    pub btc_price: f64,
+}
+
+#[async_trait]
+impl RateSource for FixedRate {
+    async fn btc_price(&self) -> Result<f64> {
+        Ok(self.btc_price)
+    }
+}
+
+/// Price read from a JSON HTTP endpoint
+///
+/// `pointer` is a JSON pointer to the price, which may be a number or a
+/// numeric string, e.g. `/data/amount` for the Coinbase spot price API.
+#[derive(Debug, Clone)]
+pub struct HttpRateSource {
+    client: reqwest::Client,
+    pub url: String,
+    pub pointer: String,
+}
+
+impl HttpRateSource {
+    pub fn new(url: String, pointer: String) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(RATE_REQUEST_TIMEOUT)
+                .build()?,
+            url,
+            pointer,
+        })
+    }
+}
+
+#[async_trait]
+impl RateSource for HttpRateSource {
+    async fn btc_price(&self) -> Result<f64> {
+        let body: serde_json::Value = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let price = match body.pointer(&self.pointer) {
+            Some(serde_json::Value::Number(n)) => n.as_f64(),
+            Some(serde_json::Value::String(s)) => s.parse().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("No price at {} in response of {}", self.pointer, self.url))?;
+
+        if !price.is_finite() || price <= 0.0 {
+            bail!("Invalid price {} from {}", price, self.url);
+        }
+
+        Ok(price)
+    }
+}
+
+/// Rate source for a unit and how many of the unit make up a whole one
+#[derive(Clone)]
+pub struct UnitRate {
+    pub source: Arc<dyn RateSource>,
+    /// Smallest denominations per whole unit, 100 for cents
+    pub minor_units: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedRate {
+    sats_per_unit: f64,
+    fetched_at: Instant,
+}
+
+/// Cached exchange rates for the non-bitcoin units the LSP accepts
+///
+/// Payments are converted at the cached rate, which is refreshed in the
+/// background so payment handling never waits on a rate source. A rate that
+/// could not be refreshed for `max_age` is no longer used.
+#[derive(Clone)]
+pub struct ExchangeRates {
+    units: HashMap<CurrencyUnit, UnitRate>,
+    /// Parts per thousand a payment may fall short after converting at the current rate
+    pub slippage_ppk: u64,
+    pub refresh_interval: Duration,
+    pub max_age: Duration,
+    cache: Arc<RwLock<HashMap<CurrencyUnit, CachedRate>>>,
+}
+
+impl ExchangeRates {
+    pub fn new(
+        units: HashMap<CurrencyUnit, UnitRate>,
+        slippage_ppk: u64,
+        refresh_interval: Duration,
+        max_age: Duration,
+    ) -> Self {
+        Self {
+            units,
+            slippage_ppk,
+            refresh_interval,
+            max_age,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Units rates are known for
+    pub fn units(&self) -> impl Iterator<Item = &CurrencyUnit> {
+        self.units.keys()
+    }
+
+    /// Sats one minor unit of `unit` is worth, `None` until fetched or once older than `max_age`
+    pub fn sats_per_unit(&self, unit: &CurrencyUnit) -> Option<f64> {
+        self.cache
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(unit)
+            .filter(|rate| rate.fetched_at.elapsed() <= self.max_age)
+            .map(|rate| rate.sats_per_unit)
+    }
+
+    /// Fetch the current rate of every unit
+    pub async fn refresh(&self) {
+        for (unit, rate) in self.units.iter() {
+            match rate.source.btc_price().await {
+                Ok(price) => {
+                    let sats_per_unit = SATS_PER_BTC / (price * rate.minor_units as f64);
+
+                    self.cache
+                        .write()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .insert(
+                            unit.clone(),
+                            CachedRate {
+                                sats_per_unit,
+                                fetched_at: Instant::now(),
+                            },
+                        );
+                }
+                // The last known rate is kept until it is too old
+                Err(err) => tracing::warn!("Could not fetch {} rate: {}", unit, err),
+            }
+        }
+    }
+
+    /// Refresh rates every `refresh_interval` until cancelled
+    pub async fn keep_fresh(self, cancel_token: CancellationToken) {
+        loop {
+            self.refresh().await;
+
+            tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                _ = tokio::time::sleep(self.refresh_interval) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingRate;
+
+    #[async_trait]
+    impl RateSource for FailingRate {
+        async fn btc_price(&self) -> Result<f64> {
+            bail!("Rate source unavailable")
+        }
+    }
+
+    fn rates(source: Arc<dyn RateSource>, max_age: Duration) -> ExchangeRates {
+        let units = HashMap::from([(
+            CurrencyUnit::Usd,
+            UnitRate {
+                source,
+                minor_units: 100,
+            },
+        )]);
+
+        ExchangeRates::new(units, 10, Duration::from_secs(60), max_age)
+    }
+
+    #[tokio::test]
+    async fn converts_fetched_price_to_sats_per_minor_unit() {
+        let rates = rates(
+            Arc::new(FixedRate {
+                btc_price: 1_000_000.0,
+            }),
+            Duration::from_secs(600),
+        );
+
+        assert_eq!(rates.sats_per_unit(&CurrencyUnit::Usd), None);
+
+        rates.refresh().await;
+
+        assert_eq!(rates.sats_per_unit(&CurrencyUnit::Usd), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn stale_rates_are_not_used() {
+        let rates = rates(
+            Arc::new(FixedRate {
+                btc_price: 1_000_000.0,
+            }),
+            Duration::ZERO,
+        );
+
+        rates.refresh().await;
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(rates.sats_per_unit(&CurrencyUnit::Usd), None);
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_keeps_last_rate() {
+        let mut rates = rates(
+            Arc::new(FixedRate {
+                btc_price: 1_000_000.0,
+            }),
+            Duration::from_secs(600),
+        );
+
+        rates.refresh().await;
+
+        rates.units.insert(
+            CurrencyUnit::Usd,
+            UnitRate {
+                source: Arc::new(FailingRate),
+                minor_units: 100,
+            },
+        );
+
+        rates.refresh().await;
+
+        assert_eq!(rates.sats_per_unit(&CurrencyUnit::Usd), Some(1.0));
+    }
+}
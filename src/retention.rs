@@ -0,0 +1,427 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::LspStore;
+use crate::lsp_server::CashuLspState;
+use crate::types::{
+    QuoteAuditEntry, QuoteInfo, QuotePayment, QuoteRefund, QuoteState, QuoteStateTransition,
+    SignedReceipt,
+};
+
+/// How often quotes are pruned by default
+pub const DEFAULT_RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Audit entries read per page when archiving
+const AUDIT_PAGE_SIZE: usize = 1000;
+
+/// How long quotes and journal events are kept
+///
+/// Quotes in `ChannelOpen` or `ChannelExpired` are deleted with their history,
+/// audit entries, payment, receipt and refund once they have been in that state for
+/// `max_age`, and `Unpaid` quotes once they have been unpaid for `unpaid_max_age`.
+/// Quotes still holding locked proofs, or whose channel is still open, are kept.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Unset keeps `ChannelOpen` and `ChannelExpired` quotes
+    pub max_age: Option<Duration>,
+    /// Unset keeps `Unpaid` quotes
+    pub unpaid_max_age: Option<Duration>,
+    /// Unset keeps journal events
+    pub event_max_age: Option<Duration>,
+    /// JSON lines file pruned quotes are appended to before deleting, unset only deletes
+    pub archive_path: Option<PathBuf>,
+    pub interval: Duration,
+}
+
+/// Pruned quote as written to the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedQuote {
+    pub quote: QuoteInfo,
+    pub history: Vec<QuoteStateTransition>,
+    pub payment: Option<QuotePayment>,
+    pub receipt: Option<SignedReceipt>,
+    pub refund: Option<QuoteRefund>,
+    #[serde(default)]
+    pub audit: Vec<QuoteAuditEntry>,
+    /// Unix time the quote was pruned
+    pub archived_at: u64,
+}
+
+/// Prune quotes past `policy` until the node is stopped
+pub(crate) async fn enforce_retention(state: CashuLspState, policy: RetentionPolicy) {
+    let cancel_token = state.node.events_cancel_token.clone();
+
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            _ = tokio::time::sleep(policy.interval) => apply_retention(&state, &policy),
+        }
+    }
+}
+
+fn apply_retention(state: &CashuLspState, policy: &RetentionPolicy) {
+    let open_channels: HashSet<u128> = state
+        .node
+        .inner
+        .list_channels()
+        .iter()
+        .map(|c| c.user_channel_id.0)
+        .collect();
+
+    match prune_quotes(
+        state.db.as_ref(),
+        state.clock.as_ref(),
+        policy,
+        &open_channels,
+    ) {
+        Ok(0) => {}
+        Ok(pruned) => tracing::info!("Pruned {} quotes past retention", pruned),
+        Err(err) => tracing::error!("Could not prune quotes: {}", err),
+    }
+
+    match prune_events(state.db.as_ref(), state.clock.as_ref(), policy) {
+        Ok(0) => {}
+        Ok(pruned) => tracing::info!("Pruned {} journal events past retention", pruned),
+        Err(err) => tracing::error!("Could not prune journal events: {}", err),
+    }
+}
+
+/// Archive and delete quotes older than the policy allows
+///
+/// `open_channels` are the user channel ids the node still has, their quotes
+/// are kept so later closes and forwards can be attributed. Returns the number
+/// of quotes pruned.
+pub(crate) fn prune_quotes(
+    db: &dyn LspStore,
+    clock: &dyn Clock,
+    policy: &RetentionPolicy,
+    open_channels: &HashSet<u128>,
+) -> Result<usize> {
+    let now = clock.now();
+
+    let mut archived = vec![];
+
+    for quote in db.list_quotes()? {
+        let max_age = match quote.state {
+            QuoteState::ChannelOpen | QuoteState::ChannelExpired => policy.max_age,
+            QuoteState::Unpaid => policy.unpaid_max_age,
+            QuoteState::Paid | QuoteState::ChannelPending => None,
+        };
+
+        let Some(max_age) = max_age else {
+            continue;
+        };
+
+        let channel_open = quote
+            .channel_id
+            .is_some_and(|id| open_channels.contains(&id.0))
+            || quote
+                .channel
+                .as_ref()
+                .is_some_and(|c| c.closed_at.is_none());
+
+        if channel_open {
+            continue;
+        }
+
+        let history = db.list_quote_history(quote.id, 0, None)?;
+
+        // Quotes from before history was recorded only have their creation time
+        let state_since = history
+            .last()
+            .map(|t| t.timestamp)
+            .unwrap_or(quote.created_at);
+
+        if state_since > now.saturating_sub(max_age.as_secs()) {
+            continue;
+        }
+
+        // Locked proofs are still owed to the LSP or the payer
+        if db.get_locked_payment(quote.id)?.is_some() || db.is_payment_pending(quote.id)? {
+            continue;
+        }
+
+        archived.push(ArchivedQuote {
+            payment: db.get_quote_payment(quote.id)?,
+            receipt: db.get_receipt(quote.id)?,
+            refund: db.get_refund(quote.id)?,
+            audit: vec![],
+            quote,
+            history,
+            archived_at: now,
+        });
+    }
+
+    if archived.is_empty() {
+        return Ok(0);
+    }
+
+    if let Some(path) = policy.archive_path.as_ref() {
+        add_audit_entries(db, &mut archived)?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        for quote in archived.iter() {
+            writeln!(file, "{}", serde_json::to_string(quote)?)?;
+        }
+
+        // Only delete what is safely archived
+        file.sync_all()?;
+    }
+
+    let quote_ids: Vec<Uuid> = archived.iter().map(|a| a.quote.id).collect();
+
+    db.remove_quotes(&quote_ids)?;
+
+    Ok(quote_ids.len())
+}
+
+/// Fill in the audit entries of quotes about to be archived
+///
+/// Pages through the audit log once rather than once per quote.
+fn add_audit_entries(db: &dyn LspStore, archived: &mut [ArchivedQuote]) -> Result<()> {
+    let ids: HashSet<Uuid> = archived.iter().map(|a| a.quote.id).collect();
+
+    let mut audit: Vec<QuoteAuditEntry> = vec![];
+    let mut cursor = 0;
+
+    loop {
+        let page = db.list_quote_audit(None, cursor, AUDIT_PAGE_SIZE)?;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+        cursor = last.seq;
+
+        audit.extend(page.into_iter().filter(|e| ids.contains(&e.quote_id)));
+    }
+
+    for quote in archived.iter_mut() {
+        quote.audit = audit
+            .iter()
+            .filter(|e| e.quote_id == quote.quote.id)
+            .cloned()
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Delete journal events older than the policy allows
+pub(crate) fn prune_events(
+    db: &dyn LspStore,
+    clock: &dyn Clock,
+    policy: &RetentionPolicy,
+) -> Result<usize> {
+    let Some(max_age) = policy.event_max_age else {
+        return Ok(0);
+    };
+
+    db.remove_events_before(clock.now().saturating_sub(max_age.as_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::db::{Db, TempPath};
+    use crate::types::{LspEventKind, QuoteActor, QuoteChannel};
+    use ldk_node::UserChannelId;
+
+    const DAY: u64 = 86_400;
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: Some(Duration::from_secs(30 * DAY)),
+            unpaid_max_age: None,
+            event_max_age: None,
+            archive_path: None,
+            interval: DEFAULT_RETENTION_INTERVAL,
+        }
+    }
+
+    fn prune(db: &Db, clock: &ManualClock, policy: &RetentionPolicy) -> usize {
+        prune_quotes(db, clock, policy, &HashSet::new()).unwrap()
+    }
+
+    /// The file is removed when the returned path is dropped
+    fn temp_db(clock: Arc<ManualClock>) -> (Db, TempPath) {
+        let file = TempPath::new("retention.redb");
+        let db = Db::new(file.path().to_path_buf())
+            .unwrap()
+            .with_clock(clock);
+
+        (db, file)
+    }
+
+    fn add_quote(db: &Db, clock: &ManualClock, id: u128, state: QuoteState) -> Uuid {
+        let id = Uuid::from_u128(id);
+
+        db.add_quote(&QuoteInfo::for_test(id, clock.now()), QuoteActor::Client)
+            .unwrap();
+
+        if state != QuoteState::Unpaid {
+            db.update_quote_state(id, state, QuoteActor::Lsp).unwrap();
+        }
+
+        id
+    }
+
+    #[test]
+    fn prunes_terminal_quotes_past_max_age() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let old_open = add_quote(&db, &clock, 1, QuoteState::ChannelOpen);
+        let old_unpaid = add_quote(&db, &clock, 2, QuoteState::Unpaid);
+
+        clock.advance(20 * DAY);
+
+        let recent_expired = add_quote(&db, &clock, 3, QuoteState::ChannelExpired);
+
+        clock.advance(15 * DAY);
+
+        assert_eq!(prune(&db, &clock, &policy()), 1);
+
+        assert!(db.get_quote(old_open).is_err());
+        assert!(db.list_quote_history(old_open, 0, None).unwrap().is_empty());
+        assert!(db.get_quote(old_unpaid).is_ok());
+        assert!(db.get_quote(recent_expired).is_ok());
+
+        clock.advance(15 * DAY);
+
+        assert_eq!(prune(&db, &clock, &policy()), 1);
+        assert!(db.get_quote(recent_expired).is_err());
+    }
+
+    #[test]
+    fn age_counts_from_reaching_terminal_state() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let id = add_quote(&db, &clock, 1, QuoteState::Unpaid);
+
+        clock.advance(60 * DAY);
+        db.update_quote_state(id, QuoteState::ChannelOpen, QuoteActor::Node)
+            .unwrap();
+
+        clock.advance(DAY);
+
+        assert_eq!(prune(&db, &clock, &policy()), 0);
+        assert!(db.get_quote(id).is_ok());
+    }
+
+    #[test]
+    fn keeps_quotes_with_open_channels() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let listed = add_quote(&db, &clock, 1, QuoteState::ChannelOpen);
+        db.set_quote_channel_id(listed, Some(UserChannelId(1)))
+            .unwrap();
+
+        let unclosed = add_quote(&db, &clock, 2, QuoteState::ChannelOpen);
+        db.set_quote_channel(unclosed, Some(QuoteChannel::for_test(None)))
+            .unwrap();
+
+        let closed = add_quote(&db, &clock, 3, QuoteState::ChannelOpen);
+        db.set_quote_channel(closed, Some(QuoteChannel::for_test(Some(clock.now()))))
+            .unwrap();
+
+        clock.advance(60 * DAY);
+
+        let open_channels = HashSet::from([1]);
+
+        assert_eq!(
+            prune_quotes(&db, clock.as_ref(), &policy(), &open_channels).unwrap(),
+            1
+        );
+        assert!(db.get_quote(listed).is_ok());
+        assert!(db.get_quote(unclosed).is_ok());
+        assert!(db.get_quote(closed).is_err());
+    }
+
+    #[test]
+    fn prunes_abandoned_unpaid_quotes() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let unpaid = add_quote(&db, &clock, 1, QuoteState::Unpaid);
+        let paid = add_quote(&db, &clock, 2, QuoteState::Paid);
+
+        clock.advance(8 * DAY);
+
+        let policy = RetentionPolicy {
+            max_age: None,
+            unpaid_max_age: Some(Duration::from_secs(7 * DAY)),
+            ..policy()
+        };
+
+        assert_eq!(prune(&db, &clock, &policy), 1);
+        assert!(db.get_quote(unpaid).is_err());
+        assert!(db.get_quote(paid).is_ok());
+    }
+
+    #[test]
+    fn archives_audit_entries() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let archive_file = TempPath::new("archive.jsonl");
+
+        let id = add_quote(&db, &clock, 1, QuoteState::ChannelExpired);
+
+        clock.advance(60 * DAY);
+
+        let policy = RetentionPolicy {
+            archive_path: Some(archive_file.path().to_path_buf()),
+            ..policy()
+        };
+
+        assert_eq!(prune(&db, &clock, &policy), 1);
+
+        let archive = std::fs::read_to_string(archive_file.path()).unwrap();
+
+        let archived: ArchivedQuote = serde_json::from_str(archive.trim()).unwrap();
+
+        assert_eq!(archived.quote.id, id);
+        assert_eq!(archived.audit.len(), 2);
+        assert!(db.list_quote_audit(Some(id), 0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn prunes_old_events_but_keeps_the_latest() {
+        let clock = Arc::new(ManualClock::new(1_000 * DAY));
+        let (db, _file) = temp_db(clock.clone());
+
+        let kind = || LspEventKind::QuoteCreated {
+            quote_id: Uuid::from_u128(1),
+        };
+
+        db.append_event(kind()).unwrap();
+        db.append_event(kind()).unwrap();
+
+        clock.advance(10 * DAY);
+
+        let policy = RetentionPolicy {
+            event_max_age: Some(Duration::from_secs(7 * DAY)),
+            ..policy()
+        };
+
+        assert_eq!(prune_events(&db, clock.as_ref(), &policy).unwrap(), 1);
+
+        let latest = db.append_event(kind()).unwrap();
+        assert_eq!(latest.seq, 3);
+        assert_eq!(db.list_events(0, 10).unwrap().len(), 2);
+    }
+}
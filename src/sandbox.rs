@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::db::LspStore;
+use crate::events::EventJournal;
+use crate::lsp_server::LspError;
+
+/// Header carrying a sandbox API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Isolated environment wallet vendors can run CI against
+///
+/// Sandbox quotes live in their own database and are settled without
+/// redeeming proofs or opening channels, debiting a fake per key balance instead.
+#[derive(Clone)]
+pub struct Sandbox {
+    pub(crate) db: Arc<dyn LspStore>,
+    pub(crate) events: EventJournal,
+    balances: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Sandbox {
+    /// Create a sandbox from `(api key, fake balance in sats)` pairs
+    pub fn new(db: Arc<dyn LspStore>, keys: Vec<(String, u64)>) -> Self {
+        Self {
+            events: EventJournal::new(db.clone()),
+            db,
+            balances: Arc::new(Mutex::new(keys.into_iter().collect())),
+        }
+    }
+
+    pub fn has_key(&self, key: &str) -> bool {
+        self.balances
+            .lock()
+            .map(|b| b.contains_key(key))
+            .unwrap_or(false)
+    }
+
+    /// Debit `amount_sats` from the fake balance of `key`
+    pub fn charge(&self, key: &str, amount_sats: u64) -> Result<(), LspError> {
+        let mut balances = self
+            .balances
+            .lock()
+            .map_err(|_| LspError::InternalError("Sandbox lock poisoned".to_string()))?;
+
+        let balance = balances.get_mut(key).ok_or(LspError::InvalidApiKey)?;
+
+        if *balance < amount_sats {
+            return Err(LspError::SandboxBalanceExceeded {
+                balance: *balance,
+                required: amount_sats,
+            });
+        }
+
+        *balance -= amount_sats;
+
+        Ok(())
+    }
+
+    /// Give back `amount_sats` charged for a payment that was not settled
+    pub fn credit(&self, key: &str, amount_sats: u64) {
+        let mut balances = self
+            .balances
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(balance) = balances.get_mut(key) {
+            *balance = balance.saturating_add(amount_sats);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use bip39::Mnemonic;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ldk_node::bitcoin::hex::{DisplayHex, FromHex};
+
+/// First field of an encrypted mnemonic file
+const ENCRYPTED_PREFIX: &str = "cashu-lsp-mnemonic-v1";
+/// scrypt cost as log2 of N, stored with the ciphertext so it can be raised later
+const SCRYPT_LOG_N: u8 = 17;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Whether a stored mnemonic was written by [`encrypt_mnemonic`]
+pub fn is_encrypted(stored: &str) -> bool {
+    stored.trim().starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypt a mnemonic with a key derived from `passphrase`
+///
+/// The result is `<prefix>:<log_n>:<salt>:<nonce>:<ciphertext>` with hex fields.
+pub fn encrypt_mnemonic(mnemonic: &Mnemonic, passphrase: &str) -> anyhow::Result<String> {
+    encrypt_with_cost(mnemonic, passphrase, SCRYPT_LOG_N)
+}
+
+/// Decrypt a mnemonic written by [`encrypt_mnemonic`]
+pub fn decrypt_mnemonic(stored: &str, passphrase: &str) -> anyhow::Result<Mnemonic> {
+    let mut fields = stored.trim().split(':');
+
+    if fields.next() != Some(ENCRYPTED_PREFIX) {
+        bail!("Mnemonic is not encrypted");
+    }
+
+    let (Some(log_n), Some(salt), Some(nonce), Some(ciphertext), None) = (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) else {
+        bail!("Malformed encrypted mnemonic");
+    };
+
+    let log_n: u8 = log_n.parse()?;
+    let salt = Vec::<u8>::from_hex(salt)?;
+    let nonce = Vec::<u8>::from_hex(nonce)?;
+    let ciphertext = Vec::<u8>::from_hex(ciphertext)?;
+
+    if nonce.len() != NONCE_LEN {
+        bail!("Malformed encrypted mnemonic");
+    }
+
+    let plaintext = cipher(passphrase, &salt, log_n)?
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| anyhow!("Wrong mnemonic passphrase"))?;
+
+    Ok(Mnemonic::from_str(std::str::from_utf8(&plaintext)?)?)
+}
+
+fn encrypt_with_cost(mnemonic: &Mnemonic, passphrase: &str, log_n: u8) -> anyhow::Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(passphrase, &salt, log_n)?
+        .encrypt(&nonce, mnemonic.to_string().as_bytes())
+        .map_err(|_| anyhow!("Could not encrypt mnemonic"))?;
+
+    Ok(format!(
+        "{}:{}:{}:{}:{}",
+        ENCRYPTED_PREFIX,
+        log_n,
+        salt.to_lower_hex_string(),
+        nonce.as_slice().to_lower_hex_string(),
+        ciphertext.to_lower_hex_string()
+    ))
+}
+
+fn cipher(passphrase: &str, salt: &[u8], log_n: u8) -> anyhow::Result<XChaCha20Poly1305> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| anyhow!("Invalid scrypt parameters: {}", e))?;
+
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| anyhow!("Could not derive mnemonic key: {}", e))?;
+
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap enough for debug builds, the format keeps the cost per file
+    const TEST_LOG_N: u8 = 4;
+
+    #[test]
+    fn encrypted_mnemonic_round_trips() {
+        let mnemonic = Mnemonic::generate(12).unwrap();
+        let stored = encrypt_with_cost(&mnemonic, "hunter2", TEST_LOG_N).unwrap();
+
+        assert!(is_encrypted(&stored));
+        assert!(!stored.contains(&mnemonic.to_string()));
+        assert_eq!(decrypt_mnemonic(&stored, "hunter2").unwrap(), mnemonic);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let mnemonic = Mnemonic::generate(12).unwrap();
+        let stored = encrypt_with_cost(&mnemonic, "hunter2", TEST_LOG_N).unwrap();
+
+        assert!(decrypt_mnemonic(&stored, "hunter3").is_err());
+    }
+
+    #[test]
+    fn plaintext_mnemonic_is_not_encrypted() {
+        let mnemonic = Mnemonic::generate(12).unwrap();
+
+        assert!(!is_encrypted(&mnemonic.to_string()));
+        assert!(decrypt_mnemonic(&mnemonic.to_string(), "hunter2").is_err());
+    }
+}
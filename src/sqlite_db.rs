@@ -0,0 +1,1049 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Result, anyhow, bail};
+use cdk::mint_url::MintUrl;
+use cdk::nuts::PaymentRequestPayload;
+use ldk_node::UserChannelId;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use uuid::Uuid;
+
+use crate::clock::{Clock, SystemClock};
+use crate::db::LspStore;
+use crate::types::{
+    ChannelOpenJob, FeeLedgerEntry, LspEvent, LspEventKind, OfferRecord, PeerAccess, PeerSla,
+    QuoteActor, QuoteAuditEntry, QuoteChannel, QuoteInfo, QuotePayment, QuoteRefund, QuoteState,
+    QuoteStateTransition, RefundMethod, SignedReceipt,
+};
+
+/// Records are stored as JSON like in [`crate::db::Db`], with the columns
+/// operators are likely to filter on kept alongside them.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS quotes (
+    id TEXT PRIMARY KEY,
+    node_pubkey TEXT NOT NULL,
+    state TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    quote TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS quotes_node_pubkey ON quotes (node_pubkey);
+CREATE INDEX IF NOT EXISTS quotes_state ON quotes (state);
+
+CREATE TABLE IF NOT EXISTS quote_history (
+    quote_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    transition TEXT NOT NULL,
+    PRIMARY KEY (quote_id, seq)
+);
+
+CREATE TABLE IF NOT EXISTS quote_audit (
+    seq INTEGER PRIMARY KEY,
+    quote_id TEXT NOT NULL,
+    from_state TEXT,
+    to_state TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    actor TEXT NOT NULL,
+    entry TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS quote_audit_quote_id ON quote_audit (quote_id);
+
+CREATE TABLE IF NOT EXISTS payment_hashes (
+    quote_id TEXT PRIMARY KEY,
+    payload_hash TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS channel_open_jobs (
+    quote_id TEXT PRIMARY KEY,
+    next_attempt_at INTEGER NOT NULL,
+    job TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pending_payments (
+    quote_id TEXT PRIMARY KEY,
+    payload TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS quote_payments (
+    quote_id TEXT PRIMARY KEY,
+    mint_url TEXT NOT NULL,
+    received_at INTEGER NOT NULL,
+    payment TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS locked_payments (
+    quote_id TEXT PRIMARY KEY,
+    payload TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS receipts (
+    quote_id TEXT PRIMARY KEY,
+    receipt TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS fee_ledger (
+    quote_id TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    revenue_sats INTEGER NOT NULL,
+    entry TEXT NOT NULL,
+    PRIMARY KEY (recorded_at, quote_id)
+);
+
+CREATE TABLE IF NOT EXISTS refunds (
+    quote_id TEXT PRIMARY KEY,
+    method TEXT NOT NULL,
+    amount_sats INTEGER NOT NULL,
+    claimed_at INTEGER,
+    refund TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS peer_sla (
+    node_pubkey TEXT PRIMARY KEY,
+    sla TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS peer_access (
+    node_pubkey TEXT PRIMARY KEY,
+    access TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS mints (
+    mint_url TEXT PRIMARY KEY,
+    accepted INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS offers (
+    offer_id BLOB PRIMARY KEY,
+    offer TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    seq INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    event TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS event_cursors (
+    consumer TEXT PRIMARY KEY,
+    seq INTEGER NOT NULL
+);
+"#;
+
+/// SQLite backed [`LspStore`]
+#[derive(Clone)]
+pub struct SqliteDb {
+    conn: Arc<Mutex<Connection>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SqliteDb {
+    pub fn new(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Override the clock used to timestamp records
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Set the state of a quote if it is currently `from`, or unconditionally
+    /// without one, returning the quote as it was before
+    fn set_quote_state(
+        &self,
+        quote_id: Uuid,
+        from: Option<QuoteState>,
+        quote_state: QuoteState,
+        reason: Option<&str>,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let current_quote = get_quote(&tx, quote_id)?;
+
+        if from.is_some_and(|from| from != current_quote.state) {
+            return Ok(current_quote);
+        }
+
+        let mut quote = current_quote.clone();
+        quote.state = quote_state;
+        put_quote(&tx, &quote)?;
+
+        if current_quote.state != quote_state || reason.is_some() {
+            self.record_transition(
+                &tx,
+                quote_id,
+                Some(current_quote.state),
+                quote_state,
+                reason,
+                actor,
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(current_quote)
+    }
+
+    /// Append a state transition to the quote's history and the audit log
+    fn record_transition(
+        &self,
+        tx: &Transaction,
+        quote_id: Uuid,
+        from: Option<QuoteState>,
+        state: QuoteState,
+        reason: Option<&str>,
+        actor: QuoteActor,
+    ) -> Result<()> {
+        let seq: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(seq) + 1, 0) FROM quote_history WHERE quote_id = ?1",
+            params![quote_id.to_string()],
+            |row| row.get(0),
+        )?;
+
+        let transition = QuoteStateTransition {
+            state,
+            timestamp: self.clock.now(),
+            reason: reason.map(str::to_string),
+        };
+
+        tx.execute(
+            "INSERT INTO quote_history (quote_id, seq, state, timestamp, transition)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                quote_id.to_string(),
+                seq,
+                state_name(state),
+                transition.timestamp as i64,
+                serde_json::to_string(&transition)?,
+            ],
+        )?;
+
+        let audit_seq: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(seq) + 1, 1) FROM quote_audit",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let entry = QuoteAuditEntry {
+            seq: audit_seq as u64,
+            quote_id,
+            from,
+            to: state,
+            timestamp: transition.timestamp,
+            reason: transition.reason,
+            actor,
+        };
+
+        tx.execute(
+            "INSERT INTO quote_audit (seq, quote_id, from_state, to_state, timestamp, actor, entry)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                audit_seq,
+                quote_id.to_string(),
+                from.map(state_name),
+                state_name(state),
+                entry.timestamp as i64,
+                format!("{:?}", actor),
+                serde_json::to_string(&entry)?,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Read, update and write back a peer's SLA in one transaction
+    fn update_peer_sla(
+        &self,
+        node_pubkey: PublicKey,
+        update: impl FnOnce(&mut PeerSla),
+    ) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT sla FROM peer_sla WHERE node_pubkey = ?1",
+                params![node_pubkey.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let mut sla = match existing {
+            Some(value) => serde_json::from_str(&value)?,
+            None => PeerSla::new(node_pubkey),
+        };
+
+        update(&mut sla);
+
+        tx.execute(
+            "INSERT OR REPLACE INTO peer_sla (node_pubkey, sla) VALUES (?1, ?2)",
+            params![node_pubkey.to_string(), serde_json::to_string(&sla)?],
+        )?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+}
+
+fn state_name(state: QuoteState) -> String {
+    format!("{:?}", state)
+}
+
+fn refund_method_name(method: RefundMethod) -> String {
+    format!("{:?}", method)
+}
+
+fn get_quote(conn: &Connection, quote_id: Uuid) -> Result<QuoteInfo> {
+    let quote: String = conn
+        .query_row(
+            "SELECT quote FROM quotes WHERE id = ?1",
+            params![quote_id.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(anyhow!("Unknown quote"))?;
+
+    Ok(serde_json::from_str(&quote)?)
+}
+
+fn put_quote(conn: &Connection, quote: &QuoteInfo) -> Result<()> {
+    conn.execute(
+        "INSERT INTO quotes (id, node_pubkey, state, created_at, quote)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (id) DO UPDATE SET
+            node_pubkey = excluded.node_pubkey,
+            state = excluded.state,
+            created_at = excluded.created_at,
+            quote = excluded.quote",
+        params![
+            quote.id.to_string(),
+            quote.node_pubkey.to_string(),
+            state_name(quote.state),
+            quote.created_at as i64,
+            serde_json::to_string(quote)?,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// JSON value of the row keyed by `quote_id` in a single column table
+fn get_json<T: serde::de::DeserializeOwned>(
+    conn: &Connection,
+    sql: &str,
+    quote_id: Uuid,
+) -> Result<Option<T>> {
+    conn.query_row(sql, params![quote_id.to_string()], |row| {
+        row.get::<_, String>(0)
+    })
+    .optional()?
+    .map(|value| serde_json::from_str(&value))
+    .transpose()
+    .map_err(Into::into)
+}
+
+/// Decode every row of a query returning a single JSON column
+fn list_json<T: serde::de::DeserializeOwned>(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<T>> {
+    let mut stmt = conn.prepare(sql)?;
+
+    let rows = stmt.query_map(params, |row| row.get::<_, String>(0))?;
+
+    let mut values = vec![];
+
+    for row in rows {
+        values.push(serde_json::from_str(&row?)?);
+    }
+
+    Ok(values)
+}
+
+impl LspStore for SqliteDb {
+    fn check(&self) -> Result<()> {
+        self.conn()
+            .query_row("SELECT COUNT(*) FROM quotes", [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+
+        Ok(())
+    }
+
+    fn add_quote(&self, quote_info: &QuoteInfo, actor: QuoteActor) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let previous_state =
+            get_json::<QuoteInfo>(&tx, "SELECT quote FROM quotes WHERE id = ?1", quote_info.id)?
+                .map(|quote| quote.state);
+
+        put_quote(&tx, quote_info)?;
+
+        if previous_state != Some(quote_info.state) {
+            self.record_transition(
+                &tx,
+                quote_info.id,
+                previous_state,
+                quote_info.state,
+                None,
+                actor,
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_quote(&self, quote_id: Uuid) -> Result<QuoteInfo> {
+        get_quote(&self.conn(), quote_id)
+    }
+
+    fn list_quotes(&self) -> Result<Vec<QuoteInfo>> {
+        list_json(&self.conn(), "SELECT quote FROM quotes ORDER BY id", [])
+    }
+
+    fn list_quotes_after(&self, after: Option<Uuid>, limit: usize) -> Result<Vec<QuoteInfo>> {
+        // Hyphenated ids sort as text in the same order as their bytes
+        list_json(
+            &self.conn(),
+            "SELECT quote FROM quotes WHERE id > ?1 ORDER BY id LIMIT ?2",
+            params![
+                after.map(|id| id.to_string()).unwrap_or_default(),
+                limit.min(i64::MAX as usize) as i64,
+            ],
+        )
+    }
+
+    fn list_node_quotes(&self, node_pubkey: PublicKey) -> Result<Vec<QuoteInfo>> {
+        list_json(
+            &self.conn(),
+            "SELECT quote FROM quotes WHERE node_pubkey = ?1 ORDER BY id",
+            params![node_pubkey.to_string()],
+        )
+    }
+
+    fn backup(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("{} already exists", path.display());
+        }
+
+        // Copies the database as of one read transaction, WAL included
+        self.conn()
+            .execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+
+        Ok(())
+    }
+
+    fn remove_quotes(&self, quote_ids: &[Uuid]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        for quote_id in quote_ids {
+            let quote_id = quote_id.to_string();
+
+            tx.execute("DELETE FROM quotes WHERE id = ?1", params![quote_id])?;
+
+            for table in [
+                "quote_history",
+                "quote_audit",
+                "quote_payments",
+                "receipts",
+                "refunds",
+                "payment_hashes",
+                "locked_payments",
+                "pending_payments",
+                "channel_open_jobs",
+            ] {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE quote_id = ?1", table),
+                    params![quote_id],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn update_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, None, quote_state, None, actor)
+    }
+
+    fn transition_quote_state(
+        &self,
+        quote_id: Uuid,
+        from: QuoteState,
+        to: QuoteState,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, Some(from), to, None, actor)
+    }
+
+    fn override_quote_state(
+        &self,
+        quote_id: Uuid,
+        quote_state: QuoteState,
+        reason: &str,
+        actor: QuoteActor,
+    ) -> Result<QuoteInfo> {
+        self.set_quote_state(quote_id, None, quote_state, Some(reason), actor)
+    }
+
+    fn set_payment_error(&self, quote_id: Uuid, payment_error: Option<String>) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut quote = get_quote(&tx, quote_id)?;
+        quote.payment_error = payment_error;
+        put_quote(&tx, &quote)?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn set_quote_channel(&self, quote_id: Uuid, channel: Option<QuoteChannel>) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut quote = get_quote(&tx, quote_id)?;
+        quote.channel = channel;
+        put_quote(&tx, &quote)?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn set_quote_channel_id(
+        &self,
+        quote_id: Uuid,
+        channel_id: Option<UserChannelId>,
+    ) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut quote = get_quote(&tx, quote_id)?;
+        quote.channel_id = channel_id;
+        put_quote(&tx, &quote)?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn add_quote_payment(&self, quote_id: Uuid, payment: &QuotePayment) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO quote_payments (quote_id, mint_url, received_at, payment)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                quote_id.to_string(),
+                payment.mint_url.to_string(),
+                payment.received_at as i64,
+                serde_json::to_string(payment)?,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_quote_payment(&self, quote_id: Uuid) -> Result<Option<QuotePayment>> {
+        get_json(
+            &self.conn(),
+            "SELECT payment FROM quote_payments WHERE quote_id = ?1",
+            quote_id,
+        )
+    }
+
+    fn add_receipt(&self, quote_id: Uuid, receipt: &SignedReceipt) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO receipts (quote_id, receipt) VALUES (?1, ?2)",
+            params![quote_id.to_string(), serde_json::to_string(receipt)?],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_receipt(&self, quote_id: Uuid) -> Result<Option<SignedReceipt>> {
+        get_json(
+            &self.conn(),
+            "SELECT receipt FROM receipts WHERE quote_id = ?1",
+            quote_id,
+        )
+    }
+
+    fn add_refund(&self, refund: &QuoteRefund) -> Result<bool> {
+        let inserted = self.conn().execute(
+            "INSERT OR IGNORE INTO refunds (quote_id, method, amount_sats, claimed_at, refund)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                refund.quote_id.to_string(),
+                refund_method_name(refund.method),
+                refund.amount_sats as i64,
+                refund.claimed_at.map(|t| t as i64),
+                serde_json::to_string(refund)?
+            ],
+        )?;
+
+        Ok(inserted == 1)
+    }
+
+    fn update_refund(&self, refund: &QuoteRefund) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO refunds (quote_id, method, amount_sats, claimed_at, refund)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                refund.quote_id.to_string(),
+                refund_method_name(refund.method),
+                refund.amount_sats as i64,
+                refund.claimed_at.map(|t| t as i64),
+                serde_json::to_string(refund)?
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn remove_refund(&self, quote_id: Uuid) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM refunds WHERE quote_id = ?1",
+            params![quote_id.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_refund(&self, quote_id: Uuid) -> Result<Option<QuoteRefund>> {
+        get_json(
+            &self.conn(),
+            "SELECT refund FROM refunds WHERE quote_id = ?1",
+            quote_id,
+        )
+    }
+
+    fn list_refunds(&self) -> Result<Vec<QuoteRefund>> {
+        list_json(
+            &self.conn(),
+            "SELECT refund FROM refunds ORDER BY quote_id",
+            [],
+        )
+    }
+
+    fn add_fee_entry(&self, entry: &FeeLedgerEntry) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO fee_ledger (quote_id, recorded_at, revenue_sats, entry)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entry.quote_id.to_string(),
+                entry.recorded_at as i64,
+                entry.revenue_sats,
+                serde_json::to_string(entry)?
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn list_fee_entries(&self, since: u64, until: u64) -> Result<Vec<FeeLedgerEntry>> {
+        list_json(
+            &self.conn(),
+            "SELECT entry FROM fee_ledger WHERE recorded_at >= ?1 AND recorded_at < ?2
+             ORDER BY recorded_at, quote_id",
+            params![since as i64, until as i64],
+        )
+    }
+
+    fn add_locked_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO locked_payments (quote_id, payload) VALUES (?1, ?2)",
+            params![quote_id.to_string(), serde_json::to_string(payload)?],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_locked_payment(&self, quote_id: Uuid) -> Result<Option<PaymentRequestPayload>> {
+        get_json(
+            &self.conn(),
+            "SELECT payload FROM locked_payments WHERE quote_id = ?1",
+            quote_id,
+        )
+    }
+
+    fn remove_locked_payment(&self, quote_id: Uuid) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM locked_payments WHERE quote_id = ?1",
+            params![quote_id.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn set_payment_hash(&self, quote_id: Uuid, payload_hash: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO payment_hashes (quote_id, payload_hash) VALUES (?1, ?2)",
+            params![quote_id.to_string(), payload_hash],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_payment_hash(&self, quote_id: Uuid) -> Result<Option<String>> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT payload_hash FROM payment_hashes WHERE quote_id = ?1",
+                params![quote_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn put_channel_open_job(&self, job: &ChannelOpenJob) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO channel_open_jobs (quote_id, next_attempt_at, job)
+             VALUES (?1, ?2, ?3)",
+            params![
+                job.quote_id.to_string(),
+                job.next_attempt_at as i64,
+                serde_json::to_string(job)?
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_channel_open_job(&self, quote_id: Uuid) -> Result<Option<ChannelOpenJob>> {
+        get_json(
+            &self.conn(),
+            "SELECT job FROM channel_open_jobs WHERE quote_id = ?1",
+            quote_id,
+        )
+    }
+
+    fn remove_channel_open_job(&self, quote_id: Uuid) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM channel_open_jobs WHERE quote_id = ?1",
+            params![quote_id.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn list_channel_open_jobs(&self) -> Result<Vec<ChannelOpenJob>> {
+        list_json(
+            &self.conn(),
+            "SELECT job FROM channel_open_jobs ORDER BY quote_id",
+            [],
+        )
+    }
+
+    fn add_pending_payment(&self, quote_id: Uuid, payload: &PaymentRequestPayload) -> Result<bool> {
+        let inserted = self.conn().execute(
+            "INSERT OR IGNORE INTO pending_payments (quote_id, payload) VALUES (?1, ?2)",
+            params![quote_id.to_string(), serde_json::to_string(payload)?],
+        )?;
+
+        Ok(inserted == 1)
+    }
+
+    fn remove_pending_payment(&self, quote_id: Uuid) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM pending_payments WHERE quote_id = ?1",
+            params![quote_id.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn is_payment_pending(&self, quote_id: Uuid) -> Result<bool> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT 1 FROM pending_payments WHERE quote_id = ?1",
+                params![quote_id.to_string()],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    fn list_pending_payments(&self) -> Result<Vec<(Uuid, PaymentRequestPayload)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT quote_id, payload FROM pending_payments")?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut payments = vec![];
+
+        for row in rows {
+            let (quote_id, payload) = row?;
+            payments.push((Uuid::from_str(&quote_id)?, serde_json::from_str(&payload)?));
+        }
+
+        Ok(payments)
+    }
+
+    fn list_quote_history(
+        &self,
+        quote_id: Uuid,
+        since: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<QuoteStateTransition>> {
+        list_json(
+            &self.conn(),
+            "SELECT transition FROM quote_history
+             WHERE quote_id = ?1 AND timestamp >= ?2
+             ORDER BY seq LIMIT ?3",
+            params![
+                quote_id.to_string(),
+                since as i64,
+                limit.map_or(-1, |limit| limit as i64),
+            ],
+        )
+    }
+
+    fn list_quote_audit(
+        &self,
+        quote_id: Option<Uuid>,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<Vec<QuoteAuditEntry>> {
+        list_json(
+            &self.conn(),
+            "SELECT entry FROM quote_audit
+             WHERE seq > ?1 AND (?2 IS NULL OR quote_id = ?2)
+             ORDER BY seq LIMIT ?3",
+            params![
+                cursor.min(i64::MAX as u64) as i64,
+                quote_id.map(|id| id.to_string()),
+                limit.min(i64::MAX as usize) as i64,
+            ],
+        )
+    }
+
+    fn add_sla_sample(&self, node_pubkey: PublicKey, connected: bool, usable: bool) -> Result<()> {
+        let now = self.clock.now();
+
+        self.update_peer_sla(node_pubkey, |sla| {
+            sla.samples += 1;
+            if connected {
+                sla.connected_samples += 1;
+            }
+            if usable {
+                sla.usable_samples += 1;
+            }
+            sla.last_sample = now;
+        })
+    }
+
+    fn record_sla_forward(&self, node_pubkey: PublicKey, amount_msat: u64) -> Result<()> {
+        self.update_peer_sla(node_pubkey, |sla| {
+            sla.forwarded_payments += 1;
+            sla.forwarded_msats = sla.forwarded_msats.saturating_add(amount_msat);
+        })
+    }
+
+    fn record_sla_close(&self, node_pubkey: PublicKey) -> Result<()> {
+        self.update_peer_sla(node_pubkey, |sla| sla.closed_channels += 1)
+    }
+
+    fn list_peer_sla(&self) -> Result<Vec<PeerSla>> {
+        list_json(&self.conn(), "SELECT sla FROM peer_sla", [])
+    }
+
+    fn set_peer_access(&self, node_pubkey: PublicKey, access: Option<PeerAccess>) -> Result<()> {
+        let conn = self.conn();
+
+        match access {
+            Some(access) => conn.execute(
+                "INSERT OR REPLACE INTO peer_access (node_pubkey, access) VALUES (?1, ?2)",
+                params![node_pubkey.to_string(), serde_json::to_string(&access)?],
+            )?,
+            None => conn.execute(
+                "DELETE FROM peer_access WHERE node_pubkey = ?1",
+                params![node_pubkey.to_string()],
+            )?,
+        };
+
+        Ok(())
+    }
+
+    fn list_peer_access(&self) -> Result<Vec<(PublicKey, PeerAccess)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT node_pubkey, access FROM peer_access")?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut peers = vec![];
+
+        for row in rows {
+            let (node_pubkey, access) = row?;
+            peers.push((
+                PublicKey::from_str(&node_pubkey)?,
+                serde_json::from_str(&access)?,
+            ));
+        }
+
+        Ok(peers)
+    }
+
+    fn set_mint_accepted(&self, mint_url: &MintUrl, accepted: bool) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO mints (mint_url, accepted) VALUES (?1, ?2)",
+            params![mint_url.to_string(), accepted],
+        )?;
+
+        Ok(())
+    }
+
+    fn list_mint_overrides(&self) -> Result<Vec<(MintUrl, bool)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT mint_url, accepted FROM mints")?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+        })?;
+
+        let mut mints = vec![];
+
+        for row in rows {
+            let (mint_url, accepted) = row?;
+            mints.push((MintUrl::from_str(&mint_url)?, accepted));
+        }
+
+        Ok(mints)
+    }
+
+    fn add_offer(
+        &self,
+        offer_id: [u8; 32],
+        offer: String,
+        description: String,
+        amount_msats: Option<u64>,
+    ) -> Result<OfferRecord> {
+        let record = OfferRecord {
+            offer_id,
+            offer,
+            description,
+            amount_msats,
+            created_at: self.clock.now(),
+        };
+
+        self.conn().execute(
+            "INSERT OR REPLACE INTO offers (offer_id, offer) VALUES (?1, ?2)",
+            params![offer_id.as_slice(), serde_json::to_string(&record)?],
+        )?;
+
+        Ok(record)
+    }
+
+    fn list_offers(&self) -> Result<Vec<OfferRecord>> {
+        list_json(
+            &self.conn(),
+            "SELECT offer FROM offers ORDER BY offer_id",
+            [],
+        )
+    }
+
+    fn append_event(&self, kind: LspEventKind) -> Result<LspEvent> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let seq: i64 = tx.query_row("SELECT COALESCE(MAX(seq) + 1, 1) FROM events", [], |row| {
+            row.get(0)
+        })?;
+
+        let event = LspEvent {
+            seq: seq as u64,
+            timestamp: self.clock.now(),
+            kind,
+        };
+
+        tx.execute(
+            "INSERT INTO events (seq, timestamp, event) VALUES (?1, ?2, ?3)",
+            params![seq, event.timestamp as i64, serde_json::to_string(&event)?],
+        )?;
+
+        tx.commit()?;
+
+        Ok(event)
+    }
+
+    fn list_events(&self, cursor: u64, limit: usize) -> Result<Vec<LspEvent>> {
+        list_json(
+            &self.conn(),
+            "SELECT event FROM events WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+            params![
+                cursor.min(i64::MAX as u64) as i64,
+                limit.min(i64::MAX as usize) as i64,
+            ],
+        )
+    }
+
+    fn remove_events_before(&self, timestamp: u64) -> Result<usize> {
+        let removed = self.conn().execute(
+            "DELETE FROM events
+             WHERE timestamp < ?1 AND seq < (SELECT MAX(seq) FROM events)",
+            params![timestamp.min(i64::MAX as u64) as i64],
+        )?;
+
+        Ok(removed)
+    }
+
+    fn get_event_cursor(&self, consumer: &str) -> Result<u64> {
+        let seq: i64 = self.conn().query_row(
+            "SELECT COALESCE(
+                 (SELECT seq FROM event_cursors WHERE consumer = ?1),
+                 (SELECT MAX(seq) FROM events),
+                 0
+             )",
+            params![consumer],
+            |row| row.get(0),
+        )?;
+
+        Ok(seq as u64)
+    }
+
+    fn set_event_cursor(&self, consumer: &str, seq: u64) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO event_cursors (consumer, seq) VALUES (?1, ?2)",
+            params![consumer, seq.min(i64::MAX as u64) as i64],
+        )?;
+
+        Ok(())
+    }
+}
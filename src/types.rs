@@ -1,11 +1,14 @@
 use std::str::FromStr;
 
+use cdk::mint_url::MintUrl;
+use cdk::nuts::CurrencyUnit;
 use ldk_node::UserChannelId;
 use ldk_node::bitcoin::secp256k1::PublicKey;
 use ldk_node::lightning::ln::msgs::SocketAddress;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 // Custom serialization for UserChannelId
@@ -107,6 +110,16 @@ mod socket_address_serde {
     }
 }
 
+/// Convert sats to msats, `None` on overflow
+pub fn sats_to_msats(sats: u64) -> Option<u64> {
+    sats.checked_mul(1_000)
+}
+
+/// Convert msats to sats, rounding down
+pub fn msats_to_sats(msats: u64) -> u64 {
+    msats / 1_000
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct QuoteInfo {
     pub id: Uuid,
@@ -119,18 +132,166 @@ pub struct QuoteInfo {
     pub state: QuoteState,
     #[serde(with = "user_channel_id_serde")]
     pub channel_id: Option<UserChannelId>,
+    /// Mints the quote was priced for
+    #[serde(default)]
+    pub mints: Vec<MintUrl>,
+    /// Unit the payment request was issued in
+    #[serde(default)]
+    pub unit: CurrencyUnit,
+    /// Unix time the quote was created
+    #[serde(default)]
+    pub created_at: u64,
+    /// Why the last payment for the quote could not be processed
+    #[serde(default)]
+    pub payment_error: Option<String>,
+    /// Forwarding base fee requested for the LSP's side of the channel
+    #[serde(default)]
+    pub forwarding_fee_base_msat: Option<u32>,
+    /// Forwarding proportional fee requested for the LSP's side of the channel
+    #[serde(default)]
+    pub forwarding_fee_ppm: Option<u32>,
+    /// BOLT12 offer the quote can be paid with instead of ecash
+    #[serde(default)]
+    pub bolt12_offer: Option<String>,
+    /// Id of `bolt12_offer`, used to match incoming payments
+    #[serde(default)]
+    pub bolt12_offer_id: Option<[u8; 32]>,
+    #[serde(default)]
+    pub mode: QuoteMode,
+    /// Expected mint input fee for swapping the payment, included in `expected_payment_sats`
+    #[serde(default)]
+    pub mint_fee_sats: u64,
+    /// Set when the quote is paid with HTLC locked proofs
+    #[serde(default)]
+    pub htlc: Option<HtlcLock>,
+    /// Set when the quote is paid with proofs refundable to the payer
+    #[serde(default)]
+    pub refund_lock: Option<RefundLock>,
+    /// Sats per minor unit the quote was priced at, for units other than sat and msat
+    #[serde(default)]
+    pub sats_per_unit: Option<f64>,
+    /// Funding details of the channel, recorded from node events once it is pending
+    #[serde(default)]
+    pub channel: Option<QuoteChannel>,
+    /// Funding transaction cost estimated when the quote was created
+    #[serde(default)]
+    pub onchain_fee_sats: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Channel sold for a quote, as reported by the node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct QuoteChannel {
+    /// Hex channel id shared with the counterparty
+    pub channel_id: String,
+    pub funding_txid: String,
+    pub funding_output_index: u32,
+    /// Set once the channel is ready
+    pub short_channel_id: Option<u64>,
+    /// Unix time the funding transaction was negotiated
+    pub opened_at: u64,
+    pub ready_at: Option<u64>,
+    pub closed_at: Option<u64>,
+    pub close_reason: Option<String>,
+}
+
+/// NUT-11 lock the proofs paying a quote must carry to be refundable
+///
+/// The proofs are locked to the LSP, and `refund_pubkey` can spend them
+/// after `locktime` if the LSP never claimed them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct RefundLock {
+    /// Hex pubkey of the payer
+    pub refund_pubkey: String,
+    /// Unix time the refund path may open at the earliest
+    pub locktime: u64,
+}
+
+/// NUT-14 lock the proofs paying a quote must carry
+///
+/// The payer reveals the preimage once the channel is ready, and can reclaim
+/// the proofs after `locktime` if it never is.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct HtlcLock {
+    /// Hex sha256 hash of the payer's preimage
+    pub hash: String,
+    /// Unix time the proofs must stay locked until
+    pub locktime: u64,
+}
+
+impl QuoteInfo {
+    /// Part of the payment kept by the LSP
+    pub fn service_fee_sats(&self) -> u64 {
+        let capacity = match self.mode {
+            QuoteMode::Funded => self.channel_size_sats,
+            QuoteMode::InboundOnly => 0,
+        };
+
+        self.expected_payment_sats
+            .saturating_sub(capacity)
+            .saturating_sub(self.push_amount_sats.unwrap_or_default())
+            .saturating_sub(self.mint_fee_sats)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ChannelQuoteRequest {
     pub channel_size_sats: u64,
+    #[schema(value_type = String)]
     pub node_pubkey: PublicKey,
     #[serde(with = "socket_address_serde")]
+    #[schema(value_type = String, example = "127.0.0.1:9735")]
     pub addr: SocketAddress,
     pub push_amount: Option<u64>,
+    /// Mints the client intends to pay from, defaults to all accepted mints
+    #[schema(value_type = Option<Vec<String>>)]
+    pub mints: Option<Vec<MintUrl>>,
+    /// Unit the client will pay in, defaults to sat
+    #[schema(value_type = Option<String>, example = "sat")]
+    pub unit: Option<CurrencyUnit>,
+    /// Base fee the LSP should charge for forwarding over the channel
+    pub forwarding_fee_base_msat: Option<u32>,
+    /// Proportional fee the LSP should charge for forwarding over the channel
+    pub forwarding_fee_ppm: Option<u32>,
+    /// Defaults to a funded channel
+    pub mode: Option<QuoteMode>,
+    /// `<timestamp>:<nonce>` proof of work stamp, when the LSP requires one
+    pub pow: Option<String>,
+    /// Non-refundable ecash bond, when the LSP requires one
+    pub bond_token: Option<String>,
+    /// Hex sha256 hash to pay with HTLC locked proofs instead of plain ecash
+    pub htlc_hash: Option<String>,
+    /// Hex pubkey to pay with proofs locked to the LSP and refundable to this key
+    pub refund_pubkey: Option<String>,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// What the client pays for in a quote
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteMode {
+    /// The client pays for the channel capacity plus the service fee
+    #[default]
+    Funded,
+    /// The client pays the service fee only and the LSP funds the capacity
+    InboundOnly,
+}
+
+impl QuoteMode {
+    /// Amount due for a channel in this mode
+    pub fn payment_required(&self, channel_size_sats: u64, fee: u64, push_amount_sats: u64) -> u64 {
+        let capacity = match self {
+            Self::Funded => channel_size_sats,
+            Self::InboundOnly => 0,
+        };
+
+        capacity
+            .checked_add(fee)
+            .expect("amount overflow")
+            .checked_add(push_amount_sats)
+            .expect("amount overflow")
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, ToSchema)]
 pub enum QuoteState {
     Unpaid,
     Paid,
@@ -138,3 +299,402 @@ pub enum QuoteState {
     ChannelOpen,
     ChannelExpired,
 }
+
+/// Quote terms the LSP commits to by signing them
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignedQuoteTerms {
+    pub quote_id: Uuid,
+    #[schema(value_type = String)]
+    pub node_pubkey: PublicKey,
+    pub channel_size_sats: u64,
+    pub push_amount_sats: Option<u64>,
+    pub expected_payment_sats: u64,
+    #[schema(value_type = String)]
+    pub unit: CurrencyUnit,
+    #[schema(value_type = Vec<String>)]
+    pub mints: Vec<MintUrl>,
+    pub mode: QuoteMode,
+    pub forwarding_fee_base_msat: Option<u32>,
+    pub forwarding_fee_ppm: Option<u32>,
+    /// Unix time the quote was priced
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub htlc: Option<HtlcLock>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refund_lock: Option<RefundLock>,
+}
+
+impl From<&QuoteInfo> for SignedQuoteTerms {
+    fn from(quote: &QuoteInfo) -> Self {
+        Self {
+            quote_id: quote.id,
+            node_pubkey: quote.node_pubkey,
+            channel_size_sats: quote.channel_size_sats,
+            push_amount_sats: quote.push_amount_sats,
+            expected_payment_sats: quote.expected_payment_sats,
+            unit: quote.unit.clone(),
+            mints: quote.mints.clone(),
+            mode: quote.mode,
+            forwarding_fee_base_msat: quote.forwarding_fee_base_msat,
+            forwarding_fee_ppm: quote.forwarding_fee_ppm,
+            created_at: quote.created_at,
+            htlc: quote.htlc.clone(),
+            refund_lock: quote.refund_lock.clone(),
+        }
+    }
+}
+
+/// Ecash received for a quote, kept for refunds, disputes and accounting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotePayment {
+    pub mint_url: MintUrl,
+    pub unit: CurrencyUnit,
+    pub proofs: Vec<ReceivedProof>,
+    /// Unix time the proofs were redeemed
+    pub received_at: u64,
+    /// Sats per minor unit the payment was accepted at, for units other than sat and msat
+    #[serde(default)]
+    pub sats_per_unit: Option<f64>,
+}
+
+/// Payment the LSP acknowledges receiving for a quote
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PaymentReceipt {
+    pub quote_id: Uuid,
+    #[schema(value_type = String)]
+    pub mint_url: MintUrl,
+    #[schema(value_type = String)]
+    pub unit: CurrencyUnit,
+    /// Total of the redeemed proofs in `unit`
+    pub amount: u64,
+    /// Unix time the proofs were redeemed
+    pub received_at: u64,
+}
+
+/// Payment receipt signed with the LSP node key
+///
+/// `receipt` is the exact signed message, a JSON encoded [`PaymentReceipt`].
+/// The signature can be checked against `lsp_pubkey` with any lightning
+/// `verifymessage` implementation.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignedReceipt {
+    pub receipt: String,
+    pub signature: String,
+    pub lsp_pubkey: String,
+}
+
+/// Fee the LSP earned on a quote
+///
+/// Recorded once the channel is ready, and when an anti-spam bond is kept
+/// for the quote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeLedgerEntry {
+    pub quote_id: Uuid,
+    /// Unix time the channel became ready or the bond was redeemed
+    pub recorded_at: u64,
+    /// Fee charged for the channel, the mint fee is charged on top
+    pub service_fee_sats: u64,
+    /// Estimated cost of the funding transaction at the fee rate quoted
+    pub onchain_fee_sats: u64,
+    /// Mint input fee the client paid for the LSP's swap
+    pub mint_fee_sats: u64,
+    /// Anti-spam bond kept when the quote was created
+    #[serde(default)]
+    pub bond_sats: u64,
+    /// Service fee and bond minus the onchain cost and mint fee, negative when the channel was sold at a loss
+    pub revenue_sats: i64,
+}
+
+/// Durable record of a channel still to be opened for a paid quote
+///
+/// Written when a quote is paid and removed once its channel is being opened,
+/// so a restart between redeeming the payment and opening the channel resumes
+/// the open.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelOpenJob {
+    pub quote_id: Uuid,
+    /// Failed attempts to open the channel so far
+    pub attempts: u32,
+    /// Unix time the open is attempted next
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+/// How a refund is paid out
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundMethod {
+    /// An ecash token from the mint the quote was paid at
+    Ecash,
+    /// A payment to an invoice of the client
+    Lightning,
+}
+
+/// Payment returned for a quote whose channel could not be sold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRefund {
+    pub quote_id: Uuid,
+    pub amount_sats: u64,
+    pub method: RefundMethod,
+    /// Token handed to the client for ecash refunds
+    pub token: Option<String>,
+    /// Invoice paid for lightning refunds
+    pub invoice: Option<String>,
+    /// Unix time the refund was started
+    pub created_at: u64,
+    /// Unix time the refund was paid out, unset while it is being issued
+    pub claimed_at: Option<u64>,
+}
+
+/// A received proof, identified by its Y so the secret is not stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivedProof {
+    pub amount: u64,
+    pub keyset_id: String,
+    /// Hex `hash_to_curve(secret)`, how mints identify spent proofs
+    pub y: String,
+}
+
+/// BOLT12 offer created through the management interface
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfferRecord {
+    pub offer_id: [u8; 32],
+    pub offer: String,
+    pub description: String,
+    pub amount_msats: Option<u64>,
+    pub created_at: u64,
+}
+
+/// Operator access rule for a counterparty node
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum PeerAccess {
+    Allow,
+    Deny,
+}
+
+/// Availability metrics for a counterparty we have sold channels to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerSla {
+    pub node_pubkey: PublicKey,
+    /// Number of times the peer has been sampled
+    pub samples: u64,
+    /// Samples where the peer was connected
+    pub connected_samples: u64,
+    /// Samples where all sold channels with the peer were usable
+    pub usable_samples: u64,
+    /// Unix time of the latest sample
+    pub last_sample: u64,
+    /// Payments forwarded through channels sold to the peer
+    ///
+    /// ldk-node 0.4 has no event for failed forwards, so only successes are
+    /// counted.
+    #[serde(default)]
+    pub forwarded_payments: u64,
+    /// Total amount of the forwarded payments
+    #[serde(default)]
+    pub forwarded_msats: u64,
+    /// Channels sold to the peer that have closed
+    #[serde(default)]
+    pub closed_channels: u64,
+}
+
+impl PeerSla {
+    pub fn new(node_pubkey: PublicKey) -> Self {
+        Self {
+            node_pubkey,
+            samples: 0,
+            connected_samples: 0,
+            usable_samples: 0,
+            last_sample: 0,
+            forwarded_payments: 0,
+            forwarded_msats: 0,
+            closed_channels: 0,
+        }
+    }
+
+    /// Percentage of samples where the sold channels were usable
+    pub fn uptime_percent(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+
+        self.usable_samples as f64 / self.samples as f64 * 100.0
+    }
+}
+
+/// A state a quote moved into and when
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteStateTransition {
+    pub state: QuoteState,
+    /// Unix time of the transition
+    pub timestamp: u64,
+    /// Operator's reason when the state was set manually
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Who caused a quote state change
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteActor {
+    /// The client creating, renewing or paying for the quote
+    Client,
+    /// The LSP acting on its own, e.g. opening the channel for a paid quote
+    Lsp,
+    /// A lightning node event, a channel becoming ready or closing
+    Node,
+    /// An operator through the management API
+    Operator,
+}
+
+/// Audit record of a quote state change, kept for investigating disputes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteAuditEntry {
+    /// Audit log sequence number, strictly increasing across quotes
+    pub seq: u64,
+    pub quote_id: Uuid,
+    /// `None` when the quote was created
+    pub from: Option<QuoteState>,
+    pub to: QuoteState,
+    /// Unix time of the change
+    pub timestamp: u64,
+    pub reason: Option<String>,
+    pub actor: QuoteActor,
+}
+
+/// Event recorded in the LSP event journal
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LspEvent {
+    /// Journal sequence number, strictly increasing
+    pub seq: u64,
+    /// Unix time the event was recorded
+    pub timestamp: u64,
+    pub kind: LspEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LspEventKind {
+    QuoteCreated {
+        quote_id: Uuid,
+    },
+    QuoteStateChanged {
+        quote_id: Uuid,
+        state: QuoteState,
+    },
+    QuoteRenewed {
+        quote_id: Uuid,
+        expected_payment_sats: u64,
+    },
+    PaymentReceived {
+        payment_hash: String,
+        amount_msat: u64,
+    },
+    PaymentFailed {
+        payment_hash: Option<String>,
+        reason: Option<String>,
+    },
+    ChannelPending {
+        channel_id: String,
+        user_channel_id: String,
+        counterparty_node_id: String,
+        funding_txid: String,
+        funding_output_index: u32,
+    },
+    ChannelReady {
+        channel_id: String,
+        user_channel_id: String,
+        counterparty_node_id: Option<String>,
+    },
+    ChannelClosed {
+        channel_id: String,
+        user_channel_id: String,
+        counterparty_node_id: Option<String>,
+        reason: Option<String>,
+    },
+    PaymentForwarded {
+        prev_user_channel_id: Option<String>,
+        next_user_channel_id: Option<String>,
+        outbound_amount_forwarded_msat: Option<u64>,
+        total_fee_earned_msat: Option<u64>,
+    },
+}
+
+#[cfg(test)]
+impl QuoteInfo {
+    /// Unpaid quote for a 100k sat channel created at `created_at`
+    pub(crate) fn for_test(id: Uuid, created_at: u64) -> Self {
+        Self {
+            id,
+            channel_size_sats: 100_000,
+            push_amount_sats: None,
+            expected_payment_sats: 1_000,
+            node_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            addr: SocketAddress::from_str("127.0.0.1:9735").unwrap(),
+            state: QuoteState::Unpaid,
+            channel_id: None,
+            mints: vec![],
+            unit: CurrencyUnit::Sat,
+            created_at,
+            payment_error: None,
+            forwarding_fee_base_msat: None,
+            forwarding_fee_ppm: None,
+            bolt12_offer: None,
+            bolt12_offer_id: None,
+            mode: QuoteMode::default(),
+            mint_fee_sats: 0,
+            htlc: None,
+            refund_lock: None,
+            sats_per_unit: None,
+            channel: None,
+            onchain_fee_sats: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl QuoteChannel {
+    /// Funded channel that closed at `closed_at`, if set
+    pub(crate) fn for_test(closed_at: Option<u64>) -> Self {
+        Self {
+            channel_id: "00".repeat(32),
+            funding_txid: "00".repeat(32),
+            funding_output_index: 0,
+            short_channel_id: None,
+            opened_at: 0,
+            ready_at: None,
+            closed_at,
+            close_reason: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sats_round_trip_through_msats() {
+        for sats in [0, 1, 546, 21_000_000 * 100_000_000, u64::MAX / 1_000] {
+            let msats = sats_to_msats(sats).unwrap();
+            assert_eq!(msats, sats * 1_000);
+            assert_eq!(msats_to_sats(msats), sats);
+        }
+    }
+
+    #[test]
+    fn sats_to_msats_overflows() {
+        assert_eq!(sats_to_msats(u64::MAX / 1_000 + 1), None);
+        assert_eq!(sats_to_msats(u64::MAX), None);
+    }
+
+    #[test]
+    fn msats_to_sats_rounds_down() {
+        assert_eq!(msats_to_sats(999), 0);
+        assert_eq!(msats_to_sats(1_999), 1);
+        assert_eq!(msats_to_sats(u64::MAX), u64::MAX / 1_000);
+    }
+}